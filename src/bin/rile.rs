@@ -1,15 +1,19 @@
 //! rile is a simple editor written in Rust.
 //!
 
-extern crate signal_hook;
-
+use std::fs;
 use std::io::Write;
+use std::panic;
+use std::path::Path;
 
+use rile::bookmark;
 use rile::buffer::Buffer;
 use rile::context::Context;
 use rile::event_loop::event_loop;
-use rile::term::{with_raw_mode, Term};
-use rile::window::refresh_screen;
+use rile::kmacro;
+use rile::signals;
+use rile::term::{self, with_raw_mode, Term};
+use rile::window::{adjust_scroll, refresh_screen};
 
 use clap::{App, Arg};
 
@@ -19,6 +23,78 @@ const PKG_AUTHORS: &'static str = env!("CARGO_PKG_AUTHORS");
 const PKG_DESCRIPTION: &'static str = env!("CARGO_PKG_DESCRIPTION");
 const PKG_GIT_COMMIT: Option<&'static str> = option_env!("GIT_COMMIT");
 
+/// A file given on the command line, with the line and column to go
+/// to, both counted from 1.
+struct FileArg {
+    filename: String,
+    line: usize,
+    column: usize,
+}
+
+/// Parse `LINE` or `LINE:COLUMN`.
+fn parse_line_column(text: &str) -> Option<(usize, usize)> {
+    let mut parts = text.splitn(2, ':');
+    let line = parts.next()?.parse().ok()?;
+    let column = match parts.next() {
+        Some(column) => column.parse().ok()?,
+        None => 1,
+    };
+    Some((line, column))
+}
+
+/// Parse the file arguments. A `+LINE[:COLUMN]` argument applies to
+/// the next file, and a file can end with `:LINE[:COLUMN]` unless a
+/// file with that exact name exists.
+fn parse_file_args<'a>(args: impl Iterator<Item = &'a str>) -> Vec<FileArg> {
+    let mut files = vec![];
+    let mut position = None;
+    for arg in args {
+        if let Some(pos) = arg.strip_prefix('+').and_then(parse_line_column) {
+            position = Some(pos);
+            continue;
+        }
+
+        let mut file = (arg, position.take().unwrap_or((1, 1)));
+        if !Path::new(arg).exists() {
+            for n in &[3, 2] {
+                let parts: Vec<&str> = arg.rsplitn(*n, ':').collect();
+                if parts.len() != *n || parts[*n - 1].is_empty() {
+                    continue;
+                }
+                let suffix = &arg[parts[*n - 1].len() + 1..];
+                if let Some(pos) = parse_line_column(suffix) {
+                    file = (parts[*n - 1], pos);
+                    break;
+                }
+            }
+        }
+
+        let (filename, (line, column)) = file;
+        files.push(FileArg {
+            filename: filename.to_string(),
+            line,
+            column,
+        });
+    }
+    files
+}
+
+/// Whether `a` and `b` name the same file, comparing the names
+/// themselves when a file doesn't exist yet.
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Open `file` in `buffer`, going to its line and column.
+fn goto_file_position(buffer: &mut Buffer, file: &FileArg) {
+    let pos =
+        buffer.line_column_position(file.line.saturating_sub(1), file.column.saturating_sub(1));
+    buffer.set_cursor(pos);
+}
+
 /// The main entry point of the editor.
 fn main() {
     let matches = App::new(PKG_NAME)
@@ -32,29 +108,69 @@ fn main() {
         )
         .author(PKG_AUTHORS)
         .about(PKG_DESCRIPTION)
-        .arg(Arg::with_name("FILE").help("Input file").index(1))
+        .arg(
+            Arg::with_name("FILE")
+                .help("Input files, as FILE, +LINE[:COLUMN] FILE or FILE:LINE[:COLUMN]")
+                .index(1)
+                .multiple(true),
+        )
         .get_matches();
 
-    let file_arg = matches.value_of("FILE");
+    let files = parse_file_args(matches.values_of("FILE").into_iter().flatten());
+    // A file given several times is opened in a single buffer, at the
+    // last position given for it.
+    let mut buffers: Vec<Buffer> = vec![];
+    for file in &files {
+        let index = buffers.iter().position(|buffer| {
+            buffer
+                .filename
+                .as_ref()
+                .is_some_and(|filename| same_file(filename, &file.filename))
+        });
+        let buffer = match index {
+            Some(index) => &mut buffers[index],
+            None => {
+                buffers.push(Buffer::from_file(&file.filename));
+                buffers.last_mut().unwrap()
+            }
+        };
+        goto_file_position(buffer, file);
+    }
+    let mut buffers = buffers.into_iter();
+
+    let mut context = Context::new(buffers.next().unwrap_or_else(|| Buffer::from_string("")));
+    for buffer in buffers {
+        context.buffer_list.add(buffer);
+    }
+
+    kmacro::load_macros(&mut context);
+    bookmark::load_bookmarks(&mut context);
 
-    let mut context = Context::new(if let Some(filename) = file_arg {
-        Buffer::from_file(filename)
-    } else {
-        Buffer::from_string("")
-    });
+    signals::register(&mut context).unwrap();
 
-    signal_hook::flag::register(signal_hook::SIGWINCH, context.was_resized.clone()).unwrap();
+    // Give the terminal back before the panic message is printed, so
+    // it can be read.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        term::restore_terminal();
+        default_hook(info);
+    }));
 
     let term = &mut Term::new();
     let context = &mut context;
 
     term.enable_alternative_screen_buffer();
+    term.enable_mouse_tracking();
+    term.enable_bracketed_paste();
 
+    adjust_scroll(term, context);
     refresh_screen(term, context).unwrap();
 
     with_raw_mode(|| while !event_loop(term, context, |_, _| {}, false).is_ok() {})
         .expect("Could not initialize the terminal to run in raw mode.");
 
+    term.disable_bracketed_paste();
+    term.disable_mouse_tracking();
     term.disable_alternative_screen_buffer();
     term.show_cursor();
     term.flush().unwrap();
@@ -9,7 +9,11 @@ use std::io::Write;
 
 use clap::{App, AppSettings, Arg, SubCommand};
 
-use rile::term::{read_key_timeout, with_raw_mode, ErasePart, Term};
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
+
+use rile::input::InputDecoder;
+use rile::term::{read_input, with_raw_mode, ErasePart, Term};
 use rile::Color;
 use rile::Key;
 
@@ -56,12 +60,21 @@ fn check_truecolor(term: &mut Term) -> io::Result<()> {
 fn check_input() {
     println!("Reading and printing keys. Press 'q' to exit.\n");
 
+    let mut decoder = InputDecoder::new();
     let _ = with_raw_mode(|| loop {
-        if let Some(key) = read_key_timeout() {
+        let mut fds = [PollFd::new(libc::STDIN_FILENO, PollFlags::POLLIN)];
+        let _ = poll(&mut fds, -1);
+
+        let mut buf = [0u8; 64];
+        let n = read_input(&mut buf);
+        print!("bytes: {:?}\r\n", &buf[..n]);
+        decoder.feed(&buf[..n]);
+
+        while let Some(key) = decoder.next_key(true) {
             print!("{} ({})\r\n", key, key.to_code());
 
             if key == Key::parse("q").unwrap() {
-                break;
+                return;
             }
         }
     });
@@ -0,0 +1,492 @@
+//! Bookmarks
+//!
+//! A bookmark is a named position in a file, saved in the user
+//! configuration so it lasts between sessions. It records the text
+//! around the position, to find it again if the file changed.
+//!
+//! The `*Bookmark List*` buffer lists the bookmarks, to jump to them,
+//! rename them or delete them.
+//!
+
+use std::fs;
+use std::path::Path;
+
+use crate::commands::Result;
+use crate::config;
+use crate::mark_ring;
+use crate::read;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Buffer, Context, Cursor, Keymap};
+
+/// The configuration file where bookmarks are saved.
+const BOOKMARKS_FILE: &str = "bookmarks";
+
+/// The name of the buffer listing the bookmarks.
+pub const BOOKMARK_LIST_BUFFER: &str = "*Bookmark List*";
+
+/// The number of lines before the bookmarks in the list buffer.
+const LIST_HEADER_LINES: usize = 2;
+
+/// The number of characters of context saved on each side of the
+/// position.
+const CONTEXT_LENGTH: usize = 16;
+
+pub struct Bookmark {
+    pub name: String,
+    pub filename: String,
+    /// The position, in characters from the beginning of the file.
+    pub position: usize,
+    /// The text after the position.
+    pub front_context: String,
+    /// The text before the position.
+    pub rear_context: String,
+}
+
+/// Escape the tabs, new lines and backslashes of `text`.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+impl Bookmark {
+    /// Return a bookmark at `pos` in `buffer`, which visits
+    /// `filename`.
+    pub fn new(name: &str, filename: &str, buffer: &Buffer, pos: Cursor) -> Bookmark {
+        let text = buffer.to_string();
+        let byte = buffer.byte_offset(pos);
+        let rear: Vec<char> = text[..byte].chars().rev().take(CONTEXT_LENGTH).collect();
+        Bookmark {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            position: buffer.char_offset(pos),
+            front_context: text[byte..].chars().take(CONTEXT_LENGTH).collect(),
+            rear_context: rear.into_iter().rev().collect(),
+        }
+    }
+
+    /// Return the bookmark as a line of the bookmarks file, its
+    /// fields separated by tabs.
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            escape(&self.name),
+            escape(&self.filename),
+            self.position,
+            escape(&self.front_context),
+            escape(&self.rear_context)
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Bookmark> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields[..] {
+            [name, filename, position, front_context, rear_context] if !name.is_empty() => {
+                Some(Bookmark {
+                    name: unescape(name),
+                    filename: unescape(filename),
+                    position: position.parse().ok()?,
+                    front_context: unescape(front_context),
+                    rear_context: unescape(rear_context),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the position of the bookmark in `buffer`.
+    ///
+    /// If the text around the saved position is not the saved context
+    /// anymore, the closest place where the text after the position
+    /// is found is used, or else the closest place after the text
+    /// before the position.
+    pub fn relocate(&self, buffer: &Buffer) -> Cursor {
+        let text = buffer.to_string();
+        let saved = buffer.offset_position(self.position);
+        let byte = buffer.byte_offset(saved);
+        if text[byte..].starts_with(&self.front_context)
+            && text[..byte].ends_with(&self.rear_context)
+        {
+            return saved;
+        }
+
+        let closest =
+            |candidates: Vec<usize>| candidates.into_iter().min_by_key(|b| b.abs_diff(byte));
+        let front = Some(&self.front_context)
+            .filter(|context| !context.is_empty())
+            .and_then(|context| {
+                closest(text.match_indices(&context[..]).map(|(b, _)| b).collect())
+            });
+        let rear = || {
+            Some(&self.rear_context)
+                .filter(|context| !context.is_empty())
+                .and_then(|context| {
+                    closest(
+                        text.match_indices(&context[..])
+                            .map(|(b, m)| b + m.len())
+                            .collect(),
+                    )
+                })
+        };
+        match front.or_else(rear) {
+            Some(byte) => buffer.offset_position(text[..byte].chars().count()),
+            None => saved,
+        }
+    }
+}
+
+pub struct BookmarkState {
+    /// The bookmarks, sorted by name.
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl BookmarkState {
+    pub fn new() -> BookmarkState {
+        BookmarkState { bookmarks: vec![] }
+    }
+
+    fn find(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|bookmark| bookmark.name == name)
+    }
+
+    /// Add `bookmark`, replacing the bookmark with the same name.
+    fn add(&mut self, bookmark: Bookmark) {
+        self.bookmarks.retain(|b| b.name != bookmark.name);
+        let index = self
+            .bookmarks
+            .iter()
+            .position(|b| b.name > bookmark.name)
+            .unwrap_or(self.bookmarks.len());
+        self.bookmarks.insert(index, bookmark);
+    }
+
+    /// Remove the bookmark `name` and return it.
+    fn remove(&mut self, name: &str) -> Option<Bookmark> {
+        let index = self.bookmarks.iter().position(|b| b.name == name)?;
+        Some(self.bookmarks.remove(index))
+    }
+}
+
+/// Load the bookmarks saved in the user configuration.
+pub fn load_bookmarks(context: &mut Context) {
+    let contents = match config::config_file(BOOKMARKS_FILE).map(fs::read_to_string) {
+        Some(Ok(contents)) => contents,
+        _ => return,
+    };
+
+    for bookmark in contents.lines().filter_map(Bookmark::from_line) {
+        context.bookmarks.add(bookmark);
+    }
+}
+
+/// Save the bookmarks to the user configuration.
+fn save_bookmarks(context: &mut Context) -> Result {
+    let file = match config::config_file(BOOKMARKS_FILE) {
+        Some(file) => file,
+        None => {
+            message(context, "Could not find the configuration directory");
+            return Err(());
+        }
+    };
+
+    let contents: String = context
+        .bookmarks
+        .bookmarks
+        .iter()
+        .map(Bookmark::to_line)
+        .collect();
+    let written = file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&file, contents));
+
+    written.map_err(|err| {
+        message(context, format!("Could not save bookmarks: {}", err));
+    })
+}
+
+/// Return the absolute path of `filename`, or `filename` if it can't
+/// be resolved.
+fn absolute_path(filename: &str) -> String {
+    fs::canonicalize(filename)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| filename.to_string())
+}
+
+/// Read the name of a bookmark in the minibuffer. An empty input is
+/// `default`, if there is one.
+fn read_bookmark_name(
+    context: &mut Context,
+    term: &mut Term,
+    prompt: &str,
+    default: Option<&str>,
+) -> std::result::Result<String, ()> {
+    let prompt = match default {
+        Some(default) => format!("{} (default {}): ", prompt, default),
+        None => format!("{}: ", prompt),
+    };
+    let input = read::read_string(term, context, &prompt, |_, _| {}, false).map_err(|_| ())?;
+    match (input.trim(), default) {
+        ("", Some(default)) => Ok(default.to_string()),
+        ("", None) => Err(()),
+        (name, _) => Ok(name.to_string()),
+    }
+}
+
+/// Return the name of the bookmark on the line of point, in the
+/// bookmark list.
+fn bookmark_at_point(context: &Context) -> Option<String> {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    if buffer.name != BOOKMARK_LIST_BUFFER {
+        return None;
+    }
+    let index = buffer.cursor().line.checked_sub(LIST_HEADER_LINES)?;
+    context
+        .bookmarks
+        .bookmarks
+        .get(index)
+        .map(|bookmark| bookmark.name.clone())
+}
+
+pub fn bookmark_set(context: &mut Context, term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let filename = match &buffer.filename {
+        Some(filename) => filename.clone(),
+        None => {
+            message(context, "Buffer not visiting a file");
+            return Err(());
+        }
+    };
+    let default = Path::new(&filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+
+    let name = read_bookmark_name(context, term, "Set bookmark", default.as_deref())?;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let bookmark = Bookmark::new(&name, &absolute_path(&filename), buffer, buffer.cursor());
+    context.bookmarks.add(bookmark);
+    save_bookmarks(context)
+}
+
+/// Visit the file of the bookmark `name` in the selected window, at
+/// the position of the bookmark.
+fn jump_to_bookmark(context: &mut Context, name: &str) -> Result {
+    let (filename, position) = match context.bookmarks.find(name) {
+        Some(bookmark) => (bookmark.filename.clone(), bookmark.position),
+        None => {
+            message(context, format!("Invalid bookmark {}", name));
+            return Err(());
+        }
+    };
+
+    let existing = context
+        .buffer_list
+        .iter()
+        .find(|(_, buffer)| {
+            buffer
+                .filename
+                .as_deref()
+                .is_some_and(|f| absolute_path(f) == filename)
+        })
+        .map(|(buffer_ref, _)| buffer_ref);
+    let buffer_ref = match existing {
+        Some(buffer_ref) => buffer_ref,
+        None if Path::new(&filename).exists() => {
+            context.buffer_list.add(Buffer::from_file(&filename))
+        }
+        None => {
+            message(
+                context,
+                format!("Cannot find bookmarked file: {}", filename),
+            );
+            return Err(());
+        }
+    };
+
+    mark_ring::push_jump(context);
+    let window_list = &mut context.window_list;
+    window_list.minibuffer_focused = false;
+    let window = &mut window_list.windows[window_list.selected];
+    if window.buffer_ref != buffer_ref {
+        window.set_buffer(buffer_ref);
+    }
+
+    let bookmark = context.bookmarks.find(name).unwrap();
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let cursor = bookmark.relocate(buffer);
+    buffer.set_cursor(cursor);
+    if buffer.char_offset(cursor) != position {
+        message(context, format!("Bookmark {} relocated", name));
+    }
+    Ok(())
+}
+
+pub fn bookmark_jump(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_bookmark_name(context, term, "Jump to bookmark", None)?;
+    jump_to_bookmark(context, &name)
+}
+
+/// Rename the bookmark `old`, reading the new name in the minibuffer.
+fn rename_bookmark(context: &mut Context, term: &mut Term, old: &str) -> Result {
+    if context.bookmarks.find(old).is_none() {
+        message(context, format!("Invalid bookmark {}", old));
+        return Err(());
+    }
+    let prompt = format!("Rename bookmark {} to", old);
+    let new = read_bookmark_name(context, term, &prompt, None)?;
+    let mut bookmark = context.bookmarks.remove(old).unwrap();
+    bookmark.name = new;
+    context.bookmarks.add(bookmark);
+    save_bookmarks(context)
+}
+
+fn delete_bookmark(context: &mut Context, name: &str) -> Result {
+    if context.bookmarks.remove(name).is_none() {
+        message(context, format!("Invalid bookmark {}", name));
+        return Err(());
+    }
+    save_bookmarks(context)
+}
+
+pub fn bookmark_rename(context: &mut Context, term: &mut Term) -> Result {
+    let old = read_bookmark_name(context, term, "Old bookmark name", None)?;
+    rename_bookmark(context, term, &old)
+}
+
+pub fn bookmark_delete(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_bookmark_name(context, term, "Delete bookmark", None)?;
+    delete_bookmark(context, &name)
+}
+
+fn bookmark_menu_map() -> Keymap {
+    let mut keymap = Keymap::special_mode();
+    keymap.define_key("RET", "bookmark-bmenu-this-window");
+    keymap.define_key("j", "bookmark-bmenu-this-window");
+    keymap.define_key("r", "bookmark-bmenu-rename");
+    keymap.define_key("d", "bookmark-bmenu-delete");
+    keymap.define_key("g", "bookmark-bmenu-list");
+    keymap
+}
+
+/// Write the list of bookmarks to the bookmark list buffer, keeping
+/// point on the same line.
+fn refresh_bookmark_list(context: &mut Context) {
+    let mut text =
+        String::from("% Bookmark                     File\n- --------                     ----\n");
+    for bookmark in &context.bookmarks.bookmarks {
+        text.push_str(&format!("  {:28} {}\n", bookmark.name, bookmark.filename));
+    }
+
+    let buffer_ref = context.buffer_list.get_or_create(BOOKMARK_LIST_BUFFER);
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let line = buffer.cursor().line;
+    buffer.set(text.trim_end());
+    buffer.read_only = true;
+    buffer.modified = false;
+    buffer.keymap = bookmark_menu_map();
+    buffer.set_cursor(buffer.line_column_position(line.max(LIST_HEADER_LINES), 0));
+}
+
+/// Show the list of bookmarks in the selected window.
+pub fn bookmark_bmenu_list(context: &mut Context, _term: &mut Term) -> Result {
+    refresh_bookmark_list(context);
+    let buffer_ref = context.buffer_list.find(BOOKMARK_LIST_BUFFER).unwrap();
+    let window_list = &mut context.window_list;
+    let window = &mut window_list.windows[window_list.selected];
+    if window.buffer_ref != buffer_ref {
+        window.set_buffer(buffer_ref);
+    }
+    Ok(())
+}
+
+/// Return the bookmark on the line of point, or report that there is
+/// none.
+fn bmenu_bookmark(context: &mut Context) -> std::result::Result<String, ()> {
+    bookmark_at_point(context).ok_or_else(|| {
+        message(context, "No bookmark on this line");
+    })
+}
+
+pub fn bookmark_bmenu_this_window(context: &mut Context, _term: &mut Term) -> Result {
+    let name = bmenu_bookmark(context)?;
+    jump_to_bookmark(context, &name)
+}
+
+pub fn bookmark_bmenu_rename(context: &mut Context, term: &mut Term) -> Result {
+    let name = bmenu_bookmark(context)?;
+    let result = rename_bookmark(context, term, &name);
+    refresh_bookmark_list(context);
+    result
+}
+
+pub fn bookmark_bmenu_delete(context: &mut Context, _term: &mut Term) -> Result {
+    let name = bmenu_bookmark(context)?;
+    let result = delete_bookmark(context, &name);
+    refresh_bookmark_list(context);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_bookmarks() {
+        let buffer = Buffer::from_string("fn main() {\n\tprintln!(\"hi\");\n}\n");
+        let pos = Cursor { line: 1, column: 1 };
+        let bookmark = Bookmark::new("print\tme", "/tmp/main.rs", &buffer, pos);
+        assert_eq!(bookmark.position, 13);
+        assert_eq!(bookmark.rear_context, "fn main() {\n\t");
+
+        let loaded = Bookmark::from_line(bookmark.to_line().trim_end()).unwrap();
+        assert_eq!(loaded.name, "print\tme");
+        assert_eq!(loaded.front_context, bookmark.front_context);
+        assert_eq!(loaded.rear_context, bookmark.rear_context);
+        assert!(Bookmark::from_line("name\tfile\tNaN\t\t").is_none());
+    }
+
+    #[test]
+    fn relocate_bookmarks_in_changed_files() {
+        let buffer = Buffer::from_string("one\ntwo\nthree\n");
+        let bookmark = Bookmark::new("b", "f", &buffer, Cursor { line: 1, column: 0 });
+        assert_eq!(bookmark.relocate(&buffer), Cursor { line: 1, column: 0 });
+
+        let changed = Buffer::from_string("zero\none\ntwo\nthree\n");
+        assert_eq!(bookmark.relocate(&changed), Cursor { line: 2, column: 0 });
+
+        // The text after the position changed, not the one before.
+        let changed = Buffer::from_string("one\nTWO\nthree\n");
+        assert_eq!(bookmark.relocate(&changed), Cursor { line: 1, column: 0 });
+    }
+}
@@ -1,42 +1,108 @@
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::VecDeque;
 use std::fs;
 
+use crate::folding;
+use crate::gutter::Fringe;
+use crate::marker::{MarkerId, MarkerList};
+use crate::mode::MajorMode;
+use crate::multiple_cursors::FakeCursor;
+use crate::overlay::{self, Overlay, OverlayId, OverlayList, Segment};
+use crate::sexp::TokenCache;
 use crate::Keymap;
 
 /// A cursor into a buffer content
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Cursor {
     pub line: usize,
     pub column: usize,
 }
 
 impl Cursor {
-    fn new() -> Cursor {
-        Cursor { line: 0, column: 0 }
+    pub fn new(line: usize, column: usize) -> Cursor {
+        Cursor { line, column }
     }
 }
 
 /// A buffer contains text that can be edited.
 pub struct Buffer {
     pub keymap: Keymap,
+    pub name: String,
     pub filename: Option<String>,
+    pub mode: MajorMode,
 
-    /// Substrings to highlight in the buffer.
-    pub highlight: Option<String>,
+    /// If set, commands are not allowed to modify the buffer.
+    pub read_only: bool,
+    /// Set when the text changes, cleared when it is saved.
+    pub modified: bool,
 
-    /// The cursor should always be a valid reference to the buffer.
-    pub cursor: Cursor,
+    /// Distance between tab stops, in columns.
+    pub tab_width: usize,
+    /// If set, indentation can insert tabs as well as spaces.
+    pub indent_tabs_mode: bool,
+    /// If set, blocks between braces can be folded.
+    pub hs_minor_mode: bool,
+    /// If set, the sections under headings can be folded.
+    pub outline_minor_mode: bool,
+
+    /// Ranges of the text with properties, like faces.
+    pub overlays: OverlayList,
+    /// Marks shown in the fringe next to lines.
+    pub fringe: Fringe,
+    /// Positions that follow the text when it is edited.
+    pub markers: MarkerList,
+
+    /// The marker of the cursor, which should always be a valid
+    /// reference to the buffer.
+    point: MarkerId,
+
+    /// The marker at the other end of the region, if it has been set.
+    mark: Option<MarkerId>,
+    /// If set, the region is highlighted. Changing the text clears it.
+    pub mark_active: bool,
+    /// Markers at the previous marks, the most recent first.
+    pub mark_ring: VecDeque<MarkerId>,
+    /// Other cursors, where editing commands are repeated after they
+    /// run at `cursor`.
+    pub fake_cursors: Vec<FakeCursor>,
+    /// The accessible portion of the buffer when it is narrowed, as
+    /// markers at its start and end. The text around it is hidden and
+    /// can't be edited.
+    pub narrowing: Option<(MarkerId, MarkerId)>,
 
     /// All lines of this buffer.
     lines: Vec<String>,
+    /// The balanced expression tokens of the lines, cleared when they
+    /// change.
+    pub tokens: TokenCache,
 }
 
 impl Buffer {
     pub fn new() -> Buffer {
+        let mut markers = MarkerList::new();
         Buffer {
-            cursor: Cursor::new(),
+            point: markers.add(Cursor::new(0, 0), false),
+            mark: None,
+            mark_active: false,
+            mark_ring: VecDeque::new(),
+            fake_cursors: vec![],
+            narrowing: None,
             lines: vec!["".to_string()],
+            tokens: RefCell::new(None),
+            name: "*scratch*".to_string(),
             filename: None,
-            highlight: None,
+            read_only: false,
+            modified: false,
+            overlays: OverlayList::new(),
+            fringe: Fringe::new(),
+            markers,
             keymap: Keymap::defaults(),
+            mode: MajorMode::fundamental(),
+            tab_width: 8,
+            indent_tabs_mode: true,
+            hs_minor_mode: false,
+            outline_minor_mode: false,
         }
     }
 
@@ -52,10 +118,19 @@ impl Buffer {
             Err(_) => String::from(""),
         };
         let mut buffer = Buffer::from_string(&content);
+        buffer.name = file.to_string();
         buffer.filename = Some(file.to_string());
+        buffer.set_mode(MajorMode::for_filename(file));
         buffer
     }
 
+    /// Change the major mode of the buffer, resetting the settings
+    /// that depend on it.
+    pub fn set_mode(&mut self, mode: MajorMode) {
+        self.indent_tabs_mode = mode.indent_tabs_mode;
+        self.mode = mode;
+    }
+
     pub fn get_line(&self, nth: usize) -> Option<&str> {
         self.lines.get(nth).map(|s| &s[..])
     }
@@ -65,6 +140,9 @@ impl Buffer {
     }
 
     pub fn get_line_mut_unchecked(&mut self, nth: usize) -> &mut String {
+        self.modified = true;
+        self.mark_active = false;
+        self.tokens.take();
         &mut self.lines[nth]
     }
 
@@ -73,27 +151,531 @@ impl Buffer {
     }
 
     pub fn insert_line_at(&mut self, nth: usize, line: String) {
-        self.lines.insert(nth, line);
+        if nth < self.lines.len() {
+            self.insert_at(
+                Cursor {
+                    line: nth,
+                    column: 0,
+                },
+                &(line + "\n"),
+            );
+        } else {
+            self.insert_at(self.end_position(), &("\n".to_string() + &line));
+        }
     }
 
+    /// Remove the line `nth`, with its newline, and return its text.
     pub fn remove_line(&mut self, nth: usize) -> String {
-        self.lines.remove(nth)
+        let start = Cursor {
+            line: nth,
+            column: 0,
+        };
+        if nth + 1 < self.lines.len() {
+            let mut text = self.delete_region(
+                start,
+                Cursor {
+                    line: nth + 1,
+                    column: 0,
+                },
+            );
+            text.pop();
+            text
+        } else if nth > 0 {
+            let previous = Cursor {
+                line: nth - 1,
+                column: self.lines[nth - 1].len(),
+            };
+            let mut text = self.delete_region(previous, self.end_position());
+            text.remove(0);
+            text
+        } else {
+            self.delete_region(start, self.end_position())
+        }
+    }
+
+    /// Return the position at the end of the buffer.
+    pub fn end_position(&self) -> Cursor {
+        let line = self.lines.len() - 1;
+        Cursor {
+            line,
+            column: self.lines[line].len(),
+        }
+    }
+
+    /// Return the start of the accessible portion of the buffer, which
+    /// is the start of the buffer unless it is narrowed.
+    pub fn point_min(&self) -> Cursor {
+        self.narrowing
+            .and_then(|(start, _)| self.markers.get(start))
+            .unwrap_or(Cursor::new(0, 0))
+    }
+
+    /// Return the end of the accessible portion of the buffer, which
+    /// is the end of the buffer unless it is narrowed.
+    pub fn point_max(&self) -> Cursor {
+        self.narrowing
+            .and_then(|(_, end)| self.markers.get(end))
+            .unwrap_or_else(|| self.end_position())
+    }
+
+    /// Restrict the accessible portion of the buffer to the text from
+    /// `start` to `end`. Text inserted at either end is accessible.
+    pub fn narrow(&mut self, start: Cursor, end: Cursor) {
+        self.widen();
+        let start = self.markers.add(start, false);
+        let end = self.markers.add(end, true);
+        self.narrowing = Some((start, end));
+    }
+
+    /// Make the whole buffer accessible again.
+    pub fn widen(&mut self) {
+        if let Some((start, end)) = self.narrowing.take() {
+            self.markers.remove(start);
+            self.markers.remove(end);
+        }
+    }
+
+    /// Return the position at `line` and the character `column`, both
+    /// starting at zero, or the nearest one if it does not exist.
+    pub fn line_column_position(&self, line: usize, column: usize) -> Cursor {
+        let line = cmp::min(line, self.lines.len() - 1);
+        let text = &self.lines[line];
+        let column = text
+            .char_indices()
+            .nth(column)
+            .map_or(text.len(), |(idx, _)| idx);
+        Cursor { line, column }
+    }
+
+    /// Return the number of characters before `pos`, counting a
+    /// newline at the end of each line.
+    pub fn char_offset(&self, pos: Cursor) -> usize {
+        let before: usize = self.lines[..pos.line]
+            .iter()
+            .map(|line| line.chars().count() + 1)
+            .sum();
+        before + self.lines[pos.line][..pos.column].chars().count()
+    }
+
+    /// Return the number of bytes before `pos`.
+    pub fn byte_offset(&self, pos: Cursor) -> usize {
+        let before: usize = self.lines[..pos.line]
+            .iter()
+            .map(|line| line.len() + 1)
+            .sum();
+        before + pos.column
+    }
+
+    /// Return the position after `offset` characters, or the end of
+    /// the buffer if it is shorter.
+    pub fn offset_position(&self, offset: usize) -> Cursor {
+        let mut offset = offset;
+        for (line, text) in self.lines.iter().enumerate() {
+            let chars = text.chars().count();
+            if offset <= chars {
+                return self.line_column_position(line, offset);
+            }
+            offset -= chars + 1;
+        }
+        self.end_position()
+    }
+
+    /// Return the pieces of `line` to display, without its invisible
+    /// text and with the strings of its overlays. Text outside of the
+    /// accessible portion is invisible.
+    pub fn display_segments(&self, line: usize) -> Vec<Segment<'_>> {
+        let text = &self.lines[line];
+        let segments = if self.overlays.is_empty() {
+            vec![Segment::Text {
+                start: 0,
+                end: text.len(),
+            }]
+        } else {
+            overlay::line_segments(&self.overlays, line, text)
+        };
+        if self.narrowing.is_none() {
+            return segments;
+        }
+
+        let (min, max) = (self.point_min(), self.point_max());
+        let from = if line == min.line { min.column } else { 0 };
+        let to = if line == max.line {
+            max.column
+        } else {
+            text.len()
+        };
+        segments
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Text { start, end } => {
+                    let (start, end) = (cmp::max(start, from), cmp::min(end, to));
+                    Some(Segment::Text { start, end }).filter(|_| start <= end)
+                }
+                Segment::String { column, .. } if column < from || column > to => None,
+                segment => Some(segment),
+            })
+            .collect()
+    }
+
+    /// Check if `line` is entirely invisible, with the newline before
+    /// it, so it takes no row on the screen.
+    pub fn is_line_hidden(&self, line: usize) -> bool {
+        if self.narrowing.is_some()
+            && (line < self.point_min().line || line > self.point_max().line)
+        {
+            return true;
+        }
+        if line == 0 {
+            return false;
+        }
+        let before = Cursor {
+            line: line - 1,
+            column: self.lines[line - 1].len(),
+        };
+        let end = Cursor {
+            line,
+            column: self.lines[line].len(),
+        };
+        self.overlays
+            .in_range(before, end)
+            .any(|overlay| overlay.invisible && overlay.start <= before && overlay.end >= end)
+    }
+
+    /// Return the line and the display column where `pos` is shown.
+    /// Positions on hidden lines are shown at the end of the visible
+    /// line before them.
+    pub fn display_position(&self, pos: Cursor) -> (usize, usize) {
+        let mut line = pos.line;
+        while line > 0 && self.is_line_hidden(line) {
+            line -= 1;
+        }
+        if line == pos.line {
+            (line, self.display_column(pos))
+        } else {
+            (line, self.line_display_width(line))
+        }
+    }
+
+    /// Return the column where `pos` is displayed, expanding tabs.
+    ///
+    /// Invisible text takes no columns, and the strings of overlays
+    /// at `pos` are displayed before it.
+    pub fn display_column(&self, pos: Cursor) -> usize {
+        let text = &self.lines[pos.line];
+        let mut width = 0;
+        for segment in self.display_segments(pos.line) {
+            let shown = match segment {
+                Segment::Text { start, end } if start < pos.column => {
+                    &text[start..cmp::min(end, pos.column)]
+                }
+                Segment::String { column, text, .. } if column <= pos.column => text,
+                _ => continue,
+            };
+            width = shown.chars().fold(width, |width, ch| {
+                next_display_column(width, ch, self.tab_width)
+            });
+        }
+        width
+    }
+
+    /// Return the number of columns needed to display `line`.
+    pub fn line_display_width(&self, line: usize) -> usize {
+        self.display_column(Cursor {
+            line,
+            column: self.lines[line].len(),
+        })
+    }
+
+    /// Return the byte column of `line` that is displayed at the
+    /// display column `column`, or the end of the line if it is
+    /// shorter.
+    pub fn column_at_display(&self, line: usize, column: usize) -> usize {
+        let text = &self.lines[line];
+        let mut width = 0;
+        for segment in self.display_segments(line) {
+            match segment {
+                Segment::Text { start, end } => {
+                    for (idx, ch) in text[start..end].char_indices() {
+                        width = next_display_column(width, ch, self.tab_width);
+                        if width > column {
+                            return start + idx;
+                        }
+                    }
+                }
+                Segment::String {
+                    column: at, text, ..
+                } => {
+                    width = text.chars().fold(width, |width, ch| {
+                        next_display_column(width, ch, self.tab_width)
+                    });
+                    if width > column {
+                        return at;
+                    }
+                }
+            }
+        }
+        text.len()
+    }
+
+    /// Return the keymaps active at point: those of the overlays at
+    /// point, by decreasing priority, those of the minor modes, and
+    /// the keymap of the buffer.
+    pub fn active_keymaps(&self) -> Vec<Keymap> {
+        let mut keymaps: Vec<Keymap> = self
+            .overlays
+            .at(self.cursor())
+            .into_iter()
+            .filter_map(|overlay| overlay.keymap.clone())
+            .collect();
+        if self.hs_minor_mode {
+            keymaps.push(folding::hs_minor_mode_map());
+        }
+        if self.outline_minor_mode {
+            keymaps.push(folding::outline_minor_mode_map());
+        }
+        keymaps.push(self.keymap.clone());
+        keymaps
+    }
+
+    /// Move point out of invisible text, to its end if point moved
+    /// forward from `from` or to its start otherwise.
+    pub fn move_out_of_invisible(&mut self, from: Cursor) {
+        let mut cursor = self.cursor();
+        let forward = cursor >= from;
+        while let Some(overlay) = self
+            .overlays
+            .in_range(cursor, cursor)
+            .find(|o| o.invisible && o.start < cursor && cursor < o.end)
+        {
+            cursor = if forward { overlay.end } else { overlay.start };
+        }
+        self.set_cursor(cursor);
+    }
+
+    /// Return the character after `pos`.
+    ///
+    /// At the end of a line this is a newline. At the end of the
+    /// buffer, or of its accessible portion, there is no character.
+    pub fn char_after(&self, pos: Cursor) -> Option<char> {
+        if self.narrowing.is_some() && pos >= self.point_max() {
+            return None;
+        }
+        let line = &self.lines[pos.line];
+        match line[pos.column..].chars().next() {
+            Some(ch) => Some(ch),
+            None if pos.line + 1 < self.lines.len() => Some('\n'),
+            None => None,
+        }
+    }
+
+    /// Return the character before `pos`.
+    pub fn char_before(&self, pos: Cursor) -> Option<char> {
+        if self.narrowing.is_some() && pos <= self.point_min() {
+            return None;
+        }
+        if pos.column > 0 {
+            self.lines[pos.line][..pos.column].chars().next_back()
+        } else if pos.line > 0 {
+            Some('\n')
+        } else {
+            None
+        }
+    }
+
+    /// Return the position after the character at `pos`.
+    pub fn forward_pos(&self, pos: Cursor) -> Option<Cursor> {
+        let ch = self.char_after(pos)?;
+        Some(if ch == '\n' {
+            Cursor {
+                line: pos.line + 1,
+                column: 0,
+            }
+        } else {
+            Cursor {
+                line: pos.line,
+                column: pos.column + ch.len_utf8(),
+            }
+        })
+    }
+
+    /// Return the position before the character preceding `pos`.
+    pub fn backward_pos(&self, pos: Cursor) -> Option<Cursor> {
+        let ch = self.char_before(pos)?;
+        Some(if ch == '\n' && pos.column == 0 {
+            Cursor {
+                line: pos.line - 1,
+                column: self.lines[pos.line - 1].len(),
+            }
+        } else {
+            Cursor {
+                line: pos.line,
+                column: pos.column - ch.len_utf8(),
+            }
+        })
+    }
+
+    /// Return the text between `start` and `end`.
+    pub fn region_text(&self, start: Cursor, end: Cursor) -> String {
+        if start.line == end.line {
+            self.lines[start.line][start.column..end.column].to_string()
+        } else {
+            let mut text = self.lines[start.line][start.column..].to_string();
+            for line in &self.lines[start.line + 1..end.line] {
+                text.push('\n');
+                text.push_str(line);
+            }
+            text.push('\n');
+            text.push_str(&self.lines[end.line][..end.column]);
+            text
+        }
+    }
+
+    /// Return the position of the cursor.
+    pub fn cursor(&self) -> Cursor {
+        self.markers.get(self.point).unwrap()
+    }
+
+    pub fn set_cursor(&mut self, pos: Cursor) {
+        self.markers.set(self.point, pos);
+    }
+
+    /// Return the position of the mark, if it has been set.
+    pub fn mark(&self) -> Option<Cursor> {
+        self.mark.and_then(|mark| self.markers.get(mark))
+    }
+
+    /// Set the mark at `pos`, or unset it.
+    pub fn set_mark(&mut self, pos: Option<Cursor>) {
+        match (self.mark, pos) {
+            (Some(mark), Some(pos)) => {
+                self.markers.set(mark, pos);
+            }
+            (None, Some(pos)) => self.mark = Some(self.markers.add(pos, false)),
+            (Some(mark), None) => {
+                self.markers.remove(mark);
+                self.mark = None;
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Add `overlay` and return its id.
+    pub fn add_overlay(&mut self, overlay: Overlay) -> OverlayId {
+        self.overlays.add(&mut self.markers, overlay)
+    }
+
+    /// Change the properties of the overlay `id` with `f`.
+    pub fn update_overlay(&mut self, id: OverlayId, f: impl FnOnce(&mut Overlay)) -> bool {
+        self.overlays.update(&mut self.markers, id, f)
+    }
+
+    /// Delete the overlay `id` and return it.
+    pub fn remove_overlay(&mut self, id: OverlayId) -> Option<Overlay> {
+        self.overlays.remove(&mut self.markers, id)
+    }
+
+    /// Delete all the overlays of `source`.
+    pub fn clear_overlays(&mut self, source: &str) {
+        self.overlays.clear(&mut self.markers, source);
+    }
+
+    /// Insert `text` at `pos` and return the position after it.
+    ///
+    /// Point, the mark, markers and overlays after `pos` move with the
+    /// text. Point and the mark stay before text inserted at them.
+    pub fn insert_at(&mut self, pos: Cursor, text: &str) -> Cursor {
+        let end = self.insert_text(pos, text);
+        self.markers.adjust_for_insert(pos, end);
+        self.overlays.follow_markers(&mut self.markers, pos);
+        end
+    }
+
+    fn insert_text(&mut self, pos: Cursor, text: &str) -> Cursor {
+        let mut new_lines = text.split('\n');
+        let first = new_lines.next().unwrap_or("");
+        let rest: Vec<&str> = new_lines.collect();
+        self.modified = true;
+        self.mark_active = false;
+        self.tokens.take();
+
+        let line = &mut self.lines[pos.line];
+        if rest.is_empty() {
+            line.insert_str(pos.column, first);
+            return Cursor {
+                line: pos.line,
+                column: pos.column + first.len(),
+            };
+        }
+
+        let tail = line.split_off(pos.column);
+        line.push_str(first);
+
+        let last_index = rest.len() - 1;
+        let mut end = pos;
+        for (i, new_line) in rest.into_iter().enumerate() {
+            let mut new_line = new_line.to_string();
+            end = Cursor {
+                line: pos.line + i + 1,
+                column: new_line.len(),
+            };
+            if i == last_index {
+                new_line.push_str(&tail);
+            }
+            self.lines.insert(end.line, new_line);
+        }
+        end
+    }
+
+    /// Insert `text` at the cursor, leaving the cursor after it.
+    pub fn insert(&mut self, text: &str) {
+        let end = self.insert_at(self.cursor(), text);
+        self.set_cursor(end);
+    }
+
+    /// Delete the text between `start` and `end` and return it.
+    ///
+    /// Narrowing is ignored, so commands must keep their edits in the
+    /// accessible portion of the buffer.
+    pub fn delete_region(&mut self, start: Cursor, end: Cursor) -> String {
+        let text = self.region_text(start, end);
+        self.modified = true;
+        self.mark_active = false;
+        self.tokens.take();
+        self.markers.adjust_for_delete(start, end);
+        self.overlays.follow_markers(&mut self.markers, start);
+        self.overlays.evaporate(&mut self.markers, start);
+        if start.line == end.line {
+            self.lines[start.line].replace_range(start.column..end.column, "");
+        } else {
+            let tail = self.lines[end.line][end.column..].to_string();
+            self.lines.drain(start.line + 1..=end.line);
+            let line = &mut self.lines[start.line];
+            line.truncate(start.column);
+            line.push_str(&tail);
+        }
+        text
     }
 
     pub fn backward_delete(&mut self) {
-        if self.cursor.column > 0 {
-            self.cursor.column -= 1;
-            self.lines[self.cursor.line].remove(self.cursor.column);
-        } else if self.cursor.line > 0 {
-            let line = self.remove_line(self.cursor.line);
-            let previous_line = self.get_line_mut_unchecked(self.cursor.line - 1);
-            let previous_line_original_length = previous_line.len();
-            previous_line.push_str(&line);
-            self.cursor.line -= 1;
-            self.cursor.column = previous_line_original_length;
+        if let Some(start) = self.backward_pos(self.cursor()) {
+            self.delete_region(start, self.cursor());
         }
     }
 
+    /// Return the region between the mark and the cursor, with the
+    /// earliest position first, limited to the accessible portion.
+    pub fn region(&self) -> Option<(Cursor, Cursor)> {
+        let cursor = self.cursor();
+        self.mark().map(|mark| {
+            let mark = mark.clamp(self.point_min(), self.point_max());
+            if mark < cursor {
+                (mark, cursor)
+            } else {
+                (cursor, mark)
+            }
+        })
+    }
+
     pub fn set<T: AsRef<str>>(&mut self, str: T) {
         // Note that we can't use .lines() here because it would
         // ignore trailing new lines.
@@ -101,32 +683,60 @@ impl Buffer {
         // .split() on the other hand will always be non-empty and it
         // will allow us to recover the original content by adding a
         // \n between each line.
+        self.markers
+            .adjust_for_delete(Cursor::new(0, 0), self.end_position());
+        self.widen();
         self.lines = str.as_ref().split('\n').map(String::from).collect();
-        self.cursor.line = 0;
-        self.cursor.column = 0;
+        self.tokens.take();
+        self.overlays.clear_all(&mut self.markers);
+        self.set_cursor(Cursor::new(0, 0));
+        self.set_mark(None);
     }
 
     pub fn truncate(&mut self) {
+        self.modified = true;
+        self.mark_active = false;
+        self.markers
+            .adjust_for_delete(Cursor::new(0, 0), self.end_position());
+        self.widen();
         self.lines.clear();
         self.lines.push("".to_string());
+        self.tokens.take();
+        self.overlays.clear_all(&mut self.markers);
+        self.set_mark(None);
     }
 
     pub fn to_string(&self) -> String {
         self.lines.join("\n")
     }
 
-    pub fn save(&self) -> Result<String, SaveError> {
+    /// Write the buffer to its file and return the name of the file.
+    ///
+    /// The buffer is no longer modified only if the write succeeds.
+    pub fn save(&mut self) -> Result<String, SaveError> {
         let contents = self.to_string();
-        if let Some(filename) = &self.filename {
-            fs::write(filename, contents)
-                .map(|_| filename.clone())
-                .map_err(SaveError::IoError)
-        } else {
-            Err(SaveError::NoFile)
-        }
+        let filename = self.filename.clone().ok_or(SaveError::NoFile)?;
+        fs::write(&filename, contents).map_err(SaveError::IoError)?;
+        self.modified = false;
+        Ok(filename)
+    }
+}
+
+/// Return the display column after displaying `ch` at `column`.
+pub fn next_display_column(column: usize, ch: char, tab_width: usize) -> usize {
+    if ch == '\t' {
+        (column / tab_width + 1) * tab_width
+    } else {
+        column + 1
     }
 }
 
+/// Return the number of columns needed to display `text`.
+pub fn display_width(text: &str, tab_width: usize) -> usize {
+    text.chars()
+        .fold(0, |column, ch| next_display_column(column, ch, tab_width))
+}
+
 pub enum SaveError {
     NoFile,
     IoError(std::io::Error),
@@ -142,10 +752,47 @@ mod tests {
         assert_eq!(buffer.to_string(), "".to_string());
     }
 
+    #[test]
+    fn insert_and_delete_multiple_lines() {
+        let mut buffer = Buffer::from_string("abc\ndef");
+        let start = Cursor { line: 0, column: 1 };
+        let end = buffer.insert_at(start, "x\ny\nz");
+        assert_eq!(buffer.to_string(), "ax\ny\nzbc\ndef");
+        assert_eq!(end, Cursor { line: 2, column: 1 });
+        assert_eq!(buffer.delete_region(start, end), "x\ny\nz");
+        assert_eq!(buffer.to_string(), "abc\ndef");
+    }
+
+    #[test]
+    fn move_positions_across_lines() {
+        let buffer = Buffer::from_string("ab\nc");
+        let eol = Cursor { line: 0, column: 2 };
+        assert_eq!(buffer.char_after(eol), Some('\n'));
+        let next = buffer.forward_pos(eol).unwrap();
+        assert_eq!(next, Cursor { line: 1, column: 0 });
+        assert_eq!(buffer.backward_pos(next), Some(eol));
+        assert_eq!(buffer.char_after(buffer.end_position()), None);
+    }
+
+    #[test]
+    fn convert_offsets_and_positions() {
+        let buffer = Buffer::from_string("añb\ncd");
+        let pos = Cursor { line: 1, column: 1 };
+        assert_eq!(buffer.char_offset(pos), 5);
+        assert_eq!(buffer.byte_offset(pos), 6);
+        assert_eq!(buffer.offset_position(5), pos);
+        assert_eq!(buffer.offset_position(2), Cursor { line: 0, column: 3 });
+        assert_eq!(buffer.offset_position(99), buffer.end_position());
+        assert_eq!(
+            buffer.line_column_position(0, 9),
+            Cursor { line: 0, column: 4 }
+        );
+    }
+
     #[test]
     fn delete_backward_char_in_middle_of_string() {
         let mut buffer = Buffer::from_string("abcde");
-        buffer.cursor.column = 3;
+        buffer.set_cursor(Cursor { line: 0, column: 3 });
         buffer.backward_delete();
         assert_eq!(buffer.to_string(), "abde");
     }
@@ -153,8 +800,7 @@ mod tests {
     #[test]
     fn delete_backward_char_first_line_char() {
         let mut buffer = Buffer::from_string("abc\nde");
-        buffer.cursor.line = 1;
-        buffer.cursor.column = 0;
+        buffer.set_cursor(Cursor { line: 1, column: 0 });
         buffer.backward_delete();
         assert_eq!(buffer.to_string(), "abcde");
     }
@@ -162,9 +808,24 @@ mod tests {
     #[test]
     fn delete_backward_char_first_char_first_line() {
         let mut buffer = Buffer::from_string("abcd");
-        buffer.cursor.line = 0;
-        buffer.cursor.column = 0;
+        buffer.set_cursor(Cursor { line: 0, column: 0 });
         buffer.backward_delete();
         assert_eq!(buffer.to_string(), "abcd");
     }
+
+    #[test]
+    fn stay_modified_when_save_fails() {
+        let dir = std::env::temp_dir().join(format!("rile-save-{}", std::process::id()));
+        let mut buffer = Buffer::from_string("abc");
+        buffer.filename = Some(dir.join("missing/file").to_string_lossy().into_owned());
+        buffer.insert("x");
+        assert!(buffer.modified);
+        assert!(buffer.save().is_err());
+        assert!(buffer.modified);
+
+        fs::create_dir_all(dir.join("missing")).unwrap();
+        assert!(buffer.save().is_ok());
+        assert!(!buffer.modified);
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
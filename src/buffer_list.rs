@@ -1,7 +1,7 @@
 use crate::minibuffer;
 use crate::Buffer;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct BufferRef(u64);
 
 impl BufferRef {
@@ -14,39 +14,74 @@ impl BufferRef {
 }
 
 pub struct BufferList {
-    main_buffer: Buffer,
+    /// All buffers but the minibuffer, in the order they were
+    /// created.
+    buffers: Vec<(BufferRef, Buffer)>,
+    next_ref: u64,
     pub minibuffer: Buffer,
 }
 
 impl BufferList {
     pub fn new(main: Buffer) -> BufferList {
         BufferList {
-            main_buffer: main,
+            buffers: vec![(BufferRef::main_window(), main)],
+            next_ref: 2,
             minibuffer: minibuffer::new(),
         }
     }
 
     pub fn resolve_ref(&self, buffer_ref: BufferRef) -> &Buffer {
-        if buffer_ref.0 == 0 {
-            &self.main_buffer
-        } else if buffer_ref.0 == 1 {
-            &self.minibuffer
-        } else {
-            panic!("Can't resolve a buffer that does not exist anymore.")
+        if buffer_ref == BufferRef::minibuffer_window() {
+            return &self.minibuffer;
+        }
+        match self.buffers.iter().find(|(r, _)| *r == buffer_ref) {
+            Some((_, buffer)) => buffer,
+            None => panic!("Can't resolve a buffer that does not exist anymore."),
         }
     }
 
     pub fn resolve_ref_as_mut(&mut self, buffer_ref: BufferRef) -> &mut Buffer {
-        if buffer_ref.0 == 0 {
-            &mut self.main_buffer
-        } else if buffer_ref.0 == 1 {
-            &mut self.minibuffer
-        } else {
-            panic!("Can't resolve a buffer that does not exist anymore.")
+        if buffer_ref == BufferRef::minibuffer_window() {
+            return &mut self.minibuffer;
+        }
+        match self.buffers.iter_mut().find(|(r, _)| *r == buffer_ref) {
+            Some((_, buffer)) => buffer,
+            None => panic!("Can't resolve a buffer that does not exist anymore."),
         }
     }
 
     pub fn get_main_buffer(&self) -> &Buffer {
-        &self.main_buffer
+        self.resolve_ref(BufferRef::main_window())
+    }
+
+    /// Add `buffer` to the list and return a reference to it.
+    pub fn add(&mut self, buffer: Buffer) -> BufferRef {
+        let buffer_ref = BufferRef(self.next_ref);
+        self.next_ref += 1;
+        self.buffers.push((buffer_ref, buffer));
+        buffer_ref
+    }
+
+    /// Find the buffer called `name`.
+    pub fn find(&self, name: &str) -> Option<BufferRef> {
+        self.buffers
+            .iter()
+            .find(|(_, buffer)| buffer.name == name)
+            .map(|(r, _)| *r)
+    }
+
+    /// Return the buffer called `name`, creating an empty one if
+    /// there is none.
+    pub fn get_or_create(&mut self, name: &str) -> BufferRef {
+        self.find(name).unwrap_or_else(|| {
+            let mut buffer = Buffer::new();
+            buffer.name = name.to_string();
+            self.add(buffer)
+        })
+    }
+
+    /// Iterate over all buffers but the minibuffer.
+    pub fn iter(&self) -> impl Iterator<Item = (BufferRef, &Buffer)> {
+        self.buffers.iter().map(|(r, buffer)| (*r, buffer))
     }
 }
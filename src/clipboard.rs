@@ -0,0 +1,208 @@
+//! Clipboard
+//!
+//! Terminals that support OSC 52 escape sequences let programs set
+//! and read the system clipboard, even over SSH. Killed text can be
+//! sent to the clipboard, and yanking can read it first, so text
+//! copied in other programs can be yanked.
+//!
+
+use std::io::Write;
+use std::time::Duration;
+
+use crate::commands::{self, Result};
+use crate::event_loop;
+use crate::read;
+use crate::term::Term;
+use crate::window::message;
+use crate::Context;
+
+/// How long to wait for the terminal to reply with the clipboard.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub struct ClipboardState {
+    /// If set, killed text is also sent to the clipboard.
+    pub select_enable_clipboard: bool,
+    /// If set, yanking reads the clipboard first, if the terminal
+    /// replies.
+    pub query_on_yank: bool,
+    /// The largest text, in bytes, sent to the clipboard. Terminals
+    /// ignore sequences that are too long.
+    pub max_size: usize,
+
+    /// The last text sent to or read from the clipboard.
+    last: Option<String>,
+    /// Set when the terminal did not reply to a query, so it is not
+    /// asked again.
+    unsupported: bool,
+}
+
+impl ClipboardState {
+    pub fn new() -> ClipboardState {
+        ClipboardState {
+            select_enable_clipboard: true,
+            query_on_yank: false,
+            max_size: 100_000,
+            last: None,
+            unsupported: false,
+        }
+    }
+}
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Decode `text`, or return None if it is not valid base64.
+pub fn decode_base64(text: &[u8]) -> Option<Vec<u8>> {
+    let text: Vec<u8> = text.iter().copied().filter(|b| *b != b'=').collect();
+    let mut bytes = vec![];
+    for chunk in text.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, b) in chunk.iter().enumerate() {
+            let value = BASE64.iter().position(|c| c == b)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Send `text` to the clipboard. Return false if it is too large.
+fn send(term: &mut Term, state: &mut ClipboardState, text: &str) -> bool {
+    state.last = Some(text.to_string());
+    if text.len() > state.max_size {
+        return false;
+    }
+    write!(term, "\x1b]52;c;{}\x07", encode_base64(text.as_bytes())).unwrap();
+    term.flush().unwrap();
+    true
+}
+
+/// Send the most recent kill to the clipboard, if it changed and
+/// `select_enable_clipboard` is set.
+pub fn sync_kill_ring(term: &mut Term, context: &mut Context) {
+    let state = &mut context.clipboard;
+    if !state.select_enable_clipboard {
+        return;
+    }
+    if let Some(text) = context.kill_ring.current() {
+        if state.last.as_deref() != Some(text) {
+            send(term, state, text);
+        }
+    }
+}
+
+/// Read the clipboard, if the terminal replies.
+pub fn read_clipboard(term: &mut Term, context: &mut Context) -> Option<String> {
+    if context.clipboard.unsupported {
+        return None;
+    }
+    write!(term, "\x1b]52;c;?\x07").unwrap();
+    term.flush().unwrap();
+
+    match event_loop::read_clipboard_reply(term, context, QUERY_TIMEOUT) {
+        Some(text) => {
+            context.clipboard.last = Some(text.clone());
+            Some(text)
+        }
+        None => {
+            context.clipboard.unsupported = true;
+            None
+        }
+    }
+}
+
+/// Save the region in the kill ring and send it to the clipboard,
+/// even if `select_enable_clipboard` is not set.
+pub fn clipboard_kill_ring_save(context: &mut Context, term: &mut Term) -> Result {
+    let (start, end) = commands::region_or_message(context)?;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let text = buffer.region_text(start, end);
+    context.kill_ring.kill_new(text.clone());
+    if send(term, &mut context.clipboard, &text) {
+        Ok(())
+    } else {
+        message(context, "Text is too large for the clipboard");
+        Err(())
+    }
+}
+
+pub fn toggle_select_enable_clipboard(context: &mut Context, _term: &mut Term) -> Result {
+    let state = &mut context.clipboard;
+    state.select_enable_clipboard = !state.select_enable_clipboard;
+    if state.select_enable_clipboard {
+        message(context, "Kills are sent to the clipboard");
+    } else {
+        message(context, "Kills are not sent to the clipboard");
+    }
+    Ok(())
+}
+
+pub fn toggle_clipboard_query_on_yank(context: &mut Context, _term: &mut Term) -> Result {
+    let state = &mut context.clipboard;
+    state.query_on_yank = !state.query_on_yank;
+    if state.query_on_yank {
+        // Give a terminal that did not reply before another chance.
+        state.unsupported = false;
+        message(context, "Yanking reads the clipboard first");
+    } else {
+        message(context, "Yanking does not read the clipboard");
+    }
+    Ok(())
+}
+
+pub fn set_clipboard_max_size(context: &mut Context, term: &mut Term) -> Result {
+    let prompt = format!(
+        "Clipboard max size (bytes, {}): ",
+        context.clipboard.max_size
+    );
+    let input = read::read_string(term, context, &prompt, |_, _| {}, false).map_err(|_| ())?;
+    match input.trim().parse() {
+        Ok(size) => {
+            context.clipboard.max_size = size;
+            Ok(())
+        }
+        _ => {
+            message(context, format!("Invalid size: {}", input));
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trip() {
+        assert_eq!(encode_base64(b"rile"), "cmlsZQ==");
+        assert_eq!(encode_base64(b"ril"), "cmls");
+        for text in ["", "a", "ab", "abc", "héllo\nworld"] {
+            let encoded = encode_base64(text.as_bytes());
+            assert_eq!(decode_base64(encoded.as_bytes()).unwrap(), text.as_bytes());
+        }
+        assert_eq!(decode_base64(b"c!ls"), None);
+    }
+}
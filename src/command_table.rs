@@ -0,0 +1,977 @@
+//! The table of named commands.
+//!
+//! Every command that can be invoked by name with `M-x` must be
+//! listed here, with its documentation. In the documentation, names
+//! of other commands between backquotes become cross-references in
+//! the help buffer.
+//!
+
+use crate::keymap::CommandHandler;
+use crate::{
+    bookmark, clipboard, commands, folding, help, kmacro, mark_ring, messages, minibuffer,
+    modeline, mouse, multiple_cursors, narrowing, rectangle, register, signals,
+};
+
+pub struct Command {
+    pub name: &'static str,
+    pub handler: CommandHandler,
+    pub doc: &'static str,
+    /// If set, the command is refused in a read-only buffer.
+    pub modifies_buffer: bool,
+    /// If set, the command is repeated at every fake cursor. The
+    /// others, like window commands or commands that read from the
+    /// minibuffer, run once.
+    pub run_for_all_cursors: bool,
+}
+
+impl Command {
+    const fn modifies_buffer(mut self) -> Command {
+        self.modifies_buffer = true;
+        self
+    }
+
+    const fn run_for_all_cursors(mut self) -> Command {
+        self.run_for_all_cursors = true;
+        self
+    }
+}
+
+const fn command(name: &'static str, handler: CommandHandler, doc: &'static str) -> Command {
+    Command {
+        name,
+        handler,
+        doc,
+        modifies_buffer: false,
+        run_for_all_cursors: false,
+    }
+}
+
+static COMMANDS: &[Command] = &[
+    command(
+        "move-beginning-of-line",
+        commands::move_beginning_of_line,
+        "Move point to the first non-whitespace character of the line.\n\
+         If point is already there or before it, move to the beginning of the line.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "move-end-of-line",
+        commands::move_end_of_line,
+        "Move point to the end of the current line.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "forward-char",
+        commands::forward_char,
+        "Move point one character forward.\n\
+         At the end of a line, move to the beginning of the next one.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-char",
+        commands::backward_char,
+        "Move point one character backward.\n\
+         At the beginning of a line, move to the end of the previous one.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "next-line",
+        commands::next_line,
+        "Move point vertically down one line, keeping the goal column.\n\
+         In `visual-line-mode`, move by screen rows instead of buffer lines.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "previous-line",
+        commands::previous_line,
+        "Move point vertically up one line, keeping the goal column.\n\
+         In `visual-line-mode`, move by screen rows instead of buffer lines.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "delete-char",
+        commands::delete_char,
+        "Delete the character after point.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "delete-backward-char",
+        commands::delete_backward_char,
+        "Delete the character before point.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "kill-line",
+        commands::kill_line,
+        "Kill the rest of the current line.\n\
+         At the end of a line, kill the newline instead. The killed text\n\
+         can be inserted back with `yank`.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "yank",
+        commands::yank,
+        "Insert the last stretch of killed text at point.\n\
+         The mark is set at the beginning of the inserted text.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "xterm-paste",
+        commands::xterm_paste,
+        "Insert the text pasted in the terminal, without running the\n\
+         commands of its characters, and save it in the kill ring.\n\
+         The mark is set at the beginning of the inserted text.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "forward-word",
+        commands::forward_word,
+        "Move point to the end of the next word.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-word",
+        commands::backward_word,
+        "Move point to the beginning of the previous word.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "kill-word",
+        commands::kill_word,
+        "Kill characters forward until the end of the next word.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "backward-kill-word",
+        commands::backward_kill_word,
+        "Kill characters backward until the beginning of the previous word.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "mark-word",
+        commands::mark_word,
+        "Set the mark at the end of the next word.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "forward-sentence",
+        commands::forward_sentence,
+        "Move point to the end of the current sentence.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-sentence",
+        commands::backward_sentence,
+        "Move point to the beginning of the current sentence.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "forward-paragraph",
+        commands::forward_paragraph,
+        "Move point to the blank line after the current paragraph.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-paragraph",
+        commands::backward_paragraph,
+        "Move point to the blank line before the current paragraph.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "mark-paragraph",
+        commands::mark_paragraph,
+        "Put point at the beginning of the current paragraph and the mark at its end.",
+    ),
+    command(
+        "forward-sexp",
+        commands::forward_sexp,
+        "Move point after the next balanced expression.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-sexp",
+        commands::backward_sexp,
+        "Move point to the beginning of the previous balanced expression.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "forward-list",
+        commands::forward_list,
+        "Move point after the next group of expressions between delimiters.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-list",
+        commands::backward_list,
+        "Move point to the beginning of the previous group of expressions between delimiters.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "up-list",
+        commands::up_list,
+        "Move point after the end of the expression containing it.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "backward-up-list",
+        commands::backward_up_list,
+        "Move point to the beginning of the expression containing it.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "down-list",
+        commands::down_list,
+        "Move point inside the next expression between delimiters.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "kill-sexp",
+        commands::kill_sexp,
+        "Kill the balanced expression following point.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "mark-sexp",
+        commands::mark_sexp,
+        "Set the mark at the end of the next balanced expression.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "show-paren-mode",
+        commands::show_paren_mode,
+        "Toggle the highlighting of the delimiter matching the one at point.",
+    ),
+    command(
+        "transpose-chars",
+        commands::transpose_chars,
+        "Interchange the characters around point, moving forward one character.\n\
+         At the end of a line, interchange the two previous characters.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "transpose-words",
+        commands::transpose_words,
+        "Interchange the words around point, leaving point after them.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "transpose-lines",
+        commands::transpose_lines,
+        "Exchange the current line and the previous one, leaving point after both.",
+    )
+    .modifies_buffer(),
+    command(
+        "upcase-word",
+        commands::upcase_word,
+        "Convert the following word to upper case, moving over it.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "downcase-word",
+        commands::downcase_word,
+        "Convert the following word to lower case, moving over it.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "capitalize-word",
+        commands::capitalize_word,
+        "Capitalize the following word, moving over it.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command("newline", commands::newline, "Insert a newline at point.")
+        .modifies_buffer()
+        .run_for_all_cursors(),
+    command(
+        "newline-and-indent",
+        commands::newline_and_indent,
+        "Insert a newline, then indent the new line according to the major mode.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "indent-according-to-mode",
+        commands::indent_according_to_mode,
+        "Indent the current line as the major mode of the buffer says.",
+    )
+    .modifies_buffer()
+    .run_for_all_cursors(),
+    command(
+        "indent-region",
+        commands::indent_region,
+        "Indent each line of the region according to the major mode.",
+    )
+    .modifies_buffer(),
+    command(
+        "indent-rigidly",
+        commands::indent_rigidly,
+        "Shift the lines of the region interactively.\n\
+         Use C-b and C-f to remove or add a column of indentation,\n\
+         and any other key to stop.",
+    )
+    .modifies_buffer(),
+    command(
+        "set-tab-width",
+        commands::set_tab_width,
+        "Set the distance between tab stops for the current buffer.",
+    ),
+    command(
+        "indent-tabs-mode",
+        commands::indent_tabs_mode,
+        "Toggle the use of tabs for indentation in the current buffer.",
+    ),
+    command(
+        "save-buffer",
+        commands::save_buffer,
+        "Save the current buffer to its file.\n\n\
+         If the file cannot be written because of its permissions, offer\n\
+         to save it somewhere else with `write-file`. If its directory\n\
+         does not exist, offer to create it. On any other error the\n\
+         buffer stays modified.",
+    ),
+    command(
+        "write-file",
+        commands::write_file,
+        "Write the current buffer to a file read in the minibuffer.\n\n\
+         The buffer visits that file from then on, and is renamed after\n\
+         it.",
+    ),
+    command(
+        "scroll-up-command",
+        commands::next_screen,
+        "Scroll the text of the window up by almost a full screen.\n\
+         See also `scroll-down-command`.",
+    ),
+    command(
+        "scroll-down-command",
+        commands::previous_screen,
+        "Scroll the text of the window down by almost a full screen.\n\
+         See also `scroll-up-command`.",
+    ),
+    command(
+        "scroll-left",
+        commands::scroll_left,
+        "Scroll the text of the window to the left by almost its width.\n\
+         This only works when long lines are truncated, see `toggle-truncate-lines`.",
+    ),
+    command(
+        "scroll-right",
+        commands::scroll_right,
+        "Scroll the text of the window to the right by almost its width.\n\
+         See also `scroll-left`.",
+    ),
+    command(
+        "toggle-truncate-lines",
+        commands::toggle_truncate_lines,
+        "Toggle between truncating and wrapping long lines in the current window.",
+    ),
+    command(
+        "visual-line-mode",
+        commands::visual_line_mode,
+        "Toggle moving by screen rows rather than buffer lines in the current window.\n\
+         Enabling it wraps long lines.",
+    ),
+    command(
+        "display-line-numbers-mode",
+        commands::display_line_numbers_mode,
+        "Toggle the display of line numbers in the selected window.\n\
+         The numbers are absolute, relative to the line of point or both,\n\
+         as set by `set-line-numbers-type`.",
+    ),
+    command(
+        "set-line-numbers-type",
+        commands::set_line_numbers_type,
+        "Set how line numbers are displayed in the selected window.\n\
+         With absolute numbers, lines are numbered from 1. With relative\n\
+         numbers, each line shows its distance to the line of point, and\n\
+         with hybrid numbers the line of point shows its absolute number.\n\
+         This also displays line numbers.",
+    ),
+    command(
+        "split-window-below",
+        commands::split_window_below,
+        "Split the selected window in two, one above the other.",
+    ),
+    command(
+        "other-window",
+        commands::other_window,
+        "Select the next window.",
+    ),
+    command(
+        "delete-window",
+        commands::delete_window,
+        "Delete the selected window.",
+    ),
+    command(
+        "delete-other-windows",
+        commands::delete_other_windows,
+        "Make the selected window fill the whole frame.",
+    ),
+    command(
+        "quit-window",
+        commands::quit_window,
+        "Quit the selected window.\n\
+         Go back to the buffer shown before in that window, or delete it if\n\
+         it was created to show its buffer.",
+    ),
+    command(
+        "beginning-of-buffer",
+        commands::beginning_of_buffer,
+        "Move point to the beginning of the buffer.",
+    ),
+    command(
+        "end-of-buffer",
+        commands::end_of_buffer,
+        "Move point to the end of the buffer.",
+    ),
+    command(
+        "goto-line",
+        commands::goto_line,
+        "Go to a line, read in the minibuffer.\n\
+         Lines are counted from 1. A line past the end of the buffer goes\n\
+         to the last line.",
+    ),
+    command(
+        "goto-char",
+        commands::goto_char,
+        "Go to a position, read in the minibuffer.\n\
+         The position is a number of characters, counted from 1 at the\n\
+         beginning of the buffer.",
+    ),
+    command(
+        "what-line",
+        commands::what_line,
+        "Show the line number of point.",
+    ),
+    command(
+        "what-cursor-position",
+        commands::what_cursor_position,
+        "Show the character after point and where point is.\n\
+         The message has the code of the character in decimal, octal and\n\
+         hex, and the position of point in characters and bytes, with its\n\
+         line and column.",
+    ),
+    command(
+        "switch-to-buffer",
+        commands::switch_to_buffer,
+        "Show a buffer, read in the minibuffer, in the selected window.\n\
+         The default is the buffer shown before in the window. Only\n\
+         existing buffers can be shown.",
+    ),
+    command(
+        "save-buffers-kill-terminal",
+        commands::kill_rile,
+        "Exit the editor.",
+    ),
+    command(
+        "suspend-frame",
+        signals::suspend_frame,
+        "Stop the editor and go back to the shell.\n\
+         The screen is redrawn when the editor is continued, for example\n\
+         with the fg shell command.",
+    ),
+    command(
+        "auto-save-mode",
+        signals::auto_save_mode,
+        "Toggle saving the modified buffers when the editor is idle.\n\
+         Each buffer visiting a file is saved to a file next to it, named\n\
+         like the file between # signs, as when the editor is terminated.",
+    ),
+    command(
+        "isearch-forward",
+        commands::isearch_forward,
+        "Highlight the occurrences of the text typed in the minibuffer.",
+    ),
+    command(
+        "execute-extended-command",
+        commands::m_x,
+        "Read the name of a command or named keyboard macro and run it.",
+    ),
+    command(
+        "keyboard-quit",
+        commands::keyboard_quit,
+        "Cancel the current command or prompt.\n\
+         This also ends the definition of a keyboard macro, and removes\n\
+         the fake cursors of `mc/edit-lines` and the like.",
+    ),
+    command(
+        "set-mark-command",
+        commands::set_mark_command,
+        "Set the mark at point.\n\
+         The previous mark is saved in the mark ring of the buffer, to go\n\
+         back to it with `pop-to-mark-command`.",
+    )
+    .run_for_all_cursors(),
+    command(
+        "pop-to-mark-command",
+        mark_ring::pop_to_mark_command,
+        "Move point to the mark, and set the mark to the previous one.\n\
+         Repeating it cycles through the mark ring of the buffer.",
+    ),
+    command(
+        "pop-global-mark",
+        mark_ring::pop_global_mark,
+        "Go to the most recent position of the global mark ring.\n\
+         The global mark ring saves the marks set in all buffers, so this\n\
+         can switch buffer. Repeating it cycles through the ring.",
+    ),
+    command(
+        "jump-backward",
+        mark_ring::jump_backward,
+        "Go back to where the previous long jump started.\n\
+         Long jumps are moves like `beginning-of-buffer` or `goto-line`.",
+    ),
+    command(
+        "jump-forward",
+        mark_ring::jump_forward,
+        "Go forward to where `jump-backward` came from.",
+    ),
+    command(
+        "copy-to-register",
+        register::copy_to_register,
+        "Copy the text of the region to a register.\n\
+         Insert it back with `insert-register`.",
+    ),
+    command(
+        "insert-register",
+        register::insert_register,
+        "Insert the text or the number of a register at point.\n\
+         Point stays before the text, and the mark is set after it.",
+    )
+    .modifies_buffer(),
+    command(
+        "point-to-register",
+        register::point_to_register,
+        "Save point in a register, to go back to it with `jump-to-register`.\n\
+         The position follows the edits of the buffer.",
+    ),
+    command(
+        "jump-to-register",
+        register::jump_to_register,
+        "Go to the position saved in a register, in its buffer, or restore\n\
+         the window configuration saved in it.",
+    ),
+    command(
+        "number-to-register",
+        register::number_to_register,
+        "Store a number, read in the minibuffer, in a register.",
+    ),
+    command(
+        "increment-register",
+        register::increment_register,
+        "Add 1 to the number in a register.\n\
+         If the register contains text, append the region to it.",
+    ),
+    command(
+        "window-configuration-to-register",
+        register::window_configuration_to_register,
+        "Save the layout of the windows and their points in a register.\n\
+         Restore it with `jump-to-register`.",
+    ),
+    command(
+        "view-register",
+        register::view_register,
+        "Show the contents of a register in another window.",
+    ),
+    command(
+        "list-registers",
+        register::list_registers,
+        "Show all the non-empty registers in another window.",
+    ),
+    command(
+        "kill-rectangle",
+        rectangle::kill_rectangle,
+        "Delete the rectangle between point and the mark, and save it.\n\
+         Insert it back with `yank-rectangle`.",
+    )
+    .modifies_buffer(),
+    command(
+        "copy-rectangle-as-kill",
+        rectangle::copy_rectangle_as_kill,
+        "Save the rectangle between point and the mark, to insert it with\n\
+         `yank-rectangle`.",
+    ),
+    command(
+        "delete-rectangle",
+        rectangle::delete_rectangle,
+        "Delete the rectangle between point and the mark, without saving it.",
+    )
+    .modifies_buffer(),
+    command(
+        "yank-rectangle",
+        rectangle::yank_rectangle,
+        "Insert the last killed rectangle with its upper left corner at point.",
+    )
+    .modifies_buffer(),
+    command(
+        "open-rectangle",
+        rectangle::open_rectangle,
+        "Insert blank space in the rectangle between point and the mark,\n\
+         shifting its text to the right.",
+    )
+    .modifies_buffer(),
+    command(
+        "clear-rectangle",
+        rectangle::clear_rectangle,
+        "Replace the text of the rectangle between point and the mark with\n\
+         spaces.",
+    )
+    .modifies_buffer(),
+    command(
+        "string-rectangle",
+        rectangle::string_rectangle,
+        "Replace the rectangle between point and the mark with a string, read\n\
+         in the minibuffer, on each line.",
+    )
+    .modifies_buffer(),
+    command(
+        "rectangle-number-lines",
+        rectangle::rectangle_number_lines,
+        "Insert numbers from 1 at the left edge of the rectangle between point\n\
+         and the mark.",
+    )
+    .modifies_buffer(),
+    command(
+        "rectangle-mark-mode",
+        rectangle::rectangle_mark_mode,
+        "Toggle highlighting the region as a rectangle.\n\
+         The rectangle commands disable it.",
+    ),
+    command(
+        "narrow-to-region",
+        narrowing::narrow_to_region,
+        "Restrict editing in the buffer to the region.\n\
+         The rest of the text is hidden until `widen` is used.",
+    ),
+    command(
+        "narrow-to-defun",
+        narrowing::narrow_to_defun,
+        "Restrict editing in the buffer to the definition around point.",
+    ),
+    command(
+        "widen",
+        narrowing::widen,
+        "Remove the restrictions of `narrow-to-region` from the buffer.",
+    ),
+    command(
+        "hs-minor-mode",
+        folding::hs_minor_mode,
+        "Toggle folding the blocks between braces in the current buffer.\n\
+         Folded lines are shown as an ellipsis, and skipped by `next-line`.",
+    ),
+    command(
+        "hs-toggle-hiding",
+        folding::hs_toggle_hiding,
+        "Hide the block at point, or show it if it is hidden.",
+    ),
+    command(
+        "hs-hide-block",
+        folding::hs_hide_block,
+        "Hide the block that starts on the line of point, or that contains it.",
+    ),
+    command(
+        "hs-show-block",
+        folding::hs_show_block,
+        "Show the hidden block at point.",
+    ),
+    command(
+        "hs-hide-all",
+        folding::hs_hide_all,
+        "Hide all the top-level blocks of the buffer.",
+    ),
+    command(
+        "hs-show-all",
+        folding::hs_show_all,
+        "Show all the hidden blocks of the buffer.",
+    ),
+    command(
+        "outline-minor-mode",
+        folding::outline_minor_mode,
+        "Toggle folding the sections under headings in the current buffer.\n\
+         Headings start with `#` in Markdown files and `*` in other files.",
+    ),
+    command(
+        "outline-toggle-subtree",
+        folding::outline_toggle_subtree,
+        "Hide the section of the heading at point, or show it if it is hidden.",
+    ),
+    command(
+        "outline-hide-subtree",
+        folding::outline_hide_subtree,
+        "Hide the section of the heading at point, with its subheadings.",
+    ),
+    command(
+        "outline-show-subtree",
+        folding::outline_show_subtree,
+        "Show the section of the heading at point, with its subheadings.",
+    ),
+    command(
+        "outline-hide-body",
+        folding::outline_hide_body,
+        "Hide the text under every heading, leaving only the headings.",
+    ),
+    command(
+        "outline-show-all",
+        folding::outline_show_all,
+        "Show all the hidden sections of the buffer.",
+    ),
+    command(
+        "mc/mark-next-like-this",
+        multiple_cursors::mark_next_like_this,
+        "Add a cursor at the next occurrence of the region, or of the word at point.\n\
+         Editing and motion commands run at every cursor, each with its own\n\
+         mark and kill ring. `keyboard-quit` removes the added cursors.",
+    ),
+    command(
+        "mc/mark-previous-like-this",
+        multiple_cursors::mark_previous_like_this,
+        "Add a cursor at the previous occurrence of the region, or of the word at point.",
+    ),
+    command(
+        "mc/edit-lines",
+        multiple_cursors::edit_lines,
+        "Add a cursor on each line of the region, at the column of point.",
+    ),
+    command(
+        "mc/add-cursor-on-click",
+        multiple_cursors::add_cursor_on_click,
+        "Add a cursor where the mouse was clicked, or remove the cursor there.",
+    ),
+    command(
+        "bookmark-set",
+        bookmark::bookmark_set,
+        "Set a bookmark at point, with a name read in the minibuffer.\n\
+         Bookmarks are saved in the user configuration. A bookmark with the\n\
+         same name is replaced.",
+    ),
+    command(
+        "bookmark-jump",
+        bookmark::bookmark_jump,
+        "Visit the file of a bookmark and go to its position.\n\
+         If the file changed, the position is found again from the text\n\
+         around it.",
+    ),
+    command(
+        "bookmark-rename",
+        bookmark::bookmark_rename,
+        "Give a new name to a bookmark.",
+    ),
+    command(
+        "bookmark-delete",
+        bookmark::bookmark_delete,
+        "Delete a bookmark.",
+    ),
+    command(
+        "bookmark-bmenu-list",
+        bookmark::bookmark_bmenu_list,
+        "Show the list of bookmarks.\n\
+         In the list, `bookmark-bmenu-this-window` jumps to the bookmark of\n\
+         the line, `bookmark-bmenu-rename` renames it and\n\
+         `bookmark-bmenu-delete` deletes it.",
+    ),
+    command(
+        "bookmark-bmenu-this-window",
+        bookmark::bookmark_bmenu_this_window,
+        "Jump to the bookmark of the line, in the selected window.",
+    ),
+    command(
+        "bookmark-bmenu-rename",
+        bookmark::bookmark_bmenu_rename,
+        "Rename the bookmark of the line.",
+    ),
+    command(
+        "bookmark-bmenu-delete",
+        bookmark::bookmark_bmenu_delete,
+        "Delete the bookmark of the line.",
+    ),
+    command(
+        "exit-minibuffer",
+        minibuffer::minibuffer_complete,
+        "Terminate the input of the minibuffer.",
+    ),
+    command(
+        "kmacro-start-macro",
+        kmacro::kmacro_start_macro,
+        "Start recording a keyboard macro.\n\
+         End it with `kmacro-end-macro`.",
+    ),
+    command(
+        "kmacro-end-macro",
+        kmacro::kmacro_end_macro,
+        "Stop recording the keyboard macro.\n\
+         Run it with `kmacro-call-macro`.",
+    ),
+    command(
+        "kmacro-call-macro",
+        kmacro::kmacro_call_macro,
+        "Run the last keyboard macro.\n\
+         Typing the last key of the binding again runs it once more.",
+    ),
+    command(
+        "kmacro-insert-counter",
+        kmacro::kmacro_insert_counter,
+        "Insert the keyboard macro counter and increment it.",
+    )
+    .modifies_buffer(),
+    command(
+        "kmacro-set-counter",
+        kmacro::kmacro_set_counter,
+        "Set the value of the keyboard macro counter.",
+    ),
+    command(
+        "kmacro-add-counter",
+        kmacro::kmacro_add_counter,
+        "Add a number to the keyboard macro counter.",
+    ),
+    command(
+        "kmacro-set-format",
+        kmacro::kmacro_set_format,
+        "Set the format used by `kmacro-insert-counter`, where %d is the counter.",
+    ),
+    command(
+        "apply-macro-to-region-lines",
+        kmacro::apply_macro_to_region_lines,
+        "Run the last keyboard macro at the beginning of each line of the region.",
+    ),
+    command(
+        "kmacro-name-last-macro",
+        kmacro::kmacro_name_last_macro,
+        "Give a name to the last keyboard macro.\n\
+         It can then be run with `execute-extended-command`.",
+    ),
+    command(
+        "kmacro-save-macros",
+        kmacro::kmacro_save_macros,
+        "Save the named keyboard macros to the configuration directory.",
+    ),
+    command(
+        "describe-key",
+        help::describe_key,
+        "Read a key sequence and show the documentation of the command it runs.",
+    ),
+    command(
+        "describe-function",
+        help::describe_function,
+        "Read the name of a command and show its documentation.",
+    ),
+    command(
+        "describe-bindings",
+        help::describe_bindings,
+        "Show a list of the key bindings of the current buffer.",
+    ),
+    command(
+        "where-is",
+        help::where_is,
+        "Read the name of a command and show the keys that run it.",
+    ),
+    command(
+        "apropos-command",
+        help::apropos_command,
+        "Show the commands whose name contains the given text.",
+    ),
+    command(
+        "describe-mode",
+        help::describe_mode,
+        "Show the documentation of the major mode and the enabled minor modes.",
+    ),
+    command(
+        "view-echo-area-messages",
+        messages::view_echo_area_messages,
+        "Show the buffer where all the messages shown in the minibuffer are logged.",
+    ),
+    command(
+        "toggle-message-timestamps",
+        messages::toggle_message_timestamps,
+        "Toggle logging the time of each message in the messages buffer.",
+    ),
+    command(
+        "mouse-set-point",
+        mouse::mouse_set_point,
+        "Move point to where the mouse was clicked, selecting its window.\n\
+         Clicking the modeline of a window only selects it.",
+    ),
+    command(
+        "mouse-drag-region",
+        mouse::mouse_drag_region,
+        "Set the region from where the mouse was pressed to where it was\n\
+         dragged.",
+    ),
+    command(
+        "mwheel-scroll",
+        mouse::mwheel_scroll,
+        "Scroll the window under the mouse with the wheel.",
+    ),
+    command(
+        "clipboard-kill-ring-save",
+        clipboard::clipboard_kill_ring_save,
+        "Save the region in the kill ring and copy it to the system\n\
+         clipboard, through the terminal.",
+    ),
+    command(
+        "toggle-select-enable-clipboard",
+        clipboard::toggle_select_enable_clipboard,
+        "Toggle sending killed text to the system clipboard, through the\n\
+         terminal.",
+    ),
+    command(
+        "toggle-clipboard-query-on-yank",
+        clipboard::toggle_clipboard_query_on_yank,
+        "Toggle reading the system clipboard before yanking, so text copied\n\
+         in other programs can be yanked. The terminal must reply to OSC 52\n\
+         queries.",
+    ),
+    command(
+        "set-clipboard-max-size",
+        clipboard::set_clipboard_max_size,
+        "Set the size, in bytes, of the largest text sent to the clipboard.\n\
+         Terminals ignore sequences that are too long.",
+    ),
+    command(
+        "set-mode-line-format",
+        modeline::set_mode_line_format,
+        "Set the format of the modeline, read in the minibuffer.\n\
+         %-constructs in the format are replaced: %b by the buffer name, %*\n\
+         by its modified and read-only flags, %l and %c by the line and\n\
+         column of point, %p by the position of the window in the buffer,\n\
+         %m by the major mode, and %{name} by an extension segment. An\n\
+         empty format restores the default.",
+    ),
+    command("ignore", commands::ignore, "Do nothing."),
+    command(
+        "forward-button",
+        help::forward_button,
+        "Move point to the next cross-reference in the help buffer.",
+    ),
+    command(
+        "push-button",
+        help::push_button,
+        "Show the documentation of the command referenced at point.",
+    ),
+];
+
+/// Find a command by name.
+pub fn lookup(name: &str) -> Option<CommandHandler> {
+    find(name).map(|c| c.handler)
+}
+
+/// Find the entry of a command by name.
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// Return all the commands.
+pub fn all() -> &'static [Command] {
+    COMMANDS
+}
@@ -1,13 +1,30 @@
 use std::cmp;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 
 use crate::buffer;
+use crate::buffer_list::BufferRef;
+use crate::clipboard;
+use crate::command_table::{self, Command};
 use crate::context;
 use crate::event_loop;
+use crate::face;
+use crate::gutter::LineNumbers;
+use crate::indent;
+use crate::kill_ring::Direction;
 use crate::layout;
+use crate::mark_ring;
+use crate::mode::MajorMode;
+use crate::motion;
+use crate::multiple_cursors;
+use crate::overlay::Overlay;
 use crate::read;
+use crate::sexp;
 use crate::term::Term;
 use crate::window::{self, message};
-use crate::{Context, Cursor};
+use crate::{Buffer, Context, Cursor, Key};
 
 pub type Result = std::result::Result<(), ()>;
 
@@ -17,67 +34,116 @@ fn get_line_indentation(line: &str) -> usize {
 
 pub fn move_beginning_of_line(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
-    let mut buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let line = buffer.get_line_unchecked(buffer.cursor.line);
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let mut cursor = buffer.cursor();
+    let line = buffer.get_line_unchecked(cursor.line);
     let indentation = get_line_indentation(line);
-    buffer.cursor.column = if buffer.cursor.column <= indentation {
+    cursor.column = if cursor.column <= indentation {
         0
     } else {
         indentation
     };
+    buffer.set_cursor(cursor);
     Ok(())
 }
 
 pub fn move_end_of_line(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let eol = buffer.get_line_unchecked(buffer.cursor.line).len();
-    buffer.cursor.column = eol;
+    let mut cursor = buffer.cursor();
+    cursor.column = buffer.get_line_unchecked(cursor.line).len();
+    buffer.set_cursor(cursor);
     Ok(())
 }
 
-pub fn forward_char(context: &mut Context, term: &mut Term) -> Result {
+pub fn forward_char(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let len = buffer.get_line_unchecked(buffer.cursor.line).len();
-    if buffer.cursor.column < len {
-        buffer.cursor.column += 1;
-    } else {
-        buffer.cursor.column = 0;
-        next_line(context, term)?;
+    match buffer.forward_pos(buffer.cursor()) {
+        Some(pos) => {
+            buffer.set_cursor(pos);
+            Ok(())
+        }
+        None => {
+            message(context, "End of buffer");
+            Err(())
+        }
     }
-    Ok(())
 }
 
-pub fn backward_char(context: &mut Context, term: &mut Term) -> Result {
+pub fn backward_char(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    if buffer.cursor.column > 0 {
-        buffer.cursor.column -= 1;
-    } else {
-        previous_line(context, term)?;
-        move_end_of_line(context, term)?;
+    match buffer.backward_pos(buffer.cursor()) {
+        Some(pos) => {
+            buffer.set_cursor(pos);
+            Ok(())
+        }
+        None => {
+            message(context, "Beginning of buffer");
+            Err(())
+        }
     }
-    Ok(())
 }
 
-fn get_or_set_gaol_column(cursor: &Cursor, goal_column: &mut context::GoalColumn) -> usize {
+/// Return the goal column, setting it to `column` if there is none.
+///
+/// Columns are display columns, so moving vertically across lines
+/// with tabs keeps the cursor visually aligned.
+fn get_or_set_gaol_column(column: usize, goal_column: &mut context::GoalColumn) -> usize {
     // We set `to_preserve` to ensure the goal_column is
     // not lost for the next command.
     goal_column.to_preserve = true;
-    *goal_column.column.get_or_insert(cursor.column)
+    *goal_column.column.get_or_insert(column)
 }
 
-pub fn next_line(context: &mut Context, _term: &mut Term) -> Result {
+/// Return the width of the rows of the current window if vertical
+/// motion is by screen rows, as in `visual-line-mode`.
+fn visual_line_width(context: &Context, term: &Term) -> Option<usize> {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    if window.visual_line_mode {
+        window.wrap_width(term.columns, buffer)
+    } else {
+        None
+    }
+}
+
+/// Return the row of the cursor in its line and its column in that
+/// row, for rows of `width` columns.
+fn cursor_visual_position(buffer: &Buffer, width: Option<usize>) -> (usize, usize) {
+    let column = buffer.display_column(buffer.cursor());
+    match width {
+        Some(width) => {
+            let row = window::column_row(buffer, buffer.cursor().line, column, width);
+            (row, column - row * width)
+        }
+        None => (0, column),
+    }
+}
+
+pub fn next_line(context: &mut Context, term: &mut Term) -> Result {
+    let width = visual_line_width(context, term);
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    if buffer.cursor.line < buffer.lines_count() - 1 {
-        let goal_column = get_or_set_gaol_column(&buffer.cursor, &mut context.goal_column);
-        buffer.cursor.line += 1;
-        buffer.cursor.column = cmp::min(
-            buffer.get_line_unchecked(buffer.cursor.line).len(),
-            goal_column,
-        );
+    let (row, column) = cursor_visual_position(buffer, width);
+    let line = buffer.cursor().line;
+
+    if let Some(width) = width {
+        if row + 1 < window::line_rows(buffer, line, width) {
+            let goal_column = get_or_set_gaol_column(column, &mut context.goal_column);
+            let column = buffer.column_at_display(line, (row + 1) * width + goal_column);
+            buffer.set_cursor(Cursor { line, column });
+            return Ok(());
+        }
+    }
+
+    // Folded lines are skipped.
+    let next = (line + 1..buffer.lines_count()).find(|line| !buffer.is_line_hidden(*line));
+    if let Some(next) = next {
+        let goal_column = get_or_set_gaol_column(column, &mut context.goal_column);
+        let column = buffer.column_at_display(next, goal_column);
+        buffer.set_cursor(Cursor { line: next, column });
         Ok(())
     } else {
         message(context, "End of buffer");
@@ -85,16 +151,33 @@ pub fn next_line(context: &mut Context, _term: &mut Term) -> Result {
     }
 }
 
-pub fn previous_line(context: &mut Context, _term: &mut Term) -> Result {
+pub fn previous_line(context: &mut Context, term: &mut Term) -> Result {
+    let width = visual_line_width(context, term);
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    if buffer.cursor.line > 0 {
-        let goal_column = get_or_set_gaol_column(&buffer.cursor, &mut context.goal_column);
-        buffer.cursor.line -= 1;
-        buffer.cursor.column = cmp::min(
-            buffer.get_line_unchecked(buffer.cursor.line).len(),
-            goal_column,
-        );
+    let (row, column) = cursor_visual_position(buffer, width);
+    let line = buffer.cursor().line;
+
+    if row > 0 {
+        let goal_column = get_or_set_gaol_column(column, &mut context.goal_column);
+        let width = width.unwrap();
+        let column = buffer.column_at_display(line, (row - 1) * width + goal_column);
+        buffer.set_cursor(Cursor { line, column });
+        return Ok(());
+    }
+
+    if line > 0 {
+        let goal_column = get_or_set_gaol_column(column, &mut context.goal_column);
+        // The first line is never hidden.
+        let line = (0..line)
+            .rev()
+            .find(|line| !buffer.is_line_hidden(*line))
+            .unwrap();
+        let last_row = width.map_or(0, |width| {
+            (window::line_rows(buffer, line, width) - 1) * width
+        });
+        let column = buffer.column_at_display(line, last_row + goal_column);
+        buffer.set_cursor(Cursor { line, column });
         Ok(())
     } else {
         message(context, "Beginning of buffer");
@@ -102,13 +185,43 @@ pub fn previous_line(context: &mut Context, _term: &mut Term) -> Result {
     }
 }
 
-pub fn insert_char(context: &mut Context, ch: char) {
+/// Run `command` as a command typed by the user, unless it would
+/// modify a read-only buffer.
+pub fn call_interactively(context: &mut Context, term: &mut Term, command: &Command) -> Result {
+    if command.modifies_buffer {
+        barf_if_buffer_read_only(context)?;
+    }
+    (command.handler)(context, term)
+}
+
+/// Fail if the current buffer can't be modified.
+pub fn barf_if_buffer_read_only(context: &mut Context) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    if buffer.read_only {
+        let text = format!("Buffer is read-only: {}", buffer.name);
+        message(context, text);
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Return the region of the current buffer, or fail with a message if
+/// the mark is not set.
+pub fn region_or_message(context: &mut Context) -> std::result::Result<(Cursor, Cursor), ()> {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    buffer.region().ok_or_else(|| {
+        message(context, "The mark is not set now, so there is no region");
+    })
+}
+
+pub fn insert_char(context: &mut Context, ch: char) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let idx = buffer.cursor.column;
-    let line = buffer.get_line_mut_unchecked(buffer.cursor.line);
-    line.insert(idx, ch);
-    buffer.cursor.column += 1;
+    buffer.insert(ch.encode_utf8(&mut [0; 4]));
+    Ok(())
 }
 
 pub fn delete_char(context: &mut Context, term: &mut Term) -> Result {
@@ -124,63 +237,639 @@ pub fn delete_backward_char(context: &mut Context, _term: &mut Term) -> Result {
     Ok(())
 }
 
-pub fn kill_line(context: &mut Context, term: &mut Term) -> Result {
+pub fn kill_line(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let Cursor { line, column } = buffer.cursor;
-    let line = buffer.get_line_mut_unchecked(line);
-    if column == line.len() {
-        if buffer.cursor.line < buffer.lines_count() - 1 {
-            delete_char(context, term)?;
+    let start = buffer.cursor();
+    let line_length = buffer.get_line_unchecked(start.line).len();
+    let end = if start.column == line_length {
+        match buffer.forward_pos(start) {
+            Some(end) => end,
+            None => {
+                message(context, "End of buffer");
+                return Err(());
+            }
         }
     } else {
-        line.drain(column..);
+        cmp::min(
+            Cursor {
+                line: start.line,
+                column: line_length,
+            },
+            buffer.point_max(),
+        )
+    };
+
+    let text = buffer.delete_region(start, end);
+    context.kill_ring.kill(text, Direction::Forward);
+    Ok(())
+}
+
+pub fn yank(context: &mut Context, term: &mut Term) -> Result {
+    if context.clipboard.query_on_yank {
+        // Text copied in other programs is yanked first.
+        if let Some(text) = clipboard::read_clipboard(term, context) {
+            if !text.is_empty() && context.kill_ring.current() != Some(&text) {
+                context.kill_ring.kill_new(text);
+            }
+        }
     }
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    match context.kill_ring.current() {
+        Some(text) => {
+            buffer.set_mark(Some(buffer.cursor()));
+            buffer.insert(text);
+            Ok(())
+        }
+        None => {
+            message(context, "Kill ring is empty");
+            Err(())
+        }
+    }
+}
+
+/// Insert the text pasted in the terminal as it is, and save it in
+/// the kill ring.
+pub fn xterm_paste(context: &mut Context, _term: &mut Term) -> Result {
+    let text = context.event_loop.pasted_text().ok_or(())?.to_string();
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let cursor = context.buffer_list.resolve_ref(buffer_ref).cursor();
+    mark_ring::push_mark(context, buffer_ref, cursor);
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.insert(&text);
+    context.kill_ring.kill_new(text);
+    Ok(())
+}
+
+pub fn forward_word(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(motion::forward_word(buffer, buffer.cursor()));
+    Ok(())
+}
 
+pub fn backward_word(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(motion::backward_word(buffer, buffer.cursor()));
+    Ok(())
+}
+
+pub fn kill_word(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let end = motion::forward_word(buffer, buffer.cursor());
+    let text = buffer.delete_region(buffer.cursor(), end);
+    context.kill_ring.kill(text, Direction::Forward);
+    Ok(())
+}
+
+pub fn backward_kill_word(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let start = motion::backward_word(buffer, buffer.cursor());
+    let text = buffer.delete_region(start, buffer.cursor());
+    buffer.set_cursor(start);
+    context.kill_ring.kill(text, Direction::Backward);
+    Ok(())
+}
+
+pub fn mark_word(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_mark(Some(motion::forward_word(buffer, buffer.cursor())));
+    buffer.mark_active = true;
+    Ok(())
+}
+
+pub fn forward_sentence(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(motion::forward_sentence(buffer, buffer.cursor()));
+    Ok(())
+}
+
+pub fn backward_sentence(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(motion::backward_sentence(buffer, buffer.cursor()));
+    Ok(())
+}
+
+pub fn forward_paragraph(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(motion::forward_paragraph(buffer, buffer.cursor()));
+    Ok(())
+}
+
+pub fn backward_paragraph(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(motion::backward_paragraph(buffer, buffer.cursor()));
+    Ok(())
+}
+
+pub fn mark_paragraph(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let end = motion::forward_paragraph(buffer, buffer.cursor());
+    buffer.set_cursor(motion::backward_paragraph(buffer, end));
+    buffer.set_mark(Some(end));
+    buffer.mark_active = true;
+    Ok(())
+}
+
+/// Swap the text of two regions, the first one before the second
+/// one, and return the position after them.
+fn transpose_regions(
+    buffer: &mut Buffer,
+    first: (Cursor, Cursor),
+    second: (Cursor, Cursor),
+) -> Cursor {
+    let middle = buffer.region_text(first.1, second.0);
+    let first_text = buffer.region_text(first.0, first.1);
+    let second_text = buffer.delete_region(second.0, second.1);
+    buffer.delete_region(first.0, second.0);
+    buffer.insert_at(first.0, &(second_text + &middle + &first_text))
+}
+
+pub fn transpose_chars(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+
+    // At the end of a line, transpose the two previous characters.
+    let middle = match buffer.char_after(buffer.cursor()) {
+        Some('\n') | None => buffer.backward_pos(buffer.cursor()),
+        Some(_) => Some(buffer.cursor()),
+    };
+    let positions = middle.and_then(|middle| {
+        let start = buffer.backward_pos(middle)?;
+        let end = buffer.forward_pos(middle)?;
+        Some((start, middle, end))
+    });
+
+    if let Some((start, middle, end)) = positions {
+        let end = transpose_regions(buffer, (start, middle), (middle, end));
+        buffer.set_cursor(end);
+        Ok(())
+    } else {
+        message(context, "Don't have two things to transpose");
+        Err(())
+    }
+}
+
+pub fn transpose_words(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+
+    let end2 = motion::forward_word(buffer, buffer.cursor());
+    let start2 = motion::backward_word(buffer, end2);
+    let start1 = motion::backward_word(buffer, start2);
+    let end1 = motion::forward_word(buffer, start1);
+
+    if start1 == start2 || end1 > start2 {
+        message(context, "Don't have two things to transpose");
+        return Err(());
+    }
+
+    let end = transpose_regions(buffer, (start1, end1), (start2, end2));
+    buffer.set_cursor(end);
+    Ok(())
+}
+
+pub fn transpose_lines(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let line = buffer.cursor().line;
+
+    if line == 0 {
+        message(context, "Don't have two things to transpose");
+        return Err(());
+    }
+
+    let first = (
+        Cursor {
+            line: line - 1,
+            column: 0,
+        },
+        Cursor {
+            line: line - 1,
+            column: buffer.get_line_unchecked(line - 1).len(),
+        },
+    );
+    let second = (
+        Cursor { line, column: 0 },
+        Cursor {
+            line,
+            column: buffer.get_line_unchecked(line).len(),
+        },
+    );
+    // Both lines must be whole in the accessible portion.
+    if first.0 < buffer.point_min() || second.1 > buffer.point_max() {
+        message(context, "Don't have two things to transpose");
+        return Err(());
+    }
+    let end = transpose_regions(buffer, first, second);
+    buffer.set_cursor(buffer.forward_pos(end).unwrap_or(end));
+    Ok(())
+}
+
+/// Replace the text from the cursor to the end of the next word by
+/// the result of `f`, leaving the cursor after it.
+fn convert_word<F: Fn(&str) -> String>(context: &mut Context, f: F) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let start = buffer.cursor();
+    let end = motion::forward_word(buffer, start);
+    let text = buffer.delete_region(start, end);
+    let end = buffer.insert_at(start, &f(&text));
+    buffer.set_cursor(end);
+    Ok(())
+}
+
+pub fn upcase_word(context: &mut Context, _term: &mut Term) -> Result {
+    convert_word(context, str::to_uppercase)
+}
+
+pub fn downcase_word(context: &mut Context, _term: &mut Term) -> Result {
+    convert_word(context, str::to_lowercase)
+}
+
+pub fn capitalize_word(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let table = buffer.mode.syntax_table.clone();
+
+    convert_word(context, |text| {
+        let mut result = String::new();
+        let mut in_word = false;
+        for ch in text.chars() {
+            if !table.is_word(ch) {
+                result.push(ch);
+                in_word = false;
+            } else if in_word {
+                result.extend(ch.to_lowercase());
+            } else {
+                result.extend(ch.to_uppercase());
+                in_word = true;
+            }
+        }
+        result
+    })
+}
+
+/// Move the cursor to the position computed by `scan`, showing its
+/// error in case of failure.
+fn scan_sexp(context: &mut Context, scan: fn(&Buffer, Cursor) -> sexp::Result<Cursor>) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    match scan(buffer, buffer.cursor()) {
+        Ok(pos) => {
+            buffer.set_cursor(pos);
+            Ok(())
+        }
+        Err(err) => {
+            message(context, err.message());
+            Err(())
+        }
+    }
+}
+
+pub fn forward_sexp(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::forward_sexp)
+}
+
+pub fn backward_sexp(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::backward_sexp)
+}
+
+pub fn forward_list(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::forward_list)
+}
+
+pub fn backward_list(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::backward_list)
+}
+
+pub fn up_list(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::up_list)
+}
+
+pub fn backward_up_list(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::backward_up_list)
+}
+
+pub fn down_list(context: &mut Context, _term: &mut Term) -> Result {
+    scan_sexp(context, sexp::down_list)
+}
+
+pub fn kill_sexp(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    match sexp::forward_sexp(buffer, buffer.cursor()) {
+        Ok(end) => {
+            let text = buffer.delete_region(buffer.cursor(), end);
+            context.kill_ring.kill(text, Direction::Forward);
+            Ok(())
+        }
+        Err(err) => {
+            message(context, err.message());
+            Err(())
+        }
+    }
+}
+
+pub fn mark_sexp(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    match sexp::forward_sexp(buffer, buffer.cursor()) {
+        Ok(end) => {
+            buffer.set_mark(Some(end));
+            buffer.mark_active = true;
+            Ok(())
+        }
+        Err(err) => {
+            message(context, err.message());
+            Err(())
+        }
+    }
+}
+
+/// Remove the overlays of `source` from the buffers shown in a window,
+/// including the minibuffer.
+pub fn clear_displayed_overlays(context: &mut Context, source: &str) {
+    let mut buffer_refs: Vec<BufferRef> = context
+        .window_list
+        .windows
+        .iter()
+        .map(|window| window.buffer_ref)
+        .collect();
+    buffer_refs.push(BufferRef::minibuffer_window());
+    for buffer_ref in buffer_refs {
+        let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+        buffer.clear_overlays(source);
+    }
+}
+
+/// Highlight the delimiter at point in the current window and its
+/// match, if `show_paren_mode` is set.
+pub fn show_paren_update(context: &mut Context) {
+    clear_displayed_overlays(context, "show-paren");
+    if !context.show_paren_mode {
+        return;
+    }
+
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let paren_match = match sexp::find_paren_match(buffer, buffer.cursor()) {
+        Some(paren_match) => paren_match,
+        None => return,
+    };
+    let face = if paren_match.mismatched {
+        face::SHOW_PAREN_MISMATCH
+    } else {
+        face::SHOW_PAREN_MATCH
+    };
+    for pos in Some(paren_match.paren)
+        .into_iter()
+        .chain(paren_match.matching)
+    {
+        let end = buffer.forward_pos(pos).unwrap_or(pos);
+        let mut overlay = Overlay::new("show-paren", pos, end);
+        overlay.face = Some(face);
+        overlay.priority = 1000;
+        buffer.add_overlay(overlay);
+    }
+}
+
+/// Highlight the region of the current buffer while it is active,
+/// unless `rectangle-mark-mode` highlights its rectangle.
+pub fn region_update(context: &mut Context) {
+    clear_displayed_overlays(context, "region");
+    if context.rectangle.mark_mode {
+        return;
+    }
+
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    if !buffer.mark_active {
+        return;
+    }
+    if let Some((start, end)) = buffer.region().filter(|(start, end)| start != end) {
+        let mut overlay = Overlay::new("region", start, end);
+        overlay.face = Some(face::REGION);
+        buffer.add_overlay(overlay);
+    }
+}
+
+pub fn show_paren_mode(context: &mut Context, _term: &mut Term) -> Result {
+    context.show_paren_mode = !context.show_paren_mode;
+    if context.show_paren_mode {
+        message(context, "Show-Paren mode enabled");
+    } else {
+        message(context, "Show-Paren mode disabled");
+    }
     Ok(())
 }
 
 pub fn newline(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let Cursor { line, column } = buffer.cursor;
-    let line = buffer.get_line_mut_unchecked(line);
-    let newline = line.split_off(column);
-    buffer.insert_line_at(buffer.cursor.line + 1, newline);
-    buffer.cursor.line += 1;
-    buffer.cursor.column = 0;
+    buffer.insert("\n");
     Ok(())
 }
 
-pub fn indent_line(context: &mut Context, _term: &mut Term) -> Result {
+pub fn indent_according_to_mode(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let line = buffer.get_line_unchecked(buffer.cursor.line);
-    let indent = get_line_indentation(line);
-    if buffer.cursor.column < indent {
-        buffer.cursor.column = indent;
+    indent::indent_line(buffer, buffer.cursor().line);
+    Ok(())
+}
+
+pub fn newline_and_indent(context: &mut Context, term: &mut Term) -> Result {
+    newline(context, term)?;
+    indent_according_to_mode(context, term)
+}
+
+pub fn indent_region(context: &mut Context, _term: &mut Term) -> Result {
+    let (start, end) = region_or_message(context)?;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    indent::indent_region(buffer, start.line, end.line);
+    message(context, "Indenting region...done");
+    Ok(())
+}
+
+/// Shift the lines of the region interactively with C-b and C-f,
+/// until any other key is pressed.
+pub fn indent_rigidly(context: &mut Context, term: &mut Term) -> Result {
+    let (start, end) = region_or_message(context).map(|(start, end)| (start.line, end.line))?;
+
+    let right = Key::parse_unchecked("C-f");
+    let left = Key::parse_unchecked("C-b");
+
+    message(context, "Indent region with C-b and C-f");
+    loop {
+        let key = event_loop::read_key(term, context);
+        let delta = if key == right {
+            1
+        } else if key == left {
+            -1
+        } else {
+            context.event_loop.push_front_keys(&[key]);
+            break;
+        };
+
+        let window = context.window_list.get_current_window();
+        let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+        indent::indent_rigidly(buffer, start, end, delta);
     }
+
+    message(context, "");
     Ok(())
 }
 
-pub fn save_buffer(context: &mut Context, _term: &mut Term) -> Result {
+pub fn set_tab_width(context: &mut Context, term: &mut Term) -> Result {
+    let input =
+        read::read_string(term, context, "Tab width: ", |_, _| {}, false).map_err(|_| ())?;
+    match input.trim().parse() {
+        Ok(width) if width > 0 => {
+            let window = context.window_list.get_current_window();
+            let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+            buffer.tab_width = width;
+            Ok(())
+        }
+        _ => {
+            message(context, format!("Invalid tab width: {}", input));
+            Err(())
+        }
+    }
+}
+
+pub fn indent_tabs_mode(context: &mut Context, _term: &mut Term) -> Result {
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.indent_tabs_mode = !buffer.indent_tabs_mode;
+    if buffer.indent_tabs_mode {
+        message(context, "Indent-Tabs mode enabled");
+    } else {
+        message(context, "Indent-Tabs mode disabled");
+    }
+    Ok(())
+}
 
-    match buffer.save() {
+pub fn save_buffer(context: &mut Context, term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer_ref = window.buffer_ref;
+    if context
+        .buffer_list
+        .resolve_ref(buffer_ref)
+        .filename
+        .is_none()
+    {
+        return write_file(context, term);
+    }
+    save_to_file(context, term, buffer_ref)
+}
+
+/// Ask `prompt` in the minibuffer, reporting a quit as a failure.
+fn y_or_n(context: &mut Context, term: &mut Term, prompt: &str) -> std::result::Result<bool, ()> {
+    read::read_y_or_n(term, context, prompt).map_err(|_| {
+        message(context, "Quit");
+    })
+}
+
+/// Return the directory of `filename` if it does not exist.
+fn missing_directory(filename: &str) -> Option<&Path> {
+    Path::new(filename)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+}
+
+/// Save `buffer_ref` to its file, offering to recover from the errors
+/// the user can do something about.
+fn save_to_file(context: &mut Context, term: &mut Term, buffer_ref: BufferRef) -> Result {
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let err = match buffer.save() {
         Ok(filename) => {
             message(context, format!("Wrote {}", filename));
-            Ok(())
+            return Ok(());
         }
         Err(buffer::SaveError::NoFile) => {
             message(context, "No file");
-            Err(())
+            return Err(());
         }
-        Err(buffer::SaveError::IoError(_)) => {
-            message(context, "Could not save file");
-            Err(())
+        Err(buffer::SaveError::IoError(err)) => err,
+    };
+    let filename = buffer.filename.clone().unwrap();
+
+    match err.kind() {
+        io::ErrorKind::NotFound => {
+            if let Some(dir) = missing_directory(&filename) {
+                let prompt = format!("Directory {} does not exist; create it? ", dir.display());
+                if y_or_n(context, term, &prompt)? {
+                    if let Err(err) = fs::create_dir_all(dir) {
+                        message(
+                            context,
+                            format!("Could not create {}: {}", dir.display(), err),
+                        );
+                        return Err(());
+                    }
+                    return save_to_file(context, term, buffer_ref);
+                }
+            }
+        }
+        io::ErrorKind::PermissionDenied => {
+            let prompt = format!("Cannot write {}; save to another file? ", filename);
+            if y_or_n(context, term, &prompt)? {
+                return write_file(context, term);
+            }
         }
+        _ => {}
     }
+
+    // The buffer is still modified, so nothing is lost until the
+    // user gives up on it.
+    message(context, format!("Could not save {}: {}", filename, err));
+    Err(())
+}
+
+pub fn write_file(context: &mut Context, term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer_ref = window.buffer_ref;
+
+    let input =
+        read::read_string(term, context, "Write file: ", |_, _| {}, false).map_err(|_| ())?;
+    let input = input.trim();
+    if input.is_empty() {
+        message(context, "No file name given");
+        return Err(());
+    }
+
+    // Writing to a directory writes to a file of the same name in it.
+    let buffer = context.buffer_list.resolve_ref(buffer_ref);
+    let mut path = PathBuf::from(input);
+    if path.is_dir() {
+        let name = buffer.filename.as_ref().unwrap_or(&buffer.name);
+        path.push(Path::new(name).file_name().unwrap_or_default());
+    }
+    let filename = path.to_string_lossy().into_owned();
+
+    if buffer.filename.as_ref() != Some(&filename) && path.exists() {
+        let prompt = format!("File {} exists; overwrite? ", filename);
+        if !y_or_n(context, term, &prompt)? {
+            message(context, "Canceled");
+            return Err(());
+        }
+    }
+
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.name = filename.clone();
+    buffer.filename = Some(filename.clone());
+    buffer.set_mode(MajorMode::for_filename(&filename));
+    save_to_file(context, term, buffer_ref)
 }
 
 const CONTEXT_LINES: usize = 2;
@@ -195,7 +884,11 @@ pub fn next_screen(context: &mut Context, term: &mut Term) -> Result {
     let target = window.scroll_line + offset;
     if target < buffer.lines_count() {
         window.scroll_line = target;
-        buffer.cursor.line = target;
+        let column = buffer.cursor().column;
+        buffer.set_cursor(Cursor {
+            line: target,
+            column,
+        });
         Ok(())
     } else {
         message(context, "End of buffer");
@@ -214,8 +907,12 @@ pub fn previous_screen(context: &mut Context, term: &mut Term) -> Result {
 
     let offset = window.window_lines(&region) - 1 - CONTEXT_LINES;
 
-    let mut buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    buffer.cursor.line = window.scroll_line + CONTEXT_LINES;
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let column = buffer.cursor().column;
+    buffer.set_cursor(Cursor {
+        line: window.scroll_line + CONTEXT_LINES,
+        column,
+    });
 
     window.scroll_line = if let Some(scroll_line) = window.scroll_line.checked_sub(offset) {
         scroll_line
@@ -226,21 +923,308 @@ pub fn previous_screen(context: &mut Context, term: &mut Term) -> Result {
     Ok(())
 }
 
+/// Scroll the current window horizontally by almost its width, to
+/// the left if `left` is set or to the right otherwise.
+fn scroll_horizontally(context: &mut Context, term: &mut Term, left: bool) -> Result {
+    let window = context.window_list.get_current_window_as_mut();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+
+    if !window.truncate_lines {
+        message(context, "Long lines are not truncated in this window");
+        return Err(());
+    }
+
+    let columns = window.visible_columns(term.columns, buffer);
+    let amount = cmp::max(1, (columns.end - columns.start).saturating_sub(2));
+    window.hscroll = if left {
+        window.hscroll + amount
+    } else {
+        window.hscroll.saturating_sub(amount)
+    };
+    window.min_hscroll = window.hscroll;
+    Ok(())
+}
+
+pub fn scroll_left(context: &mut Context, term: &mut Term) -> Result {
+    scroll_horizontally(context, term, true)
+}
+
+pub fn scroll_right(context: &mut Context, term: &mut Term) -> Result {
+    scroll_horizontally(context, term, false)
+}
+
+pub fn toggle_truncate_lines(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window_as_mut();
+    window.truncate_lines = !window.truncate_lines;
+    window.hscroll = 0;
+    window.min_hscroll = 0;
+    if window.truncate_lines {
+        window.visual_line_mode = false;
+        message(context, "Truncate long lines enabled");
+    } else {
+        message(context, "Truncate long lines disabled");
+    }
+    Ok(())
+}
+
+pub fn visual_line_mode(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window_as_mut();
+    window.visual_line_mode = !window.visual_line_mode;
+    if window.visual_line_mode {
+        window.truncate_lines = false;
+        window.hscroll = 0;
+        window.min_hscroll = 0;
+        message(context, "Visual-Line mode enabled");
+    } else {
+        message(context, "Visual-Line mode disabled");
+    }
+    Ok(())
+}
+
+pub fn display_line_numbers_mode(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window_as_mut();
+    window.show_lines = !window.show_lines;
+    if window.show_lines {
+        message(context, "Display-Line-Numbers mode enabled");
+    } else {
+        message(context, "Display-Line-Numbers mode disabled");
+    }
+    Ok(())
+}
+
+pub fn set_line_numbers_type(context: &mut Context, term: &mut Term) -> Result {
+    let input = read::read_string(
+        term,
+        context,
+        "Line numbers (absolute, relative or hybrid): ",
+        |_, _| {},
+        false,
+    )
+    .map_err(|_| ())?;
+    let line_numbers = match input.trim() {
+        "absolute" => LineNumbers::Absolute,
+        "relative" => LineNumbers::Relative,
+        "hybrid" => LineNumbers::Hybrid,
+        other => {
+            message(context, format!("Invalid line numbers type: {}", other));
+            return Err(());
+        }
+    };
+    let window = context.window_list.get_current_window_as_mut();
+    window.line_numbers = line_numbers;
+    window.show_lines = true;
+    Ok(())
+}
+
 pub fn beginning_of_buffer(context: &mut Context, _term: &mut Term) -> Result {
+    mark_ring::push_jump(context);
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    buffer.cursor.line = 0;
-    buffer.cursor.column = 0;
+    buffer.set_cursor(buffer.point_min());
     Ok(())
 }
 
 pub fn end_of_buffer(context: &mut Context, _term: &mut Term) -> Result {
+    mark_ring::push_jump(context);
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(buffer.point_max());
+    Ok(())
+}
+
+/// Read a number in the minibuffer.
+fn read_number(
+    context: &mut Context,
+    term: &mut Term,
+    prompt: &str,
+) -> std::result::Result<usize, ()> {
+    let input = read::read_string(term, context, prompt, |_, _| {}, false).map_err(|_| ())?;
+    input.trim().parse().map_err(|_| {
+        message(context, format!("Invalid number: {}", input.trim()));
+    })
+}
+
+pub fn goto_line(context: &mut Context, term: &mut Term) -> Result {
+    let line = read_number(context, term, "Goto line: ")?;
+    mark_ring::push_jump(context);
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
-    let linenum = buffer.lines_count() - 1;
-    buffer.cursor.line = linenum;
-    let line = buffer.get_line_unchecked(linenum);
-    buffer.cursor.column = line.len();
+    buffer.set_cursor(buffer.line_column_position(line.saturating_sub(1), 0));
+    Ok(())
+}
+
+pub fn goto_char(context: &mut Context, term: &mut Term) -> Result {
+    let position = read_number(context, term, "Goto char: ")?;
+    mark_ring::push_jump(context);
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(buffer.offset_position(position.saturating_sub(1)));
+    Ok(())
+}
+
+pub fn what_line(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let text = format!("Line {}", buffer.cursor().line + 1);
+    message(context, text);
+    Ok(())
+}
+
+pub fn what_cursor_position(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let cursor = buffer.cursor();
+    let offset = buffer.char_offset(cursor);
+    let size = buffer.char_offset(buffer.end_position());
+    let percent = (offset * 100).checked_div(size).unwrap_or(0);
+    // The accessible portion of a narrowed buffer follows the percentage.
+    let narrowing = match buffer.narrowing {
+        Some(_) => format!(
+            " <{}-{}>",
+            buffer.char_offset(buffer.point_min()) + 1,
+            buffer.char_offset(buffer.point_max()) + 1
+        ),
+        None => String::new(),
+    };
+    let position = format!(
+        "point={} of {} ({}%){} byte={} line={} column={}",
+        offset + 1,
+        size + 1,
+        percent,
+        narrowing,
+        buffer.byte_offset(cursor) + 1,
+        cursor.line + 1,
+        buffer.get_line_unchecked(cursor.line)[..cursor.column]
+            .chars()
+            .count(),
+    );
+    let text = match buffer.char_after(cursor) {
+        Some(ch) => {
+            let code = ch as u32;
+            format!(
+                "Char: {} ({}, #o{:o}, #x{:x}) {}",
+                Key::from_code(code),
+                code,
+                code,
+                code,
+                position
+            )
+        }
+        None => position,
+    };
+    message(context, text);
+    Ok(())
+}
+
+/// The minimum number of rows of a window, including its modeline.
+const WINDOW_MIN_HEIGHT: usize = 4;
+
+pub fn split_window_below(context: &mut Context, term: &mut Term) -> Result {
+    // All windows share the available rows evenly.
+    let layout = layout::get_layout(term, context);
+    let rows: usize = layout.window_regions.iter().map(|r| r.height).sum();
+    if rows / (layout.window_regions.len() + 1) < WINDOW_MIN_HEIGHT {
+        message(context, "Window is too small for splitting");
+        return Err(());
+    }
+    context.window_list.split_selected();
+    Ok(())
+}
+
+pub fn other_window(context: &mut Context, _term: &mut Term) -> Result {
+    window::select_window(context, context.window_list.next_window());
+    Ok(())
+}
+
+/// Delete the selected window and move to the point of the window
+/// selected after it.
+fn delete_selected_window(context: &mut Context) -> bool {
+    match context.window_list.delete_selected() {
+        Some(deleted) => {
+            window::delete_window_point(context, &deleted);
+            window::restore_window_point(context);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn delete_window(context: &mut Context, _term: &mut Term) -> Result {
+    if delete_selected_window(context) {
+        Ok(())
+    } else {
+        message(
+            context,
+            "Attempt to delete minibuffer or sole ordinary window",
+        );
+        Err(())
+    }
+}
+
+pub fn delete_other_windows(context: &mut Context, _term: &mut Term) -> Result {
+    for deleted in context.window_list.delete_others() {
+        window::delete_window_point(context, &deleted);
+    }
+    Ok(())
+}
+
+pub fn quit_window(context: &mut Context, _term: &mut Term) -> Result {
+    let current = context.window_list.selected_window().buffer_ref;
+    let previous_buffer = context.window_list.selected_window().previous_buffer;
+
+    let previous = match previous_buffer {
+        Some(previous) => previous,
+        None if delete_selected_window(context) => return Ok(()),
+        // The window can't be deleted, show any other buffer.
+        None => match context.buffer_list.iter().find(|(r, _)| *r != current) {
+            Some((other, _)) => other,
+            None => return Ok(()),
+        },
+    };
+
+    let window_list = &mut context.window_list;
+    let window = &mut window_list.windows[window_list.selected];
+    window.set_buffer(previous);
+    window.previous_buffer = None;
+    Ok(())
+}
+
+pub fn switch_to_buffer(context: &mut Context, term: &mut Term) -> Result {
+    let window = context.window_list.selected_window();
+    let current = window.buffer_ref;
+    let default = window
+        .previous_buffer
+        .filter(|r| *r != current)
+        .or_else(|| {
+            context
+                .buffer_list
+                .iter()
+                .find(|(r, _)| *r != current)
+                .map(|(r, _)| r)
+        })
+        .map(|r| context.buffer_list.resolve_ref(r).name.clone());
+    let prompt = match &default {
+        Some(name) => format!("Switch to buffer (default {}): ", name),
+        None => "Switch to buffer: ".to_string(),
+    };
+
+    let input = read::read_string(term, context, &prompt, |_, _| {}, false).map_err(|_| ())?;
+    let name = match (input.trim(), default) {
+        ("", Some(default)) => default,
+        ("", None) => return Err(()),
+        (name, _) => name.to_string(),
+    };
+    let buffer_ref = match context.buffer_list.find(&name) {
+        Some(buffer_ref) => buffer_ref,
+        None => {
+            message(context, format!("No buffer named {}", name));
+            return Err(());
+        }
+    };
+    if buffer_ref != current {
+        let window_list = &mut context.window_list;
+        window_list.windows[window_list.selected].set_buffer(buffer_ref);
+    }
     Ok(())
 }
 
@@ -249,6 +1233,36 @@ pub fn kill_rile(context: &mut Context, _term: &mut Term) -> Result {
     Ok(())
 }
 
+/// Highlight the occurrences of `text` on `lines` of `buffer` with
+/// overlays.
+fn highlight_matches(buffer: &mut Buffer, text: &str, lines: RangeInclusive<usize>) {
+    buffer.clear_overlays("isearch");
+    if text.is_empty() {
+        return;
+    }
+    // Only the accessible portion of the buffer is searched.
+    let (min, max) = (buffer.point_min(), buffer.point_max());
+    let mut matches = vec![];
+    for line in cmp::max(min.line, *lines.start())..=cmp::min(max.line, *lines.end()) {
+        for (column, matched) in buffer.get_line_unchecked(line).match_indices(text) {
+            let start = Cursor { line, column };
+            let end = Cursor {
+                line,
+                column: column + matched.len(),
+            };
+            if min <= start && end <= max {
+                matches.push((start, end));
+            }
+        }
+    }
+    for (start, end) in matches {
+        let mut overlay = Overlay::new("isearch", start, end);
+        overlay.face = Some(face::ISEARCH);
+        overlay.priority = 1001;
+        buffer.add_overlay(overlay);
+    }
+}
+
 pub fn isearch_forward(context: &mut Context, term: &mut Term) -> Result {
     let buffer_ref = context.window_list.get_current_window().buffer_ref;
 
@@ -256,26 +1270,67 @@ pub fn isearch_forward(context: &mut Context, term: &mut Term) -> Result {
         term,
         context,
         "",
-        |_, context| {
+        |term, context| {
             let partial_input = context.buffer_list.minibuffer.to_string();
+            // Only the matches on the screen are highlighted.
+            let region =
+                &layout::get_layout(term, context).window_regions[context.window_list.selected];
+            let window = context.window_list.selected_window();
+            let buffer = context.buffer_list.resolve_ref(buffer_ref);
+            let lines = window.visible_lines(buffer, term.columns, region);
             let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
-            buffer.highlight = Some(partial_input);
+            highlight_matches(buffer, &partial_input, lines);
         },
         true,
     );
 
     let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
-    buffer.highlight = None;
+    buffer.clear_overlays("isearch");
 
     Ok(())
 }
 
 pub fn m_x(context: &mut Context, term: &mut Term) -> Result {
-    let _ = read::read_string(term, context, "", |_, _context| {}, false);
+    let name = read::read_string(term, context, "M-x ", |_, _context| {}, false).map_err(|_| ())?;
+    let name = name.trim();
+
+    if let Some(command) = command_table::find(name) {
+        call_interactively(context, term, command)
+    } else if let Some(keys) = context.kmacro.named.get(name).cloned() {
+        event_loop::execute_kbd_macro(term, context, &keys)
+    } else {
+        message(context, format!("No command named {}", name));
+        Err(())
+    }
+}
+
+pub fn set_mark_command(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let cursor = context.buffer_list.resolve_ref(buffer_ref).cursor();
+    mark_ring::push_mark(context, buffer_ref, cursor);
+    context
+        .buffer_list
+        .resolve_ref_as_mut(buffer_ref)
+        .mark_active = true;
+    message(context, "Mark set");
+    Ok(())
+}
+
+/// Do nothing, for keys that should not be reported as undefined.
+pub fn ignore(_context: &mut Context, _term: &mut Term) -> Result {
     Ok(())
 }
 
 pub fn keyboard_quit(context: &mut Context, term: &mut Term) -> Result {
+    // Quitting also aborts the definition of a keyboard macro.
+    context.kmacro.recording = None;
+    context.rectangle.mark_mode = false;
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    context
+        .buffer_list
+        .resolve_ref_as_mut(buffer_ref)
+        .mark_active = false;
+    multiple_cursors::remove_fake_cursors(context);
     message(context, "Quit");
     window::ding(term, context).unwrap();
     context
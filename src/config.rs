@@ -0,0 +1,22 @@
+//! Location of the user configuration files.
+//!
+
+use std::env;
+use std::path::PathBuf;
+
+/// Return the directory where the user configuration is stored.
+///
+/// This is `$XDG_CONFIG_HOME/rile`, or `~/.config/rile` if the
+/// variable is not set.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("rile"))
+}
+
+/// Return the path of the configuration file `name`.
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(name))
+}
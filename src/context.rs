@@ -1,8 +1,20 @@
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::Arc;
 
+use crate::bookmark::BookmarkState;
 use crate::buffer_list::{BufferList, BufferRef};
+use crate::clipboard::ClipboardState;
 use crate::event_loop::EventLoopState;
+use crate::help::HelpState;
+use crate::kill_ring::KillRing;
+use crate::kmacro::KmacroState;
+use crate::mark_ring::MarkRingState;
+use crate::messages::MessagesState;
+use crate::modeline::ModelineState;
+use crate::mouse::MouseState;
+use crate::rectangle::RectangleState;
+use crate::register::Registers;
+use crate::timer::{TimerId, TimerList};
 use crate::window_list::WindowList;
 use crate::{Buffer, Window};
 
@@ -17,7 +29,27 @@ pub struct Context {
     pub window_list: WindowList,
     pub event_loop: EventLoopState,
     pub was_resized: Arc<AtomicBool>,
+    /// Set when the editor is continued after being stopped.
+    pub was_continued: Arc<AtomicBool>,
+    /// The number of the signal asking the editor to terminate, or 0.
+    pub terminated: Arc<AtomicUsize>,
     pub goal_column: GoalColumn,
+    pub kmacro: KmacroState,
+    pub kill_ring: KillRing,
+    pub mark_ring: MarkRingState,
+    pub registers: Registers,
+    pub rectangle: RectangleState,
+    pub bookmarks: BookmarkState,
+    pub help: HelpState,
+    pub messages: MessagesState,
+    pub timers: TimerList,
+    /// The idle timer of `auto-save-mode`, if it is enabled.
+    pub auto_save_timer: Option<TimerId>,
+    pub mouse: MouseState,
+    pub clipboard: ClipboardState,
+    pub modeline: ModelineState,
+    /// If set, highlight the delimiter matching the one at the cursor.
+    pub show_paren_mode: bool,
 }
 
 impl Context {
@@ -25,13 +57,17 @@ impl Context {
         Context {
             buffer_list: BufferList::new(buffer),
 
-            window_list: WindowList {
-                main: Window::new(BufferRef::main_window(), true),
-                minibuffer: Window::new(BufferRef::minibuffer_window(), false),
-                minibuffer_focused: false,
-            },
+            window_list: WindowList::new(
+                Window::new(BufferRef::main_window(), true),
+                Window {
+                    truncate_lines: true,
+                    ..Window::new(BufferRef::minibuffer_window(), false)
+                },
+            ),
 
             was_resized: Arc::new(AtomicBool::new(false)),
+            was_continued: Arc::new(AtomicBool::new(false)),
+            terminated: Arc::new(AtomicUsize::new(0)),
 
             event_loop: EventLoopState::new(),
 
@@ -39,6 +75,21 @@ impl Context {
                 to_preserve: false,
                 column: None,
             },
+
+            kmacro: KmacroState::new(),
+            kill_ring: KillRing::new(),
+            mark_ring: MarkRingState::new(),
+            registers: Registers::new(),
+            rectangle: RectangleState::new(),
+            bookmarks: BookmarkState::new(),
+            help: HelpState::new(),
+            messages: MessagesState::new(),
+            timers: TimerList::new(),
+            auto_save_timer: None,
+            mouse: MouseState::new(),
+            clipboard: ClipboardState::new(),
+            modeline: ModelineState::new(),
+            show_paren_mode: true,
         }
     }
 }
@@ -1,9 +1,23 @@
 use std::collections::VecDeque;
+use std::io::Read;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
+use nix::errno::Errno;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
+
+use crate::clipboard;
 use crate::commands;
+use crate::input::{InputDecoder, MousePosition, ESC_TIMEOUT};
+use crate::multiple_cursors;
 use crate::read;
-use crate::term::{read_key_timeout, reconciliate_term_size, Term};
-use crate::window::{adjust_scroll, refresh_screen};
+use crate::rectangle;
+use crate::signals;
+use crate::term::{self, Term};
+use crate::window::{adjust_scroll, message, refresh_screen};
 use crate::{Context, Key};
 
 pub enum EventLoopError {
@@ -12,14 +26,41 @@ pub enum EventLoopError {
 
 pub type Result<T> = std::result::Result<T, EventLoopError>;
 
+/// A function called when a watched file descriptor is ready to be
+/// read, or was closed.
+pub type FdHandler = fn(&mut Context, &mut Term, RawFd);
+
+struct FdWatch {
+    fd: RawFd,
+    handler: FdHandler,
+}
+
 pub struct EventLoopState {
     /// A buffer of keys that should be read by read_key. If empty,
     /// this will be re-fill on demand from the keyboard input.
     pending_input: VecDeque<Key>,
 
+    /// The key sequence that invoked the command being executed.
+    pub this_command_keys: Vec<Key>,
+
     /// If set (Some), the event loop is about to terminate with a
     /// specified Result.
     pub result: Option<Result<()>>,
+
+    /// The bytes read from the terminal that are not keys yet.
+    input: InputDecoder,
+
+    /// The end of a pipe written to when a signal arrives, so waiting
+    /// for input is interrupted.
+    pub signal_pipe: Option<UnixStream>,
+
+    /// Other file descriptors to wait for along with the input, like
+    /// the output of subprocesses.
+    fd_watches: Vec<FdWatch>,
+
+    /// The number of event loops running, one more for each
+    /// recursive edit like reading from the minibuffer.
+    depth: usize,
 }
 
 impl EventLoopState {
@@ -27,15 +68,54 @@ impl EventLoopState {
         EventLoopState {
             result: None,
             pending_input: VecDeque::new(),
+            this_command_keys: vec![],
+            input: InputDecoder::new(),
+            depth: 0,
+            signal_pipe: None,
+            fd_watches: vec![],
         }
     }
 
+    /// Return the number of recursive edits in progress.
+    pub fn recursion_depth(&self) -> usize {
+        self.depth.saturating_sub(1)
+    }
+
+    /// Return the position of the last mouse event read.
+    pub fn mouse_position(&self) -> Option<MousePosition> {
+        self.input.mouse_position
+    }
+
+    /// Return the text of the last paste read.
+    pub fn pasted_text(&self) -> Option<&str> {
+        self.input.pasted.as_deref()
+    }
+
+    /// Call `handler` whenever `fd` is ready to be read while waiting
+    /// for input, replacing any previous handler for it.
+    pub fn watch_fd(&mut self, fd: RawFd, handler: FdHandler) {
+        self.unwatch_fd(fd);
+        self.fd_watches.push(FdWatch { fd, handler });
+    }
+
+    /// Stop watching `fd`.
+    pub fn unwatch_fd(&mut self, fd: RawFd) {
+        self.fd_watches.retain(|watch| watch.fd != fd);
+    }
+
     pub fn unpeek_keys(&mut self, keys: Vec<Key>) {
         for k in keys.into_iter() {
             self.pending_input.push_back(k);
         }
     }
 
+    /// Push `keys` so they are the next keys to be read.
+    pub fn push_front_keys(&mut self, keys: &[Key]) {
+        for k in keys.iter().rev() {
+            self.pending_input.push_front(k.clone());
+        }
+    }
+
     pub fn complete(&mut self, result: Result<()>) {
         self.result = Some(result)
     }
@@ -48,24 +128,157 @@ impl EventLoopState {
     }
 }
 
+/// Wait until there is input, a signal, a watched file descriptor
+/// is ready or `timeout` expires, and handle what happened. Return
+/// true if there was new input.
+fn wait_for_input(term: &mut Term, context: &mut Context, timeout: Option<Duration>) -> bool {
+    let state = &context.event_loop;
+    let mut fds = vec![PollFd::new(libc::STDIN_FILENO, PollFlags::POLLIN)];
+    if let Some(pipe) = &state.signal_pipe {
+        fds.push(PollFd::new(pipe.as_raw_fd(), PollFlags::POLLIN));
+    }
+    let watched: Vec<(RawFd, FdHandler)> = state
+        .fd_watches
+        .iter()
+        .map(|watch| (watch.fd, watch.handler))
+        .collect();
+    for (fd, _) in &watched {
+        fds.push(PollFd::new(*fd, PollFlags::POLLIN));
+    }
+
+    // Round up, so we do not wake up just before a timer is due.
+    let timeout = timeout.map_or(-1, |timeout| (timeout.as_micros() as i64 + 999) / 1000);
+    match poll(
+        &mut fds,
+        timeout.min(libc::c_int::MAX as i64) as libc::c_int,
+    ) {
+        Ok(_) => {}
+        Err(nix::Error::Sys(Errno::EINTR)) => return false,
+        Err(err) => panic!("Could not wait for input: {}", err),
+    }
+
+    let is_ready = |fd: &PollFd| {
+        fd.revents().is_some_and(|revents| {
+            revents.intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR)
+        })
+    };
+
+    if let Some(pipe) = &mut context.event_loop.signal_pipe {
+        if is_ready(&fds[1]) {
+            // Only the wake up matters. What the signals were about
+            // is in the flags checked by `handle_signals`.
+            let mut buf = [0u8; 64];
+            while let Ok(n) = pipe.read(&mut buf) {
+                if n < buf.len() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let first_watch = fds.len() - watched.len();
+    for (fd, (watched_fd, handler)) in fds[first_watch..].iter().zip(watched) {
+        if is_ready(fd) {
+            handler(context, term, watched_fd);
+        }
+    }
+
+    if is_ready(&fds[0]) {
+        let mut buf = [0u8; 1024];
+        let n = term::read_input(&mut buf);
+        if n == 0 {
+            // The terminal is ready but there is nothing to read: it
+            // was closed. Terminate as if it hung up, even if no
+            // SIGHUP comes, instead of polling it again and again.
+            context
+                .terminated
+                .store(signal_hook::SIGHUP as usize, Ordering::Relaxed);
+        }
+        context.event_loop.input.feed(&buf[..n]);
+        n > 0
+    } else {
+        false
+    }
+}
+
+/// Wait up to `timeout` for the terminal to reply with the content
+/// of the clipboard. Keys read in the meantime are kept to be read
+/// later.
+pub fn read_clipboard_reply(
+    term: &mut Term,
+    context: &mut Context,
+    timeout: Duration,
+) -> Option<String> {
+    context.event_loop.input.clipboard = None;
+    let deadline = Instant::now() + timeout;
+    loop {
+        let state = &mut context.event_loop;
+        while let Some(key) = state.input.next_key(false) {
+            state.pending_input.push_back(key);
+        }
+        if let Some(text) = state.input.clipboard.take() {
+            return Some(text);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        wait_for_input(term, context, Some(deadline - now));
+    }
+}
+
 pub fn read_key(term: &mut Term, context: &mut Context) -> Key {
-    context
-        .event_loop
-        .pending_input
-        .pop_front()
-        .unwrap_or_else(|| {
+    if let Some(key) = context.event_loop.pending_input.pop_front() {
+        return key;
+    }
+
+    refresh_screen(term, context).unwrap();
+    context.timers.start_idle(Instant::now());
+    let key = loop {
+        if let Some(key) = context.event_loop.input.next_key(false) {
+            break key;
+        }
+
+        let mut redisplay = signals::handle_signals(term, context);
+
+        let now = Instant::now();
+        for handler in context.timers.take_due(now) {
+            let _ = handler(context, term);
+            redisplay = true;
+        }
+
+        if redisplay {
+            adjust_scroll(term, context);
             refresh_screen(term, context).unwrap();
-            loop {
-                if let Some(key) = read_key_timeout() {
-                    return key;
-                } else {
-                    if reconciliate_term_size(term, &context.was_resized) {
-                        adjust_scroll(term, context);
-                        refresh_screen(term, context).unwrap();
-                    }
-                }
+        }
+
+        // An ESC could be the start of an escape sequence, or the ESC
+        // key if nothing follows it soon.
+        let partial = context.event_loop.input.is_pending();
+        let timeout = match context.timers.next_timeout(Instant::now()) {
+            Some(timeout) if partial => Some(timeout.min(ESC_TIMEOUT)),
+            Some(timeout) => Some(timeout),
+            None if partial => Some(ESC_TIMEOUT),
+            None => None,
+        };
+
+        let start = Instant::now();
+        let has_input = wait_for_input(term, context, timeout);
+        if partial && !has_input && start.elapsed() >= ESC_TIMEOUT {
+            if let Some(key) = context.event_loop.input.next_key(true) {
+                break key;
             }
-        })
+        }
+    };
+    context.timers.stop_idle();
+
+    // Only keys typed by the user are recorded. Keys replayed from
+    // `pending_input` come from a macro already.
+    if let Some(recording) = &mut context.kmacro.recording {
+        recording.push(key.clone());
+    }
+
+    key
 }
 
 fn is_self_insert(keys: &Vec<Key>) -> Option<char> {
@@ -79,32 +292,127 @@ fn is_self_insert(keys: &Vec<Key>) -> Option<char> {
 }
 
 /// Process user input.
-fn process_user_input(term: &mut Term, context: &mut Context) -> std::result::Result<(), Vec<Key>> {
+///
+/// Return the result of the command executed, or the keys read if
+/// they were not bound to any command.
+fn process_user_input(
+    term: &mut Term,
+    context: &mut Context,
+) -> std::result::Result<commands::Result, Vec<Key>> {
     let cmd = read::read_key_binding(term, context);
 
-    let minibuffer = &mut context.buffer_list.minibuffer;
     if !context.window_list.minibuffer_focused {
-        minibuffer.truncate();
+        context.buffer_list.minibuffer.truncate();
     }
 
-    // Execute the command.
+    // Execute the command, and repeat it at the fake cursors.
     match cmd {
-        Ok(handler) => {
-            let _ = handler(context, term);
-            Ok(())
+        Ok(command) => {
+            let result = commands::call_interactively(context, term, command);
+            if command.run_for_all_cursors {
+                multiple_cursors::run_for_fake_cursors(context, |context| {
+                    commands::call_interactively(context, term, command)
+                });
+            }
+            Ok(result)
         }
         Err(keys) => {
             if let Some(ch) = is_self_insert(&keys) {
-                commands::insert_char(context, ch);
-                Ok(())
+                if commands::barf_if_buffer_read_only(context).is_err() {
+                    return Ok(Err(()));
+                }
+                let result = commands::insert_char(context, ch);
+                multiple_cursors::run_for_fake_cursors(context, |context| {
+                    commands::insert_char(context, ch)
+                });
+                Ok(result)
             } else {
-                minibuffer.set(format!("{} is undefined", Key::format_seq(&keys)));
+                let text = format!("{} is undefined", Key::format_seq(&keys));
+                if context.window_list.minibuffer_focused {
+                    // Prompts that exit on undefined keys, like
+                    // isearch, replay them, so do not log them.
+                    context.buffer_list.minibuffer.set(text);
+                } else {
+                    message(context, text);
+                }
                 Err(keys)
             }
         }
     }
 }
 
+/// Read and execute a command, doing the bookkeeping around it.
+fn command_step(
+    term: &mut Term,
+    context: &mut Context,
+) -> std::result::Result<commands::Result, Vec<Key>> {
+    context.goal_column.to_preserve = false;
+    context.kill_ring.to_preserve = false;
+
+    let window = context.window_list.get_current_window();
+    let (buffer_ref, cursor) = (
+        window.buffer_ref,
+        context.buffer_list.resolve_ref(window.buffer_ref).cursor(),
+    );
+
+    let result = process_user_input(term, context);
+
+    // Keep point out of invisible text, moving it past the text in
+    // the direction it moved, and in the accessible portion.
+    let window = context.window_list.get_current_window();
+    let same_buffer = window.buffer_ref == buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_cursor(
+        buffer
+            .cursor()
+            .clamp(buffer.point_min(), buffer.point_max()),
+    );
+    buffer.move_out_of_invisible(if same_buffer { cursor } else { buffer.cursor() });
+    commands::show_paren_update(context);
+    commands::region_update(context);
+    rectangle::rectangle_mark_update(context);
+
+    if !context.goal_column.to_preserve {
+        context.goal_column.column = None;
+    }
+    if !context.kill_ring.to_preserve {
+        context.kill_ring.append = false;
+    }
+    clipboard::sync_kill_ring(term, context);
+
+    adjust_scroll(term, context);
+
+    result
+}
+
+/// Execute `keys` as if they were typed by the user.
+///
+/// The execution stops as soon as a command fails or a key is not
+/// bound, discarding the rest of `keys`.
+pub fn execute_kbd_macro(term: &mut Term, context: &mut Context, keys: &[Key]) -> commands::Result {
+    let base = context.event_loop.pending_input.len();
+    context.event_loop.push_front_keys(keys);
+
+    let result = loop {
+        if context.event_loop.pending_input.len() <= base {
+            break Ok(());
+        }
+        if context.event_loop.result.is_some() {
+            break Err(());
+        }
+        match command_step(term, context) {
+            Ok(Ok(())) => {}
+            _ => break Err(()),
+        }
+    };
+
+    while context.event_loop.pending_input.len() > base {
+        context.event_loop.pending_input.pop_front();
+    }
+
+    result
+}
+
 pub fn event_loop<F>(
     term: &mut Term,
     context: &mut Context,
@@ -116,11 +424,10 @@ where
 {
     // Save the context for a recursive event loop.
     let original_result = context.event_loop.result.take();
+    context.event_loop.depth += 1;
 
     let result = loop {
-        context.goal_column.to_preserve = false;
-
-        match process_user_input(term, context) {
+        match command_step(term, context) {
             Ok(_) => {}
             Err(keys) => {
                 if exit_on_undefined {
@@ -130,12 +437,6 @@ where
             }
         }
 
-        if !context.goal_column.to_preserve {
-            context.goal_column.column = None;
-        }
-
-        adjust_scroll(term, context);
-
         if let Some(result) = context.event_loop.result.take() {
             break result;
         }
@@ -145,6 +446,7 @@ where
 
     //  the saved context.
     context.event_loop.result = original_result;
+    context.event_loop.depth -= 1;
 
     result
 }
@@ -0,0 +1,99 @@
+//! Faces
+//!
+//! A face is a set of attributes, like colors, used to display text
+//! on the terminal.
+//!
+
+use crate::term::Term;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Face {
+    /// Foreground and background colors, as 256-color codes.
+    pub fg: Option<u8>,
+    pub bg: Option<u8>,
+    pub reverse: bool,
+    pub bold: bool,
+}
+
+impl Face {
+    /// Set the attributes of the terminal to display text with this face.
+    pub fn apply(&self, term: &mut Term) {
+        term.reset_attr();
+        if let Some(fg) = self.fg {
+            term.fg(fg);
+        }
+        if let Some(bg) = self.bg {
+            term.bg(bg);
+        }
+        if self.reverse {
+            term.csi("7m");
+        }
+        if self.bold {
+            term.csi("1m");
+        }
+    }
+}
+
+/// Face for the text matched by the current search.
+pub const ISEARCH: Face = Face {
+    fg: None,
+    bg: None,
+    reverse: true,
+    bold: false,
+};
+
+/// Face for the fake cursors of multiple cursors.
+pub const FAKE_CURSOR: Face = Face {
+    fg: None,
+    bg: None,
+    reverse: true,
+    bold: false,
+};
+
+/// Face for the region, when it is highlighted.
+pub const REGION: Face = Face {
+    fg: None,
+    bg: Some(238),
+    reverse: false,
+    bold: false,
+};
+
+/// Face for a delimiter and its matching delimiter.
+pub const SHOW_PAREN_MATCH: Face = Face {
+    fg: None,
+    bg: Some(67),
+    reverse: false,
+    bold: false,
+};
+
+/// Face for a delimiter that does not have a matching delimiter.
+pub const SHOW_PAREN_MISMATCH: Face = Face {
+    fg: Some(15),
+    bg: Some(129),
+    reverse: false,
+    bold: false,
+};
+
+/// Face for the indicators of truncated and continued lines.
+pub const LINE_INDICATOR: Face = Face {
+    fg: Some(240),
+    bg: None,
+    reverse: false,
+    bold: false,
+};
+
+/// Face for line numbers.
+pub const LINE_NUMBER: Face = Face {
+    fg: Some(240),
+    bg: None,
+    reverse: false,
+    bold: false,
+};
+
+/// Face for the number of the line of point.
+pub const LINE_NUMBER_CURRENT: Face = Face {
+    fg: Some(250),
+    bg: None,
+    reverse: false,
+    bold: true,
+};
@@ -0,0 +1,351 @@
+//! Folding
+//!
+//! A fold hides the lines after a line with an invisible overlay,
+//! from the end of the line to the end of the last hidden line, shown
+//! as an ellipsis. `hs-minor-mode` folds the blocks between braces,
+//! keeping the line of the closing brace, and `outline-minor-mode`
+//! folds the sections under headings: lines starting with `#` in
+//! Markdown files and `*` in other files, like Org files.
+//!
+
+use std::ops::RangeInclusive;
+
+use crate::commands::Result;
+use crate::keymap::Keymap;
+use crate::overlay::Overlay;
+use crate::sexp;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Buffer, Context, Cursor};
+
+/// The overlay source of the folds of `hs-minor-mode`.
+const HS_SOURCE: &str = "hs";
+
+/// The overlay source of the folds of `outline-minor-mode`.
+const OUTLINE_SOURCE: &str = "outline";
+
+/// The string shown in place of the hidden lines.
+const ELLIPSIS: &str = "...";
+
+fn current_buffer(context: &mut Context) -> &mut Buffer {
+    let window = context.window_list.get_current_window();
+    context.buffer_list.resolve_ref_as_mut(window.buffer_ref)
+}
+
+fn line_end(buffer: &Buffer, line: usize) -> Cursor {
+    Cursor {
+        line,
+        column: buffer.get_line_unchecked(line).len(),
+    }
+}
+
+/// Delete the folds of `source` that start on `lines`.
+fn unfold(buffer: &mut Buffer, source: &str, lines: RangeInclusive<usize>) {
+    let folds: Vec<_> = buffer
+        .overlays
+        .iter()
+        .filter(|overlay| overlay.source == source && lines.contains(&overlay.start.line))
+        .map(|overlay| overlay.id())
+        .collect();
+    for id in folds {
+        buffer.remove_overlay(id);
+    }
+}
+
+/// Hide the lines after `header` up to `last`, replacing the folds
+/// of `source` inside them.
+fn fold(buffer: &mut Buffer, source: &'static str, header: usize, last: usize) {
+    unfold(buffer, source, header..=last);
+    let mut overlay = Overlay::new(source, line_end(buffer, header), line_end(buffer, last));
+    overlay.invisible = true;
+    overlay.before_string = Some(ELLIPSIS.to_string());
+    // Text typed at the end of the header line stays visible.
+    overlay.front_advance = true;
+    overlay.evaporate = true;
+    buffer.add_overlay(overlay);
+}
+
+/// Return the lines of the fold of `source` at `pos`: the fold that
+/// starts on its line, or that it is in.
+fn fold_at(buffer: &Buffer, source: &str, pos: Cursor) -> Option<RangeInclusive<usize>> {
+    buffer
+        .overlays
+        .iter()
+        .find(|overlay| {
+            overlay.source == source
+                && (overlay.start.line == pos.line || overlay.start < pos && pos <= overlay.end)
+        })
+        .map(|overlay| overlay.start.line..=overlay.end.line)
+}
+
+/// Return the lines that can be hidden in the brace blocks of
+/// `buffer`: from the line of the open brace to the line before the
+/// close brace. Blocks without any such line are left out.
+fn hs_blocks(buffer: &Buffer) -> Vec<(usize, usize)> {
+    sexp::brace_blocks(buffer)
+        .into_iter()
+        .filter(|(open, close)| close.line > open.line + 1)
+        .map(|(open, close)| (open.line, close.line - 1))
+        .collect()
+}
+
+/// Return the block at `line`: the first block that starts on it,
+/// or else the innermost block that contains it.
+fn hs_block_at(buffer: &Buffer, line: usize) -> Option<(usize, usize)> {
+    let blocks = hs_blocks(buffer);
+    blocks
+        .iter()
+        .find(|(header, _)| *header == line)
+        .or_else(|| {
+            blocks
+                .iter()
+                .rev()
+                .find(|(header, last)| *header < line && line <= *last + 1)
+        })
+        .copied()
+}
+
+pub fn hs_minor_mode_map() -> Keymap {
+    let mut keymap = Keymap::new();
+    let mut prefix = Keymap::new();
+    prefix.define_key("C-c", "hs-toggle-hiding");
+    prefix.define_key("C-h", "hs-hide-block");
+    prefix.define_key("C-s", "hs-show-block");
+    prefix.define_key("C-M-h", "hs-hide-all");
+    prefix.define_key("C-M-s", "hs-show-all");
+    let mut c_c = Keymap::new();
+    c_c.define_keymap("@", prefix);
+    keymap.define_keymap("C-c", c_c);
+    keymap
+}
+
+pub fn hs_minor_mode(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    buffer.hs_minor_mode = !buffer.hs_minor_mode;
+    if buffer.hs_minor_mode {
+        message(context, "Hs minor mode enabled in current buffer");
+    } else {
+        buffer.clear_overlays(HS_SOURCE);
+        message(context, "Hs minor mode disabled in current buffer");
+    }
+    Ok(())
+}
+
+pub fn hs_hide_block(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    match hs_block_at(buffer, buffer.cursor().line) {
+        Some((header, last)) => {
+            fold(buffer, HS_SOURCE, header, last);
+            Ok(())
+        }
+        None => {
+            message(context, "No block to hide");
+            Err(())
+        }
+    }
+}
+
+pub fn hs_show_block(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    if let Some(lines) = fold_at(buffer, HS_SOURCE, buffer.cursor()) {
+        unfold(buffer, HS_SOURCE, lines);
+    }
+    Ok(())
+}
+
+pub fn hs_toggle_hiding(context: &mut Context, term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    if fold_at(buffer, HS_SOURCE, buffer.cursor()).is_some() {
+        hs_show_block(context, term)
+    } else {
+        hs_hide_block(context, term)
+    }
+}
+
+/// Hide the outermost blocks of the buffer.
+pub fn hs_hide_all(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    buffer.clear_overlays(HS_SOURCE);
+    let mut end = None;
+    for (header, last) in hs_blocks(buffer) {
+        if end.is_some_and(|end| header <= end) {
+            continue;
+        }
+        fold(buffer, HS_SOURCE, header, last);
+        end = Some(last);
+    }
+    Ok(())
+}
+
+pub fn hs_show_all(context: &mut Context, _term: &mut Term) -> Result {
+    current_buffer(context).clear_overlays(HS_SOURCE);
+    Ok(())
+}
+
+/// Return the level of the heading at `line`, the number of heading
+/// characters it starts with, or None if it is not a heading.
+fn heading_level(buffer: &Buffer, line: usize) -> Option<usize> {
+    let markdown = buffer
+        .filename
+        .as_deref()
+        .is_some_and(|name| name.ends_with(".md") || name.ends_with(".markdown"));
+    let marker = if markdown { '#' } else { '*' };
+    let text = buffer.get_line_unchecked(line);
+    let level = text.chars().take_while(|ch| *ch == marker).count();
+    match text[level..].chars().next() {
+        Some(' ') | None if level > 0 => Some(level),
+        _ => None,
+    }
+}
+
+/// Return the last line of the section of the heading at `header`,
+/// before the next heading of the same level or higher.
+fn subtree_end(buffer: &Buffer, header: usize) -> usize {
+    let level = heading_level(buffer, header).unwrap_or(0);
+    (header + 1..buffer.lines_count())
+        .find(|line| heading_level(buffer, *line).is_some_and(|other| other <= level))
+        .map_or(buffer.lines_count() - 1, |next| next - 1)
+}
+
+/// Return the visible heading at or before `line`.
+fn heading_at(buffer: &Buffer, line: usize) -> Option<usize> {
+    (0..=line)
+        .rev()
+        .find(|line| !buffer.is_line_hidden(*line) && heading_level(buffer, *line).is_some())
+}
+
+/// Return the visible heading at point, or report that there is none.
+fn current_heading(context: &mut Context) -> std::result::Result<usize, ()> {
+    let buffer = current_buffer(context);
+    match heading_at(buffer, buffer.cursor().line) {
+        Some(header) => Ok(header),
+        None => {
+            message(context, "Before first heading");
+            Err(())
+        }
+    }
+}
+
+pub fn outline_minor_mode_map() -> Keymap {
+    let mut keymap = Keymap::new();
+    let mut prefix = Keymap::new();
+    prefix.define_key("C-c", "outline-toggle-subtree");
+    prefix.define_key("C-d", "outline-hide-subtree");
+    prefix.define_key("C-s", "outline-show-subtree");
+    prefix.define_key("C-t", "outline-hide-body");
+    prefix.define_key("C-a", "outline-show-all");
+    let mut c_c = Keymap::new();
+    c_c.define_keymap("@", prefix);
+    keymap.define_keymap("C-c", c_c);
+    keymap
+}
+
+pub fn outline_minor_mode(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    buffer.outline_minor_mode = !buffer.outline_minor_mode;
+    if buffer.outline_minor_mode {
+        message(context, "Outline minor mode enabled in current buffer");
+    } else {
+        buffer.clear_overlays(OUTLINE_SOURCE);
+        message(context, "Outline minor mode disabled in current buffer");
+    }
+    Ok(())
+}
+
+pub fn outline_hide_subtree(context: &mut Context, _term: &mut Term) -> Result {
+    let header = current_heading(context)?;
+    let buffer = current_buffer(context);
+    let last = subtree_end(buffer, header);
+    if last > header {
+        fold(buffer, OUTLINE_SOURCE, header, last);
+    }
+    Ok(())
+}
+
+pub fn outline_show_subtree(context: &mut Context, _term: &mut Term) -> Result {
+    let header = current_heading(context)?;
+    let buffer = current_buffer(context);
+    let last = subtree_end(buffer, header);
+    unfold(buffer, OUTLINE_SOURCE, header..=last);
+    Ok(())
+}
+
+pub fn outline_toggle_subtree(context: &mut Context, term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    if fold_at(buffer, OUTLINE_SOURCE, buffer.cursor()).is_some() {
+        outline_show_subtree(context, term)
+    } else {
+        outline_hide_subtree(context, term)
+    }
+}
+
+/// Hide the text under every heading, leaving only the headings.
+pub fn outline_hide_body(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    buffer.clear_overlays(OUTLINE_SOURCE);
+    let count = buffer.lines_count();
+    let headings: Vec<usize> = (0..count)
+        .filter(|line| heading_level(buffer, *line).is_some())
+        .collect();
+    let ends = headings
+        .iter()
+        .skip(1)
+        .map(|next| next - 1)
+        .chain(Some(count - 1));
+    for (header, last) in headings.iter().copied().zip(ends) {
+        if last > header {
+            fold(buffer, OUTLINE_SOURCE, header, last);
+        }
+    }
+    Ok(())
+}
+
+pub fn outline_show_all(context: &mut Context, _term: &mut Term) -> Result {
+    current_buffer(context).clear_overlays(OUTLINE_SOURCE);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::MajorMode;
+
+    #[test]
+    fn fold_brace_blocks() {
+        let mut buffer = Buffer::from_string(
+            "fn a() {\n    if x {\n        y();\n    }\n}\n\nfn b() {\n    z();\n}",
+        );
+        buffer.mode = MajorMode::rust();
+        assert_eq!(hs_blocks(&buffer), vec![(0, 3), (1, 2), (6, 7)]);
+        assert_eq!(hs_block_at(&buffer, 1), Some((1, 2)));
+        assert_eq!(hs_block_at(&buffer, 3), Some((1, 2)));
+        assert_eq!(hs_block_at(&buffer, 4), Some((0, 3)));
+        assert_eq!(hs_block_at(&buffer, 5), None);
+
+        fold(&mut buffer, HS_SOURCE, 1, 2);
+        assert!(buffer.is_line_hidden(2));
+        assert!(!buffer.is_line_hidden(3));
+        fold(&mut buffer, HS_SOURCE, 0, 3);
+        assert_eq!(buffer.overlays.iter().count(), 1);
+        assert!((1..=3).all(|line| buffer.is_line_hidden(line)));
+        assert_eq!(
+            fold_at(&buffer, HS_SOURCE, Cursor { line: 3, column: 5 }),
+            Some(0..=3)
+        );
+    }
+
+    #[test]
+    fn fold_outline_sections() {
+        let mut buffer = Buffer::from_string("# A\ntext\n## B\nmore\n# C\nend");
+        buffer.filename = Some("notes.md".to_string());
+        assert_eq!(heading_level(&buffer, 2), Some(2));
+        assert_eq!(heading_level(&buffer, 1), None);
+        assert_eq!(subtree_end(&buffer, 0), 3);
+        assert_eq!(subtree_end(&buffer, 2), 3);
+        assert_eq!(subtree_end(&buffer, 4), 5);
+
+        fold(&mut buffer, OUTLINE_SOURCE, 0, 3);
+        assert_eq!(heading_at(&buffer, 3), Some(0));
+        assert!(!buffer.is_line_hidden(4));
+    }
+}
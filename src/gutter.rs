@@ -0,0 +1,136 @@
+//! Gutter
+//!
+//! The gutter is the area at the left of a window, before the text.
+//! It shows line numbers if they are enabled, and a fringe column
+//! where subsystems like diagnostics, version control or a debugger
+//! can place a mark next to a line.
+//!
+
+use crate::face::Face;
+
+/// How line numbers are displayed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineNumbers {
+    /// The number of each line, from 1.
+    Absolute,
+    /// The distance of each line to the line of point.
+    Relative,
+    /// Relative numbers, but the absolute number on the line of
+    /// point.
+    Hybrid,
+}
+
+impl LineNumbers {
+    /// Return the number to display for `line` when point is on
+    /// `current`, both starting at zero.
+    pub fn number(self, line: usize, current: usize) -> usize {
+        match self {
+            LineNumbers::Absolute => line + 1,
+            LineNumbers::Hybrid if line == current => line + 1,
+            LineNumbers::Relative | LineNumbers::Hybrid => line.abs_diff(current),
+        }
+    }
+}
+
+/// A mark in the fringe next to a line.
+#[derive(Clone, Debug)]
+pub struct FringeMark {
+    /// The subsystem that placed the mark, to remove its marks
+    /// together.
+    pub source: &'static str,
+    pub line: usize,
+    pub ch: char,
+    pub face: Face,
+    /// When several marks are on the same line, the one with the
+    /// highest priority is shown.
+    pub priority: i32,
+}
+
+/// The fringe marks of a buffer.
+///
+/// Marks stay on their line number when text is inserted or deleted,
+/// so subsystems should place them again when the buffer changes.
+pub struct Fringe {
+    marks: Vec<FringeMark>,
+}
+
+impl Fringe {
+    pub fn new() -> Fringe {
+        Fringe { marks: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// Add `mark`, replacing the mark of the same source on the same
+    /// line.
+    pub fn add(&mut self, mark: FringeMark) {
+        self.marks
+            .retain(|m| m.source != mark.source || m.line != mark.line);
+        self.marks.push(mark);
+    }
+
+    /// Remove the mark of `source` on `line`, if there is one.
+    pub fn remove(&mut self, source: &str, line: usize) {
+        self.marks.retain(|m| m.source != source || m.line != line);
+    }
+
+    /// Remove all the marks of `source`.
+    pub fn clear(&mut self, source: &str) {
+        self.marks.retain(|m| m.source != source);
+    }
+
+    /// Return the mark to show next to `line`.
+    pub fn mark_at(&self, line: usize) -> Option<&FringeMark> {
+        self.marks
+            .iter()
+            .filter(|m| m.line == line)
+            .max_by_key(|m| m.priority)
+    }
+}
+
+/// Return the number of digits of `n`.
+pub fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(source: &'static str, line: usize, ch: char, priority: i32) -> FringeMark {
+        FringeMark {
+            source,
+            line,
+            ch,
+            face: Face::default(),
+            priority,
+        }
+    }
+
+    #[test]
+    fn number_lines() {
+        assert_eq!(LineNumbers::Absolute.number(4, 9), 5);
+        assert_eq!(LineNumbers::Relative.number(4, 9), 5);
+        assert_eq!(LineNumbers::Relative.number(9, 9), 0);
+        assert_eq!(LineNumbers::Hybrid.number(12, 9), 3);
+        assert_eq!(LineNumbers::Hybrid.number(9, 9), 10);
+    }
+
+    #[test]
+    fn show_the_mark_with_highest_priority() {
+        let mut fringe = Fringe::new();
+        fringe.add(mark("vc", 3, '+', 0));
+        fringe.add(mark("lint", 3, '!', 10));
+        fringe.add(mark("lint", 5, '?', 10));
+        assert_eq!(fringe.mark_at(3).unwrap().ch, '!');
+
+        fringe.clear("lint");
+        assert_eq!(fringe.mark_at(3).unwrap().ch, '+');
+        assert!(fringe.mark_at(5).is_none());
+
+        fringe.remove("vc", 3);
+        assert!(fringe.is_empty());
+    }
+}
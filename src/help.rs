@@ -0,0 +1,368 @@
+//! Help
+//!
+//! Commands that describe the keys, commands and modes of the
+//! editor. They write their description to the `*Help*` buffer,
+//! where the names of commands are cross-references to their own
+//! description.
+//!
+
+use crate::command_table;
+use crate::commands::Result;
+use crate::read;
+use crate::term::Term;
+use crate::window::{self, message};
+use crate::{Buffer, Context, Cursor, Key, Keymap};
+
+/// The name of the buffer where help is shown.
+pub const HELP_BUFFER: &str = "*Help*";
+
+/// A reference to a command in the help buffer.
+pub struct Reference {
+    pub start: Cursor,
+    pub end: Cursor,
+    pub command: &'static str,
+}
+
+pub struct HelpState {
+    /// The cross-references in the help buffer.
+    pub references: Vec<Reference>,
+}
+
+impl HelpState {
+    pub fn new() -> HelpState {
+        HelpState { references: vec![] }
+    }
+}
+
+/// The content of the help buffer being built.
+struct HelpText {
+    text: String,
+    references: Vec<Reference>,
+}
+
+impl HelpText {
+    fn new() -> HelpText {
+        HelpText {
+            text: String::new(),
+            references: vec![],
+        }
+    }
+
+    /// Return the position at the end of the text.
+    fn end(&self) -> Cursor {
+        let line_start = self.text.rfind('\n').map_or(0, |idx| idx + 1);
+        Cursor {
+            line: self.text.matches('\n').count(),
+            column: self.text.len() - line_start,
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// Append the name of `command` as a reference to it.
+    fn push_reference(&mut self, command: &'static str) {
+        let start = self.end();
+        self.push(command);
+        self.references.push(Reference {
+            start,
+            end: self.end(),
+            command,
+        });
+    }
+
+    /// Append a documentation string. Names of commands between
+    /// backquotes become references.
+    fn push_doc(&mut self, doc: &str) {
+        let mut rest = doc;
+        while let Some(open) = rest.find('`') {
+            self.push(&rest[..=open]);
+            rest = &rest[open + 1..];
+            if let Some(close) = rest.find('`') {
+                match command_table::find(&rest[..close]) {
+                    Some(command) => self.push_reference(command.name),
+                    None => self.push(&rest[..close]),
+                }
+                rest = &rest[close..];
+            }
+        }
+        self.push(rest);
+    }
+}
+
+/// Return the keymap of the current buffer.
+fn current_keymap(context: &Context) -> Keymap {
+    let window = context.window_list.get_current_window();
+    context
+        .buffer_list
+        .resolve_ref(window.buffer_ref)
+        .keymap
+        .clone()
+}
+
+/// Return a description of the keys that run the command called `name`.
+fn describe_keys(keymap: &Keymap, name: &str) -> Option<String> {
+    let keys = keymap.where_is(name);
+    if keys.is_empty() {
+        None
+    } else {
+        let keys: Vec<String> = keys.iter().map(|keys| Key::format_seq(keys)).collect();
+        Some(keys.join(", "))
+    }
+}
+
+fn help_mode_map() -> Keymap {
+    let mut keymap = Keymap::special_mode();
+    keymap.define_key("TAB", "forward-button");
+    keymap.define_key("RET", "push-button");
+    keymap
+}
+
+/// Show `help` in the help buffer, in another window, and select it.
+fn show_help(context: &mut Context, help: HelpText) {
+    let buffer_ref = context.buffer_list.get_or_create(HELP_BUFFER);
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.set(help.text.trim_end());
+    buffer.read_only = true;
+    buffer.keymap = help_mode_map();
+    context.help.references = help.references;
+
+    let window_list = &mut context.window_list;
+    let index = window_list.display_buffer(buffer_ref);
+    window_list.windows[index].scroll_line = 0;
+    window::select_window(context, index);
+
+    message(context, "Type q to quit the help window");
+}
+
+/// Write the description of `command` to `help`.
+fn push_command_description(
+    help: &mut HelpText,
+    keymap: &Keymap,
+    command: &command_table::Command,
+) {
+    help.push("`");
+    help.push_reference(command.name);
+    help.push("` is an interactive command.\n\n");
+    match describe_keys(keymap, command.name) {
+        Some(keys) => help.push(&format!("It is bound to {}.\n\n", keys)),
+        None => help.push("It is not bound to any key.\n\n"),
+    }
+    help.push_doc(command.doc);
+    help.push("\n");
+}
+
+/// Read the name of a command in the minibuffer.
+fn read_command(
+    term: &mut Term,
+    context: &mut Context,
+    prompt: &str,
+) -> std::result::Result<&'static command_table::Command, ()> {
+    let name = read::read_string(term, context, prompt, |_, _| {}, false).map_err(|_| ())?;
+    let name = name.trim();
+    command_table::find(name).ok_or_else(|| {
+        message(context, format!("No command named {}", name));
+    })
+}
+
+pub fn describe_key(context: &mut Context, term: &mut Term) -> Result {
+    context.buffer_list.minibuffer.set("Describe key: ");
+    let keymap = current_keymap(context);
+    let mut help = HelpText::new();
+
+    match read::read_key_binding(term, context) {
+        Ok(command) => {
+            let keys = Key::format_seq(&context.event_loop.this_command_keys);
+            help.push(&format!("{} runs the command `", keys));
+            help.push_reference(command.name);
+            help.push("`.\n\n");
+            push_command_description(&mut help, &keymap, command);
+        }
+        Err(keys) => {
+            let description = Key::format_seq(&keys);
+            match keys.as_slice() {
+                [key] if key.as_char().is_some() => {
+                    help.push(&format!("{} inserts itself in the buffer.\n", description))
+                }
+                _ => {
+                    message(context, format!("{} is undefined", description));
+                    return Err(());
+                }
+            }
+        }
+    }
+
+    show_help(context, help);
+    Ok(())
+}
+
+pub fn describe_function(context: &mut Context, term: &mut Term) -> Result {
+    let keymap = current_keymap(context);
+    let command = read_command(term, context, "Describe function: ")?;
+    let mut help = HelpText::new();
+    push_command_description(&mut help, &keymap, command);
+    show_help(context, help);
+    Ok(())
+}
+
+pub fn describe_bindings(context: &mut Context, _term: &mut Term) -> Result {
+    let keymap = current_keymap(context);
+    let mut help = HelpText::new();
+
+    help.push("key             binding\n");
+    help.push("---             -------\n\n");
+    for (keys, command) in keymap.bindings() {
+        help.push(&format!("{:16}", Key::format_seq(&keys)));
+        help.push_reference(command.name);
+        help.push("\n");
+    }
+
+    show_help(context, help);
+    Ok(())
+}
+
+pub fn where_is(context: &mut Context, term: &mut Term) -> Result {
+    let keymap = current_keymap(context);
+    let command = read_command(term, context, "Where is command: ")?;
+    let mut help = HelpText::new();
+
+    help.push("`");
+    help.push_reference(command.name);
+    match describe_keys(&keymap, command.name) {
+        Some(keys) => help.push(&format!("` is on {}\n", keys)),
+        None => help.push("` is not on any key\n"),
+    }
+
+    show_help(context, help);
+    Ok(())
+}
+
+pub fn apropos_command(context: &mut Context, term: &mut Term) -> Result {
+    let keymap = current_keymap(context);
+    let pattern =
+        read::read_string(term, context, "Apropos command: ", |_, _| {}, false).map_err(|_| ())?;
+    let pattern = pattern.trim();
+
+    let mut commands: Vec<_> = command_table::all()
+        .iter()
+        .filter(|command| command.name.contains(pattern))
+        .collect();
+    if commands.is_empty() {
+        message(context, format!("No apropos matches for `{}'", pattern));
+        return Err(());
+    }
+    commands.sort_by_key(|command| command.name);
+
+    let mut help = HelpText::new();
+    for command in commands {
+        help.push_reference(command.name);
+        if let Some(keys) = describe_keys(&keymap, command.name) {
+            let padding = 30usize.saturating_sub(command.name.len()).max(1);
+            help.push(&format!("{:padding$}{}", "", keys, padding = padding));
+        }
+        help.push("\n   ");
+        help.push_doc(command.doc.lines().next().unwrap_or(""));
+        help.push("\n");
+    }
+
+    show_help(context, help);
+    Ok(())
+}
+
+pub fn describe_mode(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer: &Buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let mut help = HelpText::new();
+
+    let minor_modes = [
+        ("Show-Paren", "show-paren-mode", context.show_paren_mode),
+        ("Indent-Tabs", "indent-tabs-mode", buffer.indent_tabs_mode),
+        ("Visual-Line", "visual-line-mode", window.visual_line_mode),
+        (
+            "Truncate-Lines",
+            "toggle-truncate-lines",
+            window.truncate_lines,
+        ),
+    ];
+
+    help.push("Enabled minor modes:\n\n");
+    for (name, command, _) in minor_modes.iter().filter(|(_, _, enabled)| *enabled) {
+        help.push_doc(&format!("  {} mode (`{}`)\n", name, command));
+    }
+
+    help.push(&format!("\n{} mode:\n\n", buffer.mode.name));
+    help.push_doc(buffer.mode.doc);
+    help.push("\n");
+
+    show_help(context, help);
+    Ok(())
+}
+
+/// Move to the next cross-reference in the help buffer, going back to
+/// the first one after the last.
+pub fn forward_button(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let references = &context.help.references;
+
+    let next = references
+        .iter()
+        .find(|reference| reference.start > buffer.cursor())
+        .or_else(|| references.first());
+    match next {
+        Some(reference) => {
+            buffer.set_cursor(reference.start);
+            Ok(())
+        }
+        None => {
+            message(context, "No cross-references in this buffer");
+            Err(())
+        }
+    }
+}
+
+/// Show the description of the command referenced at the cursor.
+pub fn push_button(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let cursor = buffer.cursor();
+
+    let command = context
+        .help
+        .references
+        .iter()
+        .find(|reference| reference.start <= cursor && cursor < reference.end)
+        .and_then(|reference| command_table::find(reference.command));
+
+    match command {
+        Some(command) => {
+            let keymap = current_keymap(context);
+            let mut help = HelpText::new();
+            push_command_description(&mut help, &keymap, command);
+            show_help(context, help);
+            Ok(())
+        }
+        None => {
+            message(context, "No cross-reference here");
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_references_in_docs() {
+        let mut help = HelpText::new();
+        help.push("Intro\n");
+        help.push_doc("See `yank` and `no-such-command`.");
+        assert_eq!(help.text, "Intro\nSee `yank` and `no-such-command`.");
+        assert_eq!(help.references.len(), 1);
+        assert_eq!(help.references[0].command, "yank");
+        assert_eq!(help.references[0].start, Cursor { line: 1, column: 5 });
+        assert_eq!(help.references[0].end, Cursor { line: 1, column: 9 });
+    }
+}
@@ -0,0 +1,212 @@
+//! Indentation
+//!
+//! Each major mode provides a function to compute the indentation a
+//! line should have. The functions in this module change the
+//! indentation of lines, using tabs or spaces according to the
+//! buffer settings.
+//!
+
+use crate::buffer::display_width;
+use crate::sexp;
+use crate::syntax::SyntaxClass;
+use crate::{Buffer, Cursor};
+
+/// A function that returns the column a line should be indented to,
+/// given the innermost open delimiter of the expressions containing
+/// the start of the line.
+pub type IndentFunction = fn(&Buffer, usize, Option<Cursor>) -> usize;
+
+/// Return the byte index where the indentation of `text` ends.
+fn indentation_end(text: &str) -> usize {
+    text.find(|ch| ch != ' ' && ch != '\t')
+        .unwrap_or(text.len())
+}
+
+fn is_blank_line(buffer: &Buffer, line: usize) -> bool {
+    buffer.get_line_unchecked(line).trim().is_empty()
+}
+
+/// Return the indentation of `line`, in columns.
+pub fn current_indentation(buffer: &Buffer, line: usize) -> usize {
+    let text = buffer.get_line_unchecked(line);
+    display_width(&text[..indentation_end(text)], buffer.tab_width)
+}
+
+/// Indent like the previous non-blank line.
+pub fn indent_relative(buffer: &Buffer, line: usize, _open: Option<Cursor>) -> usize {
+    (0..line)
+        .rev()
+        .find(|&l| !is_blank_line(buffer, l))
+        .map_or(0, |l| current_indentation(buffer, l))
+}
+
+/// Indent according to the nesting of delimiters, as in C-like
+/// languages.
+///
+/// Lines inside a block are indented one level more than the line
+/// where the block starts. Lines inside parenthesis are aligned with
+/// the first element after the open parenthesis, if there is one in
+/// the same line.
+pub fn indent_braces(buffer: &Buffer, line: usize, open: Option<Cursor>) -> usize {
+    let open = match open {
+        Some(open) => open,
+        None => return 0,
+    };
+
+    let table = &buffer.mode.syntax_table;
+    let closes = buffer
+        .get_line_unchecked(line)
+        .trim_start()
+        .chars()
+        .next()
+        .is_some_and(|ch| matches!(table.class(ch), SyntaxClass::Close(_)));
+
+    let open_line = buffer.get_line_unchecked(open.line);
+    let after_open = buffer.forward_pos(open).unwrap();
+    let rest = &open_line[after_open.column..];
+    let first_element = after_open.column + indentation_end(rest);
+
+    let is_comment = table
+        .line_comment
+        .is_some_and(|comment| rest.trim_start().starts_with(comment));
+
+    if buffer.char_after(open) != Some('{') && first_element < open_line.len() && !is_comment {
+        let position = if closes {
+            open
+        } else {
+            Cursor {
+                line: open.line,
+                column: first_element,
+            }
+        };
+        buffer.display_column(position)
+    } else if closes {
+        current_indentation(buffer, open.line)
+    } else {
+        current_indentation(buffer, open.line) + buffer.mode.indent_offset
+    }
+}
+
+/// Change the indentation of `line` to `column`.
+///
+/// If the cursor is in the indentation, it is moved to the end of
+/// it. Otherwise it stays at the same place relative to the text.
+pub fn indent_line_to(buffer: &mut Buffer, line: usize, column: usize) {
+    let text = buffer.get_line_unchecked(line);
+    let old_end = indentation_end(text);
+
+    let indentation = if buffer.indent_tabs_mode {
+        "\t".repeat(column / buffer.tab_width) + &" ".repeat(column % buffer.tab_width)
+    } else {
+        " ".repeat(column)
+    };
+
+    if text[..old_end] != indentation {
+        let start = Cursor { line, column: 0 };
+        buffer.delete_region(
+            start,
+            Cursor {
+                line,
+                column: old_end,
+            },
+        );
+        buffer.insert_at(start, &indentation);
+    }
+
+    // The cursor moved with the text, unless it was in the old
+    // indentation.
+    let cursor = buffer.cursor();
+    if cursor.line == line && cursor.column < indentation.len() {
+        buffer.set_cursor(Cursor {
+            line,
+            column: indentation.len(),
+        });
+    }
+}
+
+/// Indent `line` according to the major mode of the buffer, with
+/// `open` the innermost open delimiter containing its start.
+fn indent_line_in(buffer: &mut Buffer, line: usize, open: Option<Cursor>) {
+    let column = if is_blank_line(buffer, line) && buffer.cursor().line != line {
+        0
+    } else {
+        (buffer.mode.indent_function)(buffer, line, open)
+    };
+    indent_line_to(buffer, line, column);
+}
+
+/// Indent `line` according to the major mode of the buffer.
+pub fn indent_line(buffer: &mut Buffer, line: usize) {
+    let open = sexp::backward_up_list(buffer, Cursor { line, column: 0 }).ok();
+    indent_line_in(buffer, line, open);
+}
+
+/// Indent the lines from `start` to `end`, both included, according
+/// to the major mode of the buffer.
+///
+/// The buffer is split into tokens once, and the tokens are updated
+/// as the lines are indented.
+pub fn indent_region(buffer: &mut Buffer, start: usize, end: usize) {
+    let mut opens = sexp::OpenDelimiters::new(sexp::buffer_tokens(buffer).to_vec());
+    for line in start..=end {
+        let open = opens.containing(Cursor { line, column: 0 });
+        let old_end = indentation_end(buffer.get_line_unchecked(line));
+        indent_line_in(buffer, line, open);
+        let new_end = indentation_end(buffer.get_line_unchecked(line));
+        opens.shift_line(line, new_end as isize - old_end as isize);
+    }
+}
+
+/// Add `delta` columns to the indentation of the lines from `start`
+/// to `end`, both included. Blank lines are not modified.
+pub fn indent_rigidly(buffer: &mut Buffer, start: usize, end: usize, delta: isize) {
+    for line in start..=end {
+        if !is_blank_line(buffer, line) {
+            let column = current_indentation(buffer, line) as isize + delta;
+            indent_line_to(buffer, line, column.max(0) as usize);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::MajorMode;
+
+    fn indent_all(text: &str) -> String {
+        let mut buffer = Buffer::from_string(text);
+        buffer.set_mode(MajorMode::rust());
+        for line in 0..buffer.lines_count() {
+            indent_line(&mut buffer, line);
+        }
+        buffer.to_string()
+    }
+
+    #[test]
+    fn indent_blocks_and_arguments() {
+        assert_eq!(
+            indent_all("fn f() {\nif x {\ng(a,\nb);\n}\n}"),
+            "fn f() {\n    if x {\n        g(a,\n          b);\n    }\n}"
+        );
+    }
+
+    #[test]
+    fn indent_regions_like_lines() {
+        let text = "fn f() {\n  if x {\ng(a, (b,\n      c),\nd);\n\t}\n    }";
+        let mut buffer = Buffer::from_string(text);
+        buffer.set_mode(MajorMode::rust());
+        let last = buffer.lines_count() - 1;
+        indent_region(&mut buffer, 0, last);
+        assert_eq!(buffer.to_string(), indent_all(text));
+    }
+
+    #[test]
+    fn indent_with_tabs() {
+        let mut buffer = Buffer::from_string("x");
+        buffer.tab_width = 4;
+        indent_line_to(&mut buffer, 0, 10);
+        assert_eq!(buffer.to_string(), "\t\t  x");
+        assert_eq!(current_indentation(&buffer, 0), 10);
+        assert_eq!(buffer.cursor().column, 4);
+    }
+}
@@ -0,0 +1,407 @@
+//! Terminal input
+//!
+//! The terminal sends bytes. Most keys are a single byte, but
+//! non-ASCII characters are encoded in UTF-8 and other keys, like the
+//! arrows, are sent as escape sequences. The decoder turns those
+//! bytes into keys as they arrive.
+//!
+//! Mouse events are reported in the SGR format (`ESC [ < b ; x ; y M`)
+//! and decoded into mouse keys, like `down-mouse-1`. Their position
+//! is kept apart, as keys do not have one.
+//!
+//! Pasted text comes between `ESC [ 200 ~` and `ESC [ 201 ~`, so it is
+//! not taken as keys typed by the user. It is decoded into a single
+//! `xterm-paste` key, and the text is kept apart too.
+//!
+//! The terminal also replies to queries of the clipboard with OSC 52
+//! sequences. They are not keys, so they are consumed silently and
+//! only their content is kept.
+//!
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::clipboard;
+use crate::Key;
+
+const ESC: u8 = 0x1b;
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// How long to wait for the rest of an escape sequence before taking
+/// ESC as a key by itself.
+pub const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// A position of the screen, starting at zero.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MousePosition {
+    pub row: usize,
+    pub column: usize,
+}
+
+pub struct InputDecoder {
+    bytes: VecDeque<u8>,
+    /// The number of bytes of an unfinished paste that were already
+    /// searched for the end of the paste.
+    paste_searched: usize,
+    /// The position of the last mouse event decoded.
+    pub mouse_position: Option<MousePosition>,
+    /// The text of the last paste decoded.
+    pub pasted: Option<String>,
+    /// The content of the clipboard, from the last reply of the
+    /// terminal to a query.
+    pub clipboard: Option<String>,
+}
+
+/// The result of decoding the bytes at the start of the input.
+enum Decoded {
+    /// A key, and the number of bytes it took.
+    Key(Key, usize),
+    /// A mouse event, where it happened and the number of bytes it
+    /// took.
+    Mouse(Key, MousePosition, usize),
+    /// Pasted text, and the number of bytes it took.
+    Paste(String, usize),
+    /// The content of the clipboard, and the number of bytes it took.
+    Clipboard(String, usize),
+    /// A sequence we do not know about, and the number of bytes it
+    /// took.
+    Ignored(usize),
+    /// More bytes are needed.
+    Incomplete,
+}
+
+impl InputDecoder {
+    pub fn new() -> InputDecoder {
+        InputDecoder {
+            bytes: VecDeque::new(),
+            paste_searched: 0,
+            mouse_position: None,
+            pasted: None,
+            clipboard: None,
+        }
+    }
+
+    /// Add bytes read from the terminal.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.bytes.extend(bytes);
+    }
+
+    /// Return true if there are bytes that were not decoded yet.
+    pub fn is_pending(&self) -> bool {
+        !self.bytes.is_empty()
+    }
+
+    /// Decode the next key.
+    ///
+    /// If the input ends in the middle of a key, it is kept until more
+    /// bytes are fed, unless `complete` is set. Then the input is
+    /// taken as it is, so a lone ESC is the ESC key.
+    pub fn next_key(&mut self, complete: bool) -> Option<Key> {
+        loop {
+            if self.bytes.is_empty() {
+                return None;
+            }
+            let bytes = self.bytes.make_contiguous();
+            let decoded = match bytes.strip_prefix(PASTE_START) {
+                Some(text) => decode_paste(text, &mut self.paste_searched),
+                None => decode(bytes, complete),
+            };
+            match decoded {
+                Decoded::Key(key, len) => {
+                    self.bytes.drain(..len);
+                    return Some(key);
+                }
+                Decoded::Mouse(key, position, len) => {
+                    self.bytes.drain(..len);
+                    self.mouse_position = Some(position);
+                    return Some(key);
+                }
+                Decoded::Paste(text, len) => {
+                    self.bytes.drain(..len);
+                    self.pasted = Some(text);
+                    return Some(Key::parse_unchecked("xterm-paste"));
+                }
+                Decoded::Clipboard(text, len) => {
+                    self.bytes.drain(..len);
+                    self.clipboard = Some(text);
+                }
+                Decoded::Ignored(len) => {
+                    self.bytes.drain(..len);
+                }
+                Decoded::Incomplete => return None,
+            }
+        }
+    }
+}
+
+impl Decoded {
+    /// Return the same result for a sequence `n` bytes longer.
+    fn after(self, n: usize) -> Decoded {
+        match self {
+            Decoded::Key(key, len) => Decoded::Key(key, len + n),
+            Decoded::Mouse(key, position, len) => Decoded::Mouse(key, position, len + n),
+            Decoded::Paste(text, len) => Decoded::Paste(text, len + n),
+            Decoded::Clipboard(text, len) => Decoded::Clipboard(text, len + n),
+            Decoded::Ignored(len) => Decoded::Ignored(len + n),
+            Decoded::Incomplete => Decoded::Incomplete,
+        }
+    }
+}
+
+fn decode(bytes: &[u8], complete: bool) -> Decoded {
+    match bytes {
+        [] => Decoded::Incomplete,
+        [ESC] if complete => Decoded::Key(Key::from_code(ESC as u32), 1),
+        [ESC] => Decoded::Incomplete,
+        [ESC, b']', rest @ ..] => match decode_osc(rest) {
+            Decoded::Incomplete if complete => Decoded::Key(Key::parse_unchecked("M-]"), 2),
+            decoded => decoded.after(2),
+        },
+        [ESC, b'[', rest @ ..] | [ESC, b'O', rest @ ..] => match decode_csi(rest) {
+            // An unfinished sequence could also be M-[ or M-O
+            // followed by other keys.
+            Decoded::Incomplete if complete => {
+                Decoded::Key(Key::from_code(bytes[1] as u32).meta(), 2)
+            }
+            decoded => decoded.after(2),
+        },
+        [ESC, rest @ ..] => match decode(rest, complete) {
+            Decoded::Key(key, len) => Decoded::Key(key.meta(), len + 1),
+            Decoded::Mouse(key, position, len) => Decoded::Mouse(key.meta(), position, len + 1),
+            decoded => decoded.after(1),
+        },
+        [byte, ..] if *byte < 0x80 => Decoded::Key(Key::from_code(*byte as u32), 1),
+        [byte, ..] => decode_utf8(bytes, *byte, complete),
+    }
+}
+
+/// Decode a control sequence, after its introducer.
+fn decode_csi(bytes: &[u8]) -> Decoded {
+    // Parameters and intermediate bytes are in 0x20-0x3f, and the
+    // sequence ends with a byte in 0x40-0x7e.
+    let end = match bytes.iter().position(|b| !(0x20..0x40).contains(b)) {
+        Some(end) => end,
+        None => return Decoded::Incomplete,
+    };
+    let len = end + 1;
+    let key = match (bytes[0], bytes[end]) {
+        (b'<', b'M') | (b'<', b'm') => {
+            return decode_mouse(&bytes[1..end], bytes[end] == b'm', len);
+        }
+        (_, b'A') => Key::parse_unchecked("C-p"),
+        (_, b'B') => Key::parse_unchecked("C-n"),
+        (_, b'C') => Key::parse_unchecked("C-f"),
+        (_, b'D') => Key::parse_unchecked("C-b"),
+        _ => return Decoded::Ignored(len),
+    };
+    Decoded::Key(key, len)
+}
+
+/// Decode the parameters `button;column;row` of a mouse event in the
+/// SGR format.
+fn decode_mouse(params: &[u8], release: bool, len: usize) -> Decoded {
+    let params: Option<Vec<usize>> = std::str::from_utf8(params)
+        .ok()
+        .and_then(|params| params.split(';').map(|n| n.parse().ok()).collect());
+    let (code, column, row) = match params.as_deref() {
+        Some(&[code, column, row]) if column > 0 && row > 0 => (code, column, row),
+        _ => return Decoded::Ignored(len),
+    };
+
+    // The low bits are the button, and the others flag motion, the
+    // wheel and the modifiers.
+    let button = code & 3;
+    let name = if code & 64 != 0 {
+        match button {
+            0 => "wheel-up".to_string(),
+            1 => "wheel-down".to_string(),
+            _ => return Decoded::Ignored(len),
+        }
+    } else if button == 3 {
+        return Decoded::Ignored(len);
+    } else if code & 32 != 0 {
+        format!("drag-mouse-{}", button + 1)
+    } else if release {
+        format!("mouse-{}", button + 1)
+    } else {
+        format!("down-mouse-{}", button + 1)
+    };
+
+    let mut key = Key::parse_unchecked(&name);
+    if code & 8 != 0 {
+        key = key.meta();
+    }
+    let position = MousePosition {
+        row: row - 1,
+        column: column - 1,
+    };
+    Decoded::Mouse(key, position, len)
+}
+
+/// Decode an operating system command, after its introducer. It
+/// ends with BEL or ESC \.
+fn decode_osc(bytes: &[u8]) -> Decoded {
+    let (end, len) = match bytes.iter().position(|b| *b == 0x07 || *b == ESC) {
+        Some(end) if bytes[end] == 0x07 => (end, end + 1),
+        Some(end) if bytes.get(end + 1) == Some(&b'\\') => (end, end + 2),
+        Some(end) if end + 1 < bytes.len() => return Decoded::Ignored(end),
+        _ => return Decoded::Incomplete,
+    };
+    // The reply to a query of the clipboard is `52;c;` followed by
+    // the content in base64.
+    let content = match bytes[..end].strip_prefix(b"52;") {
+        Some(params) => params.splitn(2, |b| *b == b';').nth(1),
+        None => None,
+    };
+    match content.and_then(clipboard::decode_base64) {
+        Some(text) => Decoded::Clipboard(String::from_utf8_lossy(&text).into_owned(), len),
+        None => Decoded::Ignored(len),
+    }
+}
+
+/// Decode pasted text, after the start of the paste. The text is
+/// not complete until the end of the paste arrives.
+///
+/// A long paste arrives in many parts. `searched` is the number of
+/// bytes already searched for the end, so each part is only searched
+/// once.
+fn decode_paste(bytes: &[u8], searched: &mut usize) -> Decoded {
+    // The end may have been cut at the end of the previous part.
+    let from = cmp::min(searched.saturating_sub(PASTE_END.len() - 1), bytes.len());
+    let end = match bytes[from..]
+        .windows(PASTE_END.len())
+        .position(|window| window == PASTE_END)
+    {
+        Some(end) => from + end,
+        None => {
+            *searched = bytes.len();
+            return Decoded::Incomplete;
+        }
+    };
+    *searched = 0;
+    // Terminals send line breaks as carriage returns.
+    let text = String::from_utf8_lossy(&bytes[..end])
+        .replace("\r\n", "\n")
+        .replace('\r', "\n");
+    Decoded::Paste(text, PASTE_START.len() + end + PASTE_END.len())
+}
+
+fn decode_utf8(bytes: &[u8], first: u8, complete: bool) -> Decoded {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Decoded::Ignored(1),
+    };
+    if bytes.len() < len {
+        return if complete {
+            Decoded::Ignored(bytes.len())
+        } else {
+            Decoded::Incomplete
+        };
+    }
+    match std::str::from_utf8(&bytes[..len]) {
+        Ok(text) => Decoded::Key(Key::from_code(text.chars().next().unwrap() as u32), len),
+        Err(_) => Decoded::Ignored(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(bytes: &[u8], complete: bool) -> Vec<String> {
+        let mut decoder = InputDecoder::new();
+        decoder.feed(bytes);
+        let mut keys = vec![];
+        while let Some(key) = decoder.next_key(complete) {
+            keys.push(key.to_string());
+        }
+        keys
+    }
+
+    #[test]
+    fn decode_control_and_meta_keys() {
+        assert_eq!(decode_all(b"\x00a\x1bf", false), ["C-SPC", "a", "M-f"]);
+        assert_eq!(
+            decode_all(b"\x1b[A\x1bOD\x1b[3~x", false),
+            ["C-p", "C-b", "x"]
+        );
+    }
+
+    #[test]
+    fn wait_for_the_rest_of_a_key() {
+        let mut decoder = InputDecoder::new();
+        decoder.feed(&[ESC]);
+        assert_eq!(decoder.next_key(false), None);
+        decoder.feed(&"[Bé".as_bytes()[..3]);
+        assert_eq!(decoder.next_key(false), Some(Key::parse_unchecked("C-n")));
+        assert_eq!(decoder.next_key(false), None);
+        decoder.feed(&"é".as_bytes()[1..]);
+        assert_eq!(decoder.next_key(false), Some(Key::from_code('é' as u32)));
+
+        decoder.feed(&[ESC]);
+        assert_eq!(decoder.next_key(true), Some(Key::from_code(ESC as u32)));
+        assert!(!decoder.is_pending());
+    }
+
+    #[test]
+    fn decode_pasted_text() {
+        let mut decoder = InputDecoder::new();
+        decoder.feed(b"a\x1b[200~\tC-x\r\x1b[2");
+        assert_eq!(decoder.next_key(false), Some(Key::parse_unchecked("a")));
+        assert_eq!(decoder.next_key(true), None);
+        decoder.feed(b"01~b");
+        let paste = decoder.next_key(false).unwrap();
+        assert_eq!(paste.to_string(), "xterm-paste");
+        assert_eq!(decoder.pasted.as_deref(), Some("\tC-x\n"));
+        assert_eq!(decoder.next_key(false), Some(Key::parse_unchecked("b")));
+    }
+
+    #[test]
+    fn decode_pastes_in_parts() {
+        let mut decoder = InputDecoder::new();
+        decoder.feed(PASTE_START);
+        for part in [&b"abc"[..], b"de\x1b", b"[20", b"1", b"~"] {
+            assert_eq!(decoder.next_key(false), None);
+            decoder.feed(part);
+        }
+        assert_eq!(decoder.next_key(false).unwrap().to_string(), "xterm-paste");
+        assert_eq!(decoder.pasted.as_deref(), Some("abcde"));
+        assert_eq!(decoder.paste_searched, 0);
+    }
+
+    #[test]
+    fn decode_clipboard_replies() {
+        let mut decoder = InputDecoder::new();
+        decoder.feed(b"\x1b]52;c;cmlsZQ==\x07x\x1b]52;c;aGk=\x1b\\");
+        assert_eq!(decoder.next_key(false), Some(Key::parse_unchecked("x")));
+        assert_eq!(decoder.clipboard.as_deref(), Some("rile"));
+        assert_eq!(decoder.next_key(false), None);
+        assert_eq!(decoder.clipboard.as_deref(), Some("hi"));
+        assert!(!decoder.is_pending());
+    }
+
+    #[test]
+    fn decode_mouse_events() {
+        let mut decoder = InputDecoder::new();
+        decoder.feed(b"\x1b[<0;5;3M\x1b[<32;7;3M\x1b[<0;7;3m\x1b[<73;1;1M");
+        let mut events = vec![];
+        while let Some(key) = decoder.next_key(false) {
+            events.push((key.to_string(), decoder.mouse_position.unwrap()));
+        }
+        let at = |row, column| MousePosition { row, column };
+        assert_eq!(
+            events,
+            [
+                ("down-mouse-1".to_string(), at(2, 4)),
+                ("drag-mouse-1".to_string(), at(2, 6)),
+                ("mouse-1".to_string(), at(2, 6)),
+                ("M-wheel-down".to_string(), at(0, 0)),
+            ]
+        );
+    }
+}
@@ -13,16 +13,41 @@ pub struct Key {
     code: u32,
 }
 
+/// The names of the events other than key presses, in the order of
+/// their codes.
+const EVENTS: [&str; 12] = [
+    "down-mouse-1",
+    "down-mouse-2",
+    "down-mouse-3",
+    "mouse-1",
+    "mouse-2",
+    "mouse-3",
+    "drag-mouse-1",
+    "drag-mouse-2",
+    "drag-mouse-3",
+    "wheel-up",
+    "wheel-down",
+    "xterm-paste",
+];
+
+/// Events are encoded after the last Unicode code point, so they are
+/// never taken as characters.
+const EVENT_BASE: u32 = 0x11_0000;
+
 impl Key {
     /// Parse a single key name without any modifiers.
     fn parse_unmodified(key: &str) -> Option<Key> {
-        if key.len() == 1 {
+        if let Some(index) = EVENTS.iter().position(|name| *name == key) {
+            Some(Key::from_code(EVENT_BASE + index as u32))
+        } else if key.len() == 1 {
             Some(Key::from_code(key.chars().next().unwrap() as u32))
         } else {
             match key {
                 "DEL" => Some(Key::from_code(127)),
                 "RET" => Some(Key::from_code(13)),
                 "TAB" => Some(Key::from_code(9)),
+                "SPC" => Some(Key::from_code(32)),
+                "ESC" => Some(Key::from_code(27)),
                 _ => None,
             }
         }
@@ -47,6 +72,13 @@ impl Key {
         }
     }
 
+    /// Parse a sequence of key descriptions separated by spaces.
+    ///
+    /// This is the inverse of [`format_seq`](#method.format_seq).
+    pub fn parse_seq(keys: &str) -> Option<Vec<Key>> {
+        keys.split_whitespace().map(Key::parse).collect()
+    }
+
     /// Parse a key press and panics in case of an error.
     pub fn parse_unchecked(key: &str) -> Key {
         Key::parse(key).unwrap()
@@ -69,6 +101,12 @@ impl Key {
         self
     }
 
+    /// Return true if the key is an event other than a key press,
+    /// like a mouse click or a paste.
+    pub fn is_event(&self) -> bool {
+        self.code >= EVENT_BASE
+    }
+
     pub fn is_ctrl(&self) -> bool {
         self.code == 0x1f & self.code
     }
@@ -110,15 +148,50 @@ impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut code = self.to_code();
 
+        if self.is_event() {
+            let meta = if self.meta { "M-" } else { "" };
+            let name = EVENTS[(code - EVENT_BASE) as usize];
+            return write!(f, "{}{}", meta, name);
+        }
+
+        // ESC is read on its own when no key follows it soon.
+        if code == 27 {
+            let meta = if self.meta { "M-" } else { "" };
+            return write!(f, "{}ESC", meta);
+        }
+
         if self.is_ctrl() {
             write!(f, "C-",)?;
-            code += 'a' as u32 & !0x1f;
+            // C-SPC is the only control key that is not a letter.
+            if code != 0 {
+                code += 'a' as u32 & !0x1f;
+            } else {
+                code = 32;
+            }
         };
 
         if self.meta {
             write!(f, "M-",)?
         };
 
-        write!(f, "{}", char::from_u32(code).unwrap())
+        match code {
+            32 => write!(f, "SPC"),
+            127 => write!(f, "DEL"),
+            _ => write!(f, "{}", char::from_u32(code).unwrap()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_and_parse_sequences() {
+        let spec = "C-x ( a SPC C-SPC M-f C-M-x DEL ESC down-mouse-1 M-wheel-up C-x )";
+        let keys = Key::parse_seq(spec).unwrap();
+        assert_eq!(keys.len(), 13);
+        assert!(keys[9].is_event() && keys[9].as_char().is_none());
+        assert_eq!(Key::format_seq(&keys), spec);
     }
 }
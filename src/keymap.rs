@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use crate::command_table::{self, Command};
 use crate::commands;
 use crate::term::Term;
 use crate::{Context, Key};
@@ -10,7 +11,7 @@ pub type CommandHandler = fn(&mut Context, term: &mut Term) -> commands::Result;
 
 #[derive(Clone)]
 pub enum Item {
-    Command(CommandHandler),
+    Command(&'static Command),
     Keymap(Keymap),
 }
 
@@ -26,9 +27,13 @@ impl Keymap {
         }
     }
 
-    pub fn define_key(&mut self, keyspec: &str, f: CommandHandler) {
+    /// Bind `keyspec` to the command called `name` in the command
+    /// table.
+    pub fn define_key(&mut self, keyspec: &str, name: &str) {
         let key = Key::parse_unchecked(keyspec);
-        self.inner.borrow_mut().insert(key, Item::Command(f));
+        let command = command_table::find(name)
+            .unwrap_or_else(|| panic!("Can't bind {} to unknown command {}", keyspec, name));
+        self.inner.borrow_mut().insert(key, Item::Command(command));
     }
 
     pub fn define_keymap(&mut self, keyspec: &str, keymap: Keymap) {
@@ -40,39 +45,194 @@ impl Keymap {
         self.inner.borrow().get(key).map(|item| item.clone())
     }
 
-    pub fn defaults() -> Keymap {
-        let mut keymap = Keymap::new();
-        let mut c_x = Keymap::new();
-
-        keymap.define_key("C-a", commands::move_beginning_of_line);
-        keymap.define_key("C-e", commands::move_end_of_line);
-        keymap.define_key("C-f", commands::forward_char);
-        keymap.define_key("C-b", commands::backward_char);
-        keymap.define_key("C-p", commands::previous_line);
-        keymap.define_key("C-n", commands::next_line);
-        keymap.define_key("C-d", commands::delete_char);
-
-        keymap.define_key("DEL", commands::delete_backward_char);
-        keymap.define_key("C-k", commands::kill_line);
-        keymap.define_key("RET", commands::newline);
-        keymap.define_key("C-j", commands::newline);
-        keymap.define_key("TAB", commands::indent_line);
-
-        keymap.define_key("M-<", commands::beginning_of_buffer);
-        keymap.define_key("M->", commands::end_of_buffer);
-
-        keymap.define_key("C-v", commands::next_screen);
-        keymap.define_key("M-v", commands::previous_screen);
+    /// Return all the key sequences bound to a command in this
+    /// keymap and the keymaps it contains, sorted by key sequence.
+    pub fn bindings(&self) -> Vec<(Vec<Key>, &'static Command)> {
+        let mut bindings = vec![];
+        for (key, item) in self.inner.borrow().iter() {
+            match item {
+                Item::Command(command) => bindings.push((vec![key.clone()], *command)),
+                Item::Keymap(keymap) => {
+                    for (mut keys, command) in keymap.bindings() {
+                        keys.insert(0, key.clone());
+                        bindings.push((keys, command));
+                    }
+                }
+            }
+        }
+        bindings.sort_by_cached_key(|(keys, _)| (keys.len(), Key::format_seq(keys)));
+        bindings
+    }
 
-        keymap.define_key("C-g", commands::keyboard_quit);
-        keymap.define_key("C-s", commands::isearch_forward);
+    /// Return the key sequences bound to the command called `name`.
+    pub fn where_is(&self, name: &str) -> Vec<Vec<Key>> {
+        self.bindings()
+            .into_iter()
+            .filter(|(_, command)| command.name == name)
+            .map(|(keys, _)| keys)
+            .collect()
+    }
 
-        keymap.define_key("M-x", commands::m_x);
+    /// The keymap of read-only buffers generated by the editor, like
+    /// the help buffer.
+    pub fn special_mode() -> Keymap {
+        let mut keymap = Keymap::defaults();
+        keymap.define_key("q", "quit-window");
+        keymap.define_key("SPC", "scroll-up-command");
+        keymap.define_key("DEL", "scroll-down-command");
+        keymap
+    }
 
-        c_x.define_key("C-s", commands::save_buffer);
-        c_x.define_key("C-c", commands::kill_rile);
+    pub fn defaults() -> Keymap {
+        let mut keymap = Keymap::new();
+        let mut c_x = Keymap::new();
+        let mut c_x_c_k = Keymap::new();
+        let mut c_h = Keymap::new();
+        let mut m_g = Keymap::new();
+        let mut c_u = Keymap::new();
+        let mut c_x_r = Keymap::new();
+        let mut c_x_m = Keymap::new();
+        let mut c_x_n = Keymap::new();
+
+        keymap.define_key("C-a", "move-beginning-of-line");
+        keymap.define_key("C-e", "move-end-of-line");
+        keymap.define_key("C-f", "forward-char");
+        keymap.define_key("C-b", "backward-char");
+        keymap.define_key("C-p", "previous-line");
+        keymap.define_key("C-n", "next-line");
+        keymap.define_key("C-d", "delete-char");
+
+        keymap.define_key("DEL", "delete-backward-char");
+        keymap.define_key("C-k", "kill-line");
+        keymap.define_key("C-y", "yank");
+
+        keymap.define_key("M-f", "forward-word");
+        keymap.define_key("M-b", "backward-word");
+        keymap.define_key("M-d", "kill-word");
+        keymap.define_key("M-DEL", "backward-kill-word");
+        keymap.define_key("M-@", "mark-word");
+        keymap.define_key("M-e", "forward-sentence");
+        keymap.define_key("M-a", "backward-sentence");
+        keymap.define_key("M-}", "forward-paragraph");
+        keymap.define_key("M-{", "backward-paragraph");
+        keymap.define_key("M-h", "mark-paragraph");
+        keymap.define_key("C-M-f", "forward-sexp");
+        keymap.define_key("C-M-b", "backward-sexp");
+        keymap.define_key("C-M-n", "forward-list");
+        keymap.define_key("C-M-p", "backward-list");
+        keymap.define_key("C-M-u", "backward-up-list");
+        keymap.define_key("C-M-d", "down-list");
+        keymap.define_key("C-M-k", "kill-sexp");
+        keymap.define_key("C-M-SPC", "mark-sexp");
+        keymap.define_key("C-t", "transpose-chars");
+        keymap.define_key("M-t", "transpose-words");
+        keymap.define_key("M-u", "upcase-word");
+        keymap.define_key("M-l", "downcase-word");
+        keymap.define_key("M-c", "capitalize-word");
+        keymap.define_key("RET", "newline-and-indent");
+        keymap.define_key("C-j", "newline");
+        keymap.define_key("TAB", "indent-according-to-mode");
+        keymap.define_key("C-M-\\", "indent-region");
+
+        keymap.define_key("M-<", "beginning-of-buffer");
+        keymap.define_key("M->", "end-of-buffer");
+
+        keymap.define_key("C-v", "scroll-up-command");
+        keymap.define_key("M-v", "scroll-down-command");
+
+        keymap.define_key("C-g", "keyboard-quit");
+        keymap.define_key("C-s", "isearch-forward");
+        keymap.define_key("C-z", "suspend-frame");
+
+        keymap.define_key("down-mouse-1", "mouse-set-point");
+        keymap.define_key("drag-mouse-1", "mouse-drag-region");
+        keymap.define_key("mouse-1", "ignore");
+        keymap.define_key("M-down-mouse-1", "mc/add-cursor-on-click");
+        keymap.define_key("M-mouse-1", "ignore");
+        keymap.define_key("wheel-up", "mwheel-scroll");
+        keymap.define_key("wheel-down", "mwheel-scroll");
+        keymap.define_key("xterm-paste", "xterm-paste");
+
+        keymap.define_key("M-x", "execute-extended-command");
+        keymap.define_key("C-SPC", "set-mark-command");
+        c_u.define_key("C-SPC", "pop-to-mark-command");
+        keymap.define_keymap("C-u", c_u);
+
+        c_x.define_key("C-s", "save-buffer");
+        c_x.define_key("C-w", "write-file");
+        c_x.define_key("C-c", "save-buffers-kill-terminal");
+        c_x.define_key("C-t", "transpose-lines");
+        c_x.define_key("TAB", "indent-rigidly");
+        c_x.define_key("2", "split-window-below");
+        c_x.define_key("o", "other-window");
+        c_x.define_key("b", "switch-to-buffer");
+        c_x.define_key("=", "what-cursor-position");
+        c_x.define_key("0", "delete-window");
+        c_x.define_key("1", "delete-other-windows");
+        c_x.define_key("C-SPC", "pop-global-mark");
+        c_x.define_key("SPC", "rectangle-mark-mode");
+        c_x.define_key("<", "scroll-left");
+        c_x.define_key(">", "scroll-right");
+
+        c_x.define_key("(", "kmacro-start-macro");
+        c_x.define_key(")", "kmacro-end-macro");
+        c_x.define_key("e", "kmacro-call-macro");
+
+        c_x_c_k.define_key("C-i", "kmacro-insert-counter");
+        c_x_c_k.define_key("C-c", "kmacro-set-counter");
+        c_x_c_k.define_key("C-a", "kmacro-add-counter");
+        c_x_c_k.define_key("C-f", "kmacro-set-format");
+        c_x_c_k.define_key("r", "apply-macro-to-region-lines");
+        c_x_c_k.define_key("n", "kmacro-name-last-macro");
+        c_x.define_keymap("C-k", c_x_c_k);
+
+        c_x_r.define_key("s", "copy-to-register");
+        c_x_r.define_key("x", "copy-to-register");
+        c_x_r.define_key("i", "insert-register");
+        c_x_r.define_key("g", "insert-register");
+        c_x_r.define_key("SPC", "point-to-register");
+        c_x_r.define_key("j", "jump-to-register");
+        c_x_r.define_key("n", "number-to-register");
+        c_x_r.define_key("+", "increment-register");
+        c_x_r.define_key("w", "window-configuration-to-register");
+        c_x_r.define_key("k", "kill-rectangle");
+        c_x_r.define_key("M-w", "copy-rectangle-as-kill");
+        c_x_r.define_key("d", "delete-rectangle");
+        c_x_r.define_key("y", "yank-rectangle");
+        c_x_r.define_key("o", "open-rectangle");
+        c_x_r.define_key("c", "clear-rectangle");
+        c_x_r.define_key("t", "string-rectangle");
+        c_x_r.define_key("N", "rectangle-number-lines");
+        c_x_r.define_key("m", "bookmark-set");
+        c_x_r.define_key("b", "bookmark-jump");
+        c_x_r.define_key("l", "bookmark-bmenu-list");
+        c_x.define_keymap("r", c_x_r);
+        c_x_m.define_key("n", "mc/mark-next-like-this");
+        c_x_m.define_key("p", "mc/mark-previous-like-this");
+        c_x_m.define_key("l", "mc/edit-lines");
+        c_x.define_keymap("m", c_x_m);
+        c_x_n.define_key("n", "narrow-to-region");
+        c_x_n.define_key("d", "narrow-to-defun");
+        c_x_n.define_key("w", "widen");
+        c_x.define_keymap("n", c_x_n);
         keymap.define_keymap("C-x", c_x);
 
+        m_g.define_key("g", "goto-line");
+        m_g.define_key("M-g", "goto-line");
+        m_g.define_key("c", "goto-char");
+        m_g.define_key("b", "jump-backward");
+        m_g.define_key("f", "jump-forward");
+        keymap.define_keymap("M-g", m_g);
+
+        c_h.define_key("k", "describe-key");
+        c_h.define_key("f", "describe-function");
+        c_h.define_key("b", "describe-bindings");
+        c_h.define_key("w", "where-is");
+        c_h.define_key("a", "apropos-command");
+        c_h.define_key("m", "describe-mode");
+        c_h.define_key("e", "view-echo-area-messages");
+        keymap.define_keymap("C-h", c_h);
+
         keymap
     }
 }
@@ -0,0 +1,89 @@
+//! The kill ring
+//!
+//! Text removed by kill commands is saved in the kill ring, so it
+//! can be inserted back later by yanking.
+//!
+
+use std::collections::VecDeque;
+
+/// Maximum number of entries in the kill ring.
+const KILL_RING_MAX: usize = 60;
+
+#[derive(Clone)]
+pub struct KillRing {
+    /// Killed text, the most recent first.
+    entries: VecDeque<String>,
+
+    /// If set, the next kill is added to the most recent entry
+    /// instead of creating a new one. This is the case when the
+    /// previous command was also a kill.
+    pub append: bool,
+
+    /// Set by kill commands to preserve `append` for the next command.
+    pub to_preserve: bool,
+}
+
+/// The side of the previous kill where the new text goes to.
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+impl KillRing {
+    pub fn new() -> KillRing {
+        KillRing {
+            entries: VecDeque::new(),
+            append: false,
+            to_preserve: false,
+        }
+    }
+
+    /// Save `text` in the kill ring.
+    ///
+    /// Consecutive kills are accumulated into the same entry,
+    /// `direction` indicates if the text was killed forward (e.g,
+    /// `kill-word`) or backward (e.g, `backward-kill-word`).
+    pub fn kill(&mut self, text: String, direction: Direction) {
+        match self.entries.front_mut() {
+            Some(last) if self.append => match direction {
+                Direction::Forward => last.push_str(&text),
+                Direction::Backward => last.insert_str(0, &text),
+            },
+            _ => {
+                self.entries.push_front(text);
+                self.entries.truncate(KILL_RING_MAX);
+            }
+        }
+        self.append = true;
+        self.to_preserve = true;
+    }
+
+    /// Save `text` in the kill ring as a new entry, even after a kill.
+    pub fn kill_new(&mut self, text: String) {
+        self.entries.push_front(text);
+        self.entries.truncate(KILL_RING_MAX);
+    }
+
+    /// Return the most recent kill.
+    pub fn current(&self) -> Option<&str> {
+        self.entries.front().map(|s| &s[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_kills_are_accumulated() {
+        let mut kill_ring = KillRing::new();
+        kill_ring.kill("world".to_string(), Direction::Forward);
+        kill_ring.kill("!".to_string(), Direction::Forward);
+        kill_ring.kill("hello ".to_string(), Direction::Backward);
+        assert_eq!(kill_ring.current(), Some("hello world!"));
+
+        kill_ring.append = false;
+        kill_ring.kill("new".to_string(), Direction::Forward);
+        assert_eq!(kill_ring.current(), Some("new"));
+    }
+}
@@ -0,0 +1,347 @@
+//! Keyboard macros.
+//!
+//! A keyboard macro is a sequence of keys recorded from the user
+//! input that can be replayed later as if they were typed again.
+//!
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::command_table;
+use crate::commands::{self, Result};
+use crate::config;
+use crate::event_loop::{execute_kbd_macro, read_key};
+use crate::read;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Context, Cursor, Key};
+
+/// The name of the file in the configuration directory where named
+/// macros are saved.
+const MACROS_FILE: &str = "macros";
+
+pub struct KmacroState {
+    /// The keys typed so far if a macro is being defined.
+    pub recording: Option<Vec<Key>>,
+    /// The last macro defined.
+    pub last: Option<Vec<Key>>,
+    /// Macros that have been given a name. They can be invoked with
+    /// M-x as any other command.
+    pub named: BTreeMap<String, Vec<Key>>,
+
+    pub counter: i64,
+    /// Format used to insert the counter. `%d` is replaced by its value.
+    pub counter_format: String,
+}
+
+impl KmacroState {
+    pub fn new() -> KmacroState {
+        KmacroState {
+            recording: None,
+            last: None,
+            named: BTreeMap::new(),
+            counter: 0,
+            counter_format: "%d".to_string(),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Return the text of the counter and increment it.
+    fn next_counter_text(&mut self) -> String {
+        let text = self.counter_format.replace("%d", &self.counter.to_string());
+        self.counter += 1;
+        text
+    }
+}
+
+/// Stop recording the current macro and make it the last macro.
+fn end_recording(context: &mut Context) -> Result {
+    if let Some(mut keys) = context.kmacro.recording.take() {
+        // Do not include the keys that ended the macro definition.
+        let ending = context.event_loop.this_command_keys.len();
+        keys.truncate(keys.len().saturating_sub(ending));
+        context.kmacro.last = Some(keys);
+        message(context, "Keyboard macro defined");
+        Ok(())
+    } else {
+        message(context, "Not defining kbd macro");
+        Err(())
+    }
+}
+
+fn last_macro(context: &mut Context) -> std::result::Result<Vec<Key>, ()> {
+    match &context.kmacro.last {
+        Some(keys) => Ok(keys.clone()),
+        None => {
+            message(context, "No kbd macro has been defined");
+            Err(())
+        }
+    }
+}
+
+pub fn kmacro_start_macro(context: &mut Context, _term: &mut Term) -> Result {
+    if context.kmacro.is_recording() {
+        message(context, "Already defining keyboard macro");
+        return Err(());
+    }
+    context.kmacro.recording = Some(vec![]);
+    message(context, "Defining kbd macro...");
+    Ok(())
+}
+
+pub fn kmacro_end_macro(context: &mut Context, _term: &mut Term) -> Result {
+    end_recording(context)
+}
+
+/// Call the last macro, ending its definition if it is being
+/// recorded. Typing `e` afterwards calls it again.
+pub fn kmacro_call_macro(context: &mut Context, term: &mut Term) -> Result {
+    if context.kmacro.is_recording() {
+        end_recording(context)?;
+    }
+
+    let keys = last_macro(context)?;
+    let repeat = Key::parse_unchecked("e");
+
+    loop {
+        execute_kbd_macro(term, context, &keys)?;
+
+        message(context, "(Type e to repeat macro)");
+        let key = read_key(term, context);
+        if key != repeat {
+            context.event_loop.push_front_keys(&[key]);
+            break;
+        }
+    }
+
+    message(context, "");
+    Ok(())
+}
+
+pub fn kmacro_insert_counter(context: &mut Context, _term: &mut Term) -> Result {
+    let text = context.kmacro.next_counter_text();
+    for ch in text.chars() {
+        commands::insert_char(context, ch)?;
+    }
+    Ok(())
+}
+
+fn read_number(
+    context: &mut Context,
+    term: &mut Term,
+    prompt: &str,
+) -> std::result::Result<i64, ()> {
+    let input = read::read_string(term, context, prompt, |_, _| {}, false).map_err(|_| ())?;
+    input.trim().parse().map_err(|_| {
+        message(context, format!("Invalid number: {}", input));
+    })
+}
+
+pub fn kmacro_set_counter(context: &mut Context, term: &mut Term) -> Result {
+    context.kmacro.counter = read_number(context, term, "Macro counter value: ")?;
+    Ok(())
+}
+
+pub fn kmacro_add_counter(context: &mut Context, term: &mut Term) -> Result {
+    context.kmacro.counter += read_number(context, term, "Add to macro counter: ")?;
+    Ok(())
+}
+
+pub fn kmacro_set_format(context: &mut Context, term: &mut Term) -> Result {
+    let format = read::read_string(term, context, "Macro counter format: ", |_, _| {}, false)
+        .map_err(|_| ())?;
+    context.kmacro.counter_format = if format.is_empty() {
+        "%d".to_string()
+    } else {
+        format
+    };
+    Ok(())
+}
+
+/// Call the last macro at the beginning of each line in the region.
+pub fn apply_macro_to_region_lines(context: &mut Context, term: &mut Term) -> Result {
+    let keys = last_macro(context)?;
+
+    let (start, end) = commands::region_or_message(context)?;
+
+    // A region ending at the beginning of a line does not include it.
+    let mut last_line = if end.column == 0 && end.line > start.line {
+        end.line - 1
+    } else {
+        end.line
+    } as isize;
+
+    let mut line = start.line as isize;
+    while line <= last_line {
+        let window = context.window_list.get_current_window();
+        let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+        if line as usize >= buffer.lines_count() {
+            break;
+        }
+        buffer.set_cursor(Cursor {
+            line: line as usize,
+            column: 0,
+        });
+        let lines_before = buffer.lines_count() as isize;
+
+        execute_kbd_macro(term, context, &keys)?;
+
+        // Assume the lines added or removed by the macro were around
+        // the current line, so the following lines are shifted.
+        let window = context.window_list.get_current_window();
+        let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+        let delta = buffer.lines_count() as isize - lines_before;
+        last_line += delta;
+        line += 1 + delta;
+    }
+
+    Ok(())
+}
+
+/// Give a name to the last macro so it can be called with M-x.
+pub fn kmacro_name_last_macro(context: &mut Context, term: &mut Term) -> Result {
+    last_macro(context)?;
+    let name = read::read_string(term, context, "Name for last kbd macro: ", |_, _| {}, false)
+        .map_err(|_| ())?;
+    name_last_macro(context, name.trim())
+}
+
+fn name_last_macro(context: &mut Context, name: &str) -> Result {
+    let keys = last_macro(context)?;
+
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        message(context, "Invalid macro name");
+        return Err(());
+    }
+    if command_table::lookup(name).is_some() {
+        message(context, format!("{} is already a command", name));
+        return Err(());
+    }
+
+    context.kmacro.named.insert(name.to_string(), keys);
+    Ok(())
+}
+
+/// Save all the named macros to the user configuration, so they are
+/// available the next time the editor starts.
+pub fn kmacro_save_macros(context: &mut Context, _term: &mut Term) -> Result {
+    let file = match config::config_file(MACROS_FILE) {
+        Some(file) => file,
+        None => {
+            message(context, "Could not find the configuration directory");
+            return Err(());
+        }
+    };
+
+    let contents: String = context
+        .kmacro
+        .named
+        .iter()
+        .map(|(name, keys)| macro_line(name, keys))
+        .collect();
+
+    let written = file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&file, contents));
+
+    match written {
+        Ok(_) => {
+            message(context, format!("Wrote {}", file.display()));
+            Ok(())
+        }
+        Err(err) => {
+            message(context, format!("Could not save macros: {}", err));
+            Err(())
+        }
+    }
+}
+
+/// Load the named macros saved in the user configuration.
+pub fn load_macros(context: &mut Context) {
+    let contents = match config::config_file(MACROS_FILE).map(fs::read_to_string) {
+        Some(Ok(contents)) => contents,
+        _ => return,
+    };
+
+    for (name, keys) in contents.lines().filter_map(parse_macro_line) {
+        context.kmacro.named.insert(name, keys);
+    }
+}
+
+/// Return the line saving the macro `name` in the macros file.
+fn macro_line(name: &str, keys: &[Key]) -> String {
+    format!("{} {}\n", name, Key::format_seq(keys))
+}
+
+/// Parse a line of the macros file, as written by `macro_line`.
+fn parse_macro_line(line: &str) -> Option<(String, Vec<Key>)> {
+    let mut parts = line.splitn(2, ' ');
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let keys = parts.next().and_then(Key::parse_seq)?;
+    Some((name.to_string(), keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    fn keys(keys: &str) -> Vec<Key> {
+        Key::parse_seq(keys).unwrap()
+    }
+
+    #[test]
+    fn record_macros_without_the_ending_keys() {
+        let mut context = Context::new(Buffer::new());
+        assert!(end_recording(&mut context).is_err());
+
+        context.kmacro.recording = Some(keys("a b C-x )"));
+        context.event_loop.this_command_keys = keys("C-x )");
+        assert!(end_recording(&mut context).is_ok());
+        assert!(!context.kmacro.is_recording());
+        assert_eq!(context.kmacro.last, Some(keys("a b")));
+    }
+
+    #[test]
+    fn format_and_increment_the_counter() {
+        let mut kmacro = KmacroState::new();
+        assert_eq!(kmacro.next_counter_text(), "0");
+        kmacro.counter = 9;
+        kmacro.counter_format = "item %d: ".to_string();
+        assert_eq!(kmacro.next_counter_text(), "item 9: ");
+        assert_eq!(kmacro.next_counter_text(), "item 10: ");
+        assert_eq!(kmacro.counter, 11);
+    }
+
+    #[test]
+    fn name_only_new_commands() {
+        let mut context = Context::new(Buffer::new());
+        assert!(name_last_macro(&mut context, "twice").is_err());
+
+        context.kmacro.last = Some(keys("C-a C-k"));
+        assert!(name_last_macro(&mut context, "").is_err());
+        assert!(name_last_macro(&mut context, "two words").is_err());
+        assert!(name_last_macro(&mut context, "kill-line").is_err());
+        assert!(context.kmacro.named.is_empty());
+
+        assert!(name_last_macro(&mut context, "clear-line").is_ok());
+        assert_eq!(context.kmacro.named["clear-line"], keys("C-a C-k"));
+    }
+
+    #[test]
+    fn save_and_load_macro_lines() {
+        let line = macro_line("clear-line", &keys("C-a C-k C-m"));
+        assert_eq!(line, "clear-line C-a C-k C-m\n");
+        assert_eq!(
+            parse_macro_line(line.trim_end()),
+            Some(("clear-line".to_string(), keys("C-a C-k C-m")))
+        );
+        assert_eq!(parse_macro_line(""), None);
+        assert_eq!(parse_macro_line("clear-line"), None);
+        assert_eq!(parse_macro_line("clear-line C-not-a-key"), None);
+    }
+}
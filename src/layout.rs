@@ -10,7 +10,9 @@ pub struct Region {
 }
 
 pub struct Layout {
-    pub main_window_region: Region,
+    /// The regions of the windows, in the same order as in the
+    /// window list.
+    pub window_regions: Vec<Region>,
     pub minibuffer_region: Region,
 }
 
@@ -22,13 +24,22 @@ pub fn get_layout(term: &term::Term, context: &Context) -> Layout {
         height: minibuffer_height,
     };
 
-    let main_window_region = Region {
-        top: 0,
-        height: term.rows - minibuffer_height,
-    };
+    // The windows share the space above the minibuffer evenly, the
+    // last one taking the remaining rows.
+    let windows_height = term.rows - minibuffer_height;
+    let count = context.window_list.windows.len();
+    let window_regions = (0..count)
+        .map(|index| {
+            let top = index * windows_height / count;
+            Region {
+                top,
+                height: (index + 1) * windows_height / count - top,
+            }
+        })
+        .collect();
 
     Layout {
-        main_window_region,
+        window_regions,
         minibuffer_region,
     }
 }
@@ -38,6 +49,6 @@ pub fn get_current_window_region(term: &term::Term, context: &Context) -> Region
     if context.window_list.minibuffer_focused {
         layout.minibuffer_region
     } else {
-        layout.main_window_region
+        layout.window_regions[context.window_list.selected].clone()
     }
 }
@@ -1,17 +1,45 @@
 //! rile is a simple editor written in Rust.
 //!
 
+pub mod bookmark;
 pub mod buffer;
 pub mod buffer_list;
+pub mod clipboard;
 pub mod color;
+pub mod command_table;
+pub mod config;
 pub mod context;
 pub mod event_loop;
+pub mod face;
+pub mod folding;
+pub mod gutter;
+pub mod help;
+pub mod indent;
+pub mod input;
 pub mod key;
 pub mod keymap;
+pub mod kill_ring;
+pub mod kmacro;
 pub mod layout;
+pub mod mark_ring;
+pub mod marker;
+pub mod messages;
 pub mod minibuffer;
+pub mod mode;
+pub mod modeline;
+pub mod motion;
+pub mod mouse;
+pub mod multiple_cursors;
+pub mod narrowing;
+pub mod overlay;
 pub mod read;
+pub mod rectangle;
+pub mod register;
+pub mod sexp;
+pub mod signals;
+pub mod syntax;
 pub mod term;
+pub mod timer;
 pub mod window;
 pub mod window_list;
 
@@ -0,0 +1,300 @@
+//! Mark rings
+//!
+//! Setting the mark, or jumping far away like `beginning-of-buffer`
+//! and `goto-line` do, saves the previous mark in the mark ring of the
+//! buffer, and the position in the global mark ring, shared by all
+//! buffers. Popping a ring goes back to the positions it saved.
+//!
+//! The jump history records where long jumps started, to go back and
+//! forward between them like the buttons of a web browser.
+//!
+
+use std::collections::VecDeque;
+
+use crate::buffer_list::BufferRef;
+use crate::commands::Result;
+use crate::marker::MarkerId;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Context, Cursor};
+
+/// Maximum number of entries in the mark ring of a buffer.
+const MARK_RING_MAX: usize = 16;
+
+/// Maximum number of entries in the global mark ring.
+const GLOBAL_MARK_RING_MAX: usize = 16;
+
+/// Maximum number of entries in the jump history.
+const JUMP_HISTORY_MAX: usize = 100;
+
+/// A position in a buffer, kept by a marker of the buffer.
+pub type Position = (BufferRef, MarkerId);
+
+/// The positions jumps started from, with the position the history
+/// is at.
+pub struct JumpHistory {
+    entries: Vec<Position>,
+    /// The index of the current entry. It is the length of `entries`
+    /// when not moving in the history.
+    index: usize,
+}
+
+impl JumpHistory {
+    pub fn new() -> JumpHistory {
+        JumpHistory {
+            entries: vec![],
+            index: 0,
+        }
+    }
+
+    /// Record the start of a jump, and return the entries that are
+    /// dropped: the ones after the current entry, and the oldest ones
+    /// past the maximum.
+    pub fn record(&mut self, position: Position) -> Vec<Position> {
+        let mut dropped = self.entries.split_off(self.index);
+        self.entries.push(position);
+        if self.entries.len() > JUMP_HISTORY_MAX {
+            dropped.push(self.entries.remove(0));
+        }
+        self.index = self.entries.len();
+        dropped
+    }
+
+    /// Move back in the history from `current` and return the entry
+    /// to go to. `current` is recorded when starting to move back, to
+    /// come forward to it.
+    pub fn back(&mut self, current: impl FnOnce() -> Position) -> Option<Position> {
+        if self.index == 0 {
+            return None;
+        }
+        if self.index == self.entries.len() {
+            self.entries.push(current());
+        }
+        self.index -= 1;
+        Some(self.entries[self.index])
+    }
+
+    /// Move forward in the history and return the entry to go to.
+    pub fn forward(&mut self) -> Option<Position> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.entries[self.index])
+    }
+}
+
+pub struct MarkRingState {
+    /// Positions in any buffer, the most recent first. Consecutive
+    /// entries are in different buffers.
+    pub global: VecDeque<Position>,
+    pub history: JumpHistory,
+}
+
+impl MarkRingState {
+    pub fn new() -> MarkRingState {
+        MarkRingState {
+            global: VecDeque::new(),
+            history: JumpHistory::new(),
+        }
+    }
+}
+
+/// Delete the markers of `positions`.
+fn remove_positions(context: &mut Context, positions: impl IntoIterator<Item = Position>) {
+    for (buffer_ref, id) in positions {
+        context
+            .buffer_list
+            .resolve_ref_as_mut(buffer_ref)
+            .markers
+            .remove(id);
+    }
+}
+
+/// Set the mark of the buffer `buffer_ref` at `pos`, saving the
+/// previous mark in the mark ring of the buffer, and `pos` in the
+/// global mark ring.
+pub fn push_mark(context: &mut Context, buffer_ref: BufferRef, pos: Cursor) {
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    if let Some(mark) = buffer.mark() {
+        let id = buffer.markers.add(mark, false);
+        buffer.mark_ring.push_front(id);
+        if buffer.mark_ring.len() > MARK_RING_MAX {
+            let oldest = buffer.mark_ring.pop_back().unwrap();
+            buffer.markers.remove(oldest);
+        }
+    }
+    buffer.set_mark(Some(pos));
+
+    if buffer_ref == BufferRef::minibuffer_window() {
+        return;
+    }
+    let global = &context.mark_ring.global;
+    if global.front().map(|(r, _)| *r) != Some(buffer_ref) {
+        let id = buffer.markers.add(pos, false);
+        context.mark_ring.global.push_front((buffer_ref, id));
+        if context.mark_ring.global.len() > GLOBAL_MARK_RING_MAX {
+            let oldest = context.mark_ring.global.pop_back();
+            remove_positions(context, oldest);
+        }
+    }
+}
+
+/// Save the cursor of the current buffer before a long jump: set the
+/// mark there, and record it in the jump history.
+pub fn push_jump(context: &mut Context) {
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let cursor = buffer.cursor();
+    if buffer_ref != BufferRef::minibuffer_window() {
+        let id = buffer.markers.add(cursor, false);
+        let dropped = context.mark_ring.history.record((buffer_ref, id));
+        remove_positions(context, dropped);
+    }
+    push_mark(context, buffer_ref, cursor);
+}
+
+/// Show `position` in the selected window and move to it.
+pub fn goto_position(context: &mut Context, (buffer_ref, id): Position) {
+    let window_list = &mut context.window_list;
+    window_list.minibuffer_focused = false;
+    let window = &mut window_list.windows[window_list.selected];
+    if window.buffer_ref != buffer_ref {
+        window.set_buffer(buffer_ref);
+    }
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    if let Some(pos) = buffer.markers.get(id) {
+        buffer.set_cursor(pos);
+    }
+}
+
+/// Move to the mark, and set the mark to the previous one from the
+/// mark ring of the buffer. The current mark goes to the end of the
+/// ring.
+pub fn pop_to_mark_command(context: &mut Context, _term: &mut Term) -> Result {
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let mark = match buffer.mark() {
+        Some(mark) => mark,
+        None => {
+            message(context, "No mark set in this buffer");
+            return Err(());
+        }
+    };
+    buffer.set_cursor(mark);
+    if let Some(id) = buffer.mark_ring.pop_front() {
+        buffer.set_mark(buffer.markers.get(id));
+        buffer.markers.set(id, mark);
+        buffer.mark_ring.push_back(id);
+    }
+    Ok(())
+}
+
+/// Move to the most recent position of the global mark ring, in its
+/// buffer. It goes to the end of the ring.
+pub fn pop_global_mark(context: &mut Context, _term: &mut Term) -> Result {
+    let position = match context.mark_ring.global.pop_front() {
+        Some(position) => position,
+        None => {
+            message(context, "No global mark set");
+            return Err(());
+        }
+    };
+    context.mark_ring.global.push_back(position);
+    goto_position(context, position);
+    Ok(())
+}
+
+/// Go back to where the previous long jump started.
+pub fn jump_backward(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer_ref = context.window_list.selected_window().buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let cursor = buffer.cursor();
+    let markers = &mut buffer.markers;
+    match context
+        .mark_ring
+        .history
+        .back(|| (buffer_ref, markers.add(cursor, false)))
+    {
+        Some(position) => {
+            goto_position(context, position);
+            Ok(())
+        }
+        None => {
+            message(context, "No previous jump");
+            Err(())
+        }
+    }
+}
+
+/// Go forward to where `jump-backward` came from.
+pub fn jump_forward(context: &mut Context, _term: &mut Term) -> Result {
+    match context.mark_ring.history.forward() {
+        Some(position) => {
+            goto_position(context, position);
+            Ok(())
+        }
+        None => {
+            message(context, "No next jump");
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn save_marks_in_the_rings() {
+        let mut context = Context::new(Buffer::from_string("a\nb\nc\nd"));
+        let main = BufferRef::main_window();
+        push_mark(&mut context, main, Cursor::new(1, 0));
+        push_mark(&mut context, main, Cursor::new(2, 0));
+        push_mark(&mut context, main, Cursor::new(3, 0));
+        assert_eq!(context.mark_ring.global.len(), 1);
+
+        let buffer = context.buffer_list.resolve_ref_as_mut(main);
+        buffer.insert_at(Cursor::new(0, 0), "z\n");
+        assert_eq!(buffer.mark(), Some(Cursor::new(4, 0)));
+        assert_eq!(
+            buffer.markers.get(buffer.mark_ring[0]),
+            Some(Cursor::new(3, 0))
+        );
+        assert_eq!(
+            buffer.markers.get(buffer.mark_ring[1]),
+            Some(Cursor::new(2, 0))
+        );
+    }
+
+    #[test]
+    fn go_back_and_forward_between_jumps() {
+        let mut context = Context::new(Buffer::from_string("a\nb\nc\nd"));
+        let main = BufferRef::main_window();
+        let mut history = JumpHistory::new();
+        let record = |context: &mut Context, history: &mut JumpHistory, pos| {
+            let buffer = context.buffer_list.resolve_ref_as_mut(main);
+            history.record((main, buffer.markers.add(pos, false)))
+        };
+        record(&mut context, &mut history, Cursor::new(0, 0));
+        record(&mut context, &mut history, Cursor::new(2, 0));
+
+        let buffer = context.buffer_list.resolve_ref_as_mut(main);
+        let (_, back) = history
+            .back(|| (main, buffer.markers.add(Cursor::new(3, 0), false)))
+            .unwrap();
+        assert_eq!(buffer.markers.get(back), Some(Cursor::new(2, 0)));
+        let (_, back) = history.back(|| unreachable!()).unwrap();
+        assert_eq!(buffer.markers.get(back), Some(Cursor::new(0, 0)));
+        assert!(history.back(|| unreachable!()).is_none());
+
+        let (_, forward) = history.forward().unwrap();
+        assert_eq!(buffer.markers.get(forward), Some(Cursor::new(2, 0)));
+
+        // A new jump drops the entries after the current one.
+        let dropped = record(&mut context, &mut history, Cursor::new(1, 0));
+        assert_eq!(dropped.len(), 2);
+        assert!(history.forward().is_none());
+    }
+}
@@ -0,0 +1,161 @@
+//! Markers
+//!
+//! A marker is a position in a buffer that moves with the text: text
+//! inserted or deleted before it moves it, so it stays next to the
+//! same character. Point, the mark, the ends of overlays and the
+//! points of windows are all markers, and this is the only place
+//! where positions are adjusted for edits.
+//!
+
+use std::collections::HashMap;
+
+use crate::Cursor;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct MarkerId(u64);
+
+/// Return where `pos` is after inserting text from `start` to `end`.
+///
+/// A position at `start` stays before the text unless `advance` is
+/// set.
+pub fn position_after_insert(pos: Cursor, start: Cursor, end: Cursor, advance: bool) -> Cursor {
+    if pos < start || (pos == start && !advance) {
+        pos
+    } else if pos.line == start.line {
+        Cursor {
+            line: end.line,
+            column: end.column + pos.column - start.column,
+        }
+    } else {
+        Cursor {
+            line: pos.line + end.line - start.line,
+            column: pos.column,
+        }
+    }
+}
+
+/// Return where `pos` is after deleting the text from `start` to
+/// `end`. Positions in the deleted text go to `start`.
+pub fn position_after_delete(pos: Cursor, start: Cursor, end: Cursor) -> Cursor {
+    if pos <= start {
+        pos
+    } else if pos <= end {
+        start
+    } else if pos.line == end.line {
+        Cursor {
+            line: start.line,
+            column: start.column + pos.column - end.column,
+        }
+    } else {
+        Cursor {
+            line: pos.line - (end.line - start.line),
+            column: pos.column,
+        }
+    }
+}
+
+struct Marker {
+    pos: Cursor,
+    /// If set, text inserted at the marker goes before it.
+    insertion_type: bool,
+}
+
+/// The markers of a buffer.
+pub struct MarkerList {
+    markers: HashMap<MarkerId, Marker>,
+    next_id: u64,
+}
+
+impl MarkerList {
+    pub fn new() -> MarkerList {
+        MarkerList {
+            markers: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Add a marker at `pos` and return its id. If `insertion_type`
+    /// is set, the marker advances when text is inserted at it.
+    pub fn add(&mut self, pos: Cursor, insertion_type: bool) -> MarkerId {
+        let id = MarkerId(self.next_id);
+        self.next_id += 1;
+        self.markers.insert(
+            id,
+            Marker {
+                pos,
+                insertion_type,
+            },
+        );
+        id
+    }
+
+    /// Return the position of the marker `id`, or None if it was
+    /// deleted.
+    pub fn get(&self, id: MarkerId) -> Option<Cursor> {
+        self.markers.get(&id).map(|marker| marker.pos)
+    }
+
+    /// Move the marker `id` to `pos`. Return false if it was deleted.
+    pub fn set(&mut self, id: MarkerId, pos: Cursor) -> bool {
+        match self.markers.get_mut(&id) {
+            Some(marker) => {
+                marker.pos = pos;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Delete the marker `id`. It is no longer adjusted.
+    pub fn remove(&mut self, id: MarkerId) {
+        self.markers.remove(&id);
+    }
+
+    /// Move the markers after text was inserted from `start` to `end`.
+    pub fn adjust_for_insert(&mut self, start: Cursor, end: Cursor) {
+        for marker in self.markers.values_mut() {
+            marker.pos = position_after_insert(marker.pos, start, end, marker.insertion_type);
+        }
+    }
+
+    /// Move the markers after the text from `start` to `end` was
+    /// deleted.
+    pub fn adjust_for_delete(&mut self, start: Cursor, end: Cursor) {
+        for marker in self.markers.values_mut() {
+            marker.pos = position_after_delete(marker.pos, start, end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Buffer;
+    use crate::Cursor;
+
+    #[test]
+    fn keep_markers_on_their_text() {
+        let mut buffer = Buffer::from_string("one\ntwo three");
+        let before = buffer.markers.add(Cursor::new(1, 4), false);
+        let after = buffer.markers.add(Cursor::new(1, 4), true);
+        buffer.set_cursor(Cursor::new(1, 4));
+        buffer.set_mark(Some(Cursor::new(1, 8)));
+
+        buffer.insert_at(Cursor::new(0, 0), "zero\n");
+        assert_eq!(buffer.markers.get(before), Some(Cursor::new(2, 4)));
+        assert_eq!(buffer.cursor(), Cursor::new(2, 4));
+        assert_eq!(buffer.mark(), Some(Cursor::new(2, 8)));
+
+        buffer.insert_at(Cursor::new(2, 4), "2 ");
+        assert_eq!(buffer.markers.get(before), Some(Cursor::new(2, 4)));
+        assert_eq!(buffer.markers.get(after), Some(Cursor::new(2, 6)));
+        assert_eq!(buffer.mark(), Some(Cursor::new(2, 10)));
+
+        buffer.delete_region(Cursor::new(0, 2), Cursor::new(2, 5));
+        assert_eq!(buffer.markers.get(before), Some(Cursor::new(0, 2)));
+        assert_eq!(buffer.markers.get(after), Some(Cursor::new(0, 3)));
+        assert_eq!(buffer.mark(), Some(Cursor::new(0, 7)));
+
+        buffer.markers.remove(before);
+        assert_eq!(buffer.markers.get(before), None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Messages log
+//!
+//! Messages shown in the minibuffer are also appended to the
+//! `*Messages*` buffer, so they can be read again after they are
+//! gone from the screen. Consecutive repetitions of the same message
+//! are collapsed into a single line with a count.
+//!
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::libc;
+
+use crate::buffer_list::BufferRef;
+use crate::commands::Result;
+use crate::term::Term;
+use crate::window::{self, message};
+use crate::{Context, Cursor, Keymap};
+
+/// The name of the buffer where messages are logged.
+pub const MESSAGES_BUFFER: &str = "*Messages*";
+
+struct LastMessage {
+    text: String,
+    /// The last line of the message as written in the buffer.
+    last_line: String,
+    count: usize,
+}
+
+pub struct MessagesState {
+    /// The maximum number of lines to keep in the messages buffer.
+    pub log_max: usize,
+    /// If set, messages are logged with the time they were shown.
+    pub timestamps: bool,
+
+    /// The last message logged and how many times it has been
+    /// repeated.
+    last: Option<LastMessage>,
+}
+
+impl MessagesState {
+    pub fn new() -> MessagesState {
+        MessagesState {
+            log_max: 1000,
+            timestamps: false,
+            last: None,
+        }
+    }
+}
+
+/// Format the current local time as `HH:MM:SS.mmm`.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs() as libc::time_t;
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&seconds, &mut tm);
+        tm
+    };
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+        now.subsec_millis()
+    )
+}
+
+/// Return the messages buffer, creating it if needed.
+fn messages_buffer(context: &mut Context) -> BufferRef {
+    match context.buffer_list.find(MESSAGES_BUFFER) {
+        Some(buffer_ref) => buffer_ref,
+        None => {
+            let buffer_ref = context.buffer_list.get_or_create(MESSAGES_BUFFER);
+            let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+            buffer.read_only = true;
+            buffer.keymap = Keymap::special_mode();
+            buffer_ref
+        }
+    }
+}
+
+/// Append `text` to the messages buffer.
+///
+/// The buffer always ends with an empty line, so a cursor at the end
+/// of the buffer stays there as new messages are logged.
+pub fn log(context: &mut Context, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    let state = &mut context.messages;
+    let repeated = match &mut state.last {
+        Some(last) if last.text == text => {
+            last.count += 1;
+            Some((last.last_line.len(), format!(" [{} times]", last.count)))
+        }
+        _ => None,
+    };
+    let logged = if state.timestamps {
+        format!("[{}] {}", timestamp(), text)
+    } else {
+        text.to_string()
+    };
+    if repeated.is_none() {
+        state.last = Some(LastMessage {
+            text: text.to_string(),
+            last_line: logged.rsplit('\n').next().unwrap_or("").to_string(),
+            count: 1,
+        });
+    }
+    let log_max = state.log_max;
+
+    let buffer_ref = messages_buffer(context);
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let at_end = buffer.cursor() == buffer.end_position();
+
+    match repeated {
+        Some((column, count)) => {
+            // Replace the count after the last line of the previous
+            // message.
+            let last = buffer.lines_count() - 2;
+            let start = Cursor::new(last, column);
+            let end = Cursor::new(last, buffer.get_line_unchecked(last).len());
+            buffer.delete_region(start, end);
+            buffer.insert_at(start, &count);
+        }
+        None => {
+            for line in logged.split('\n') {
+                buffer.insert_line_at(buffer.lines_count() - 1, line.to_string());
+            }
+        }
+    }
+
+    let excess = (buffer.lines_count() - 1).saturating_sub(log_max);
+    for _ in 0..excess {
+        buffer.remove_line(0);
+    }
+
+    if at_end {
+        buffer.set_cursor(buffer.end_position());
+    }
+}
+
+/// Show the messages buffer in another window, at its end.
+pub fn view_echo_area_messages(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer_ref = messages_buffer(context);
+    let index = context.window_list.display_buffer(buffer_ref);
+    window::select_window(context, index);
+
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.set_cursor(buffer.end_position());
+    Ok(())
+}
+
+pub fn toggle_message_timestamps(context: &mut Context, _term: &mut Term) -> Result {
+    context.messages.timestamps = !context.messages.timestamps;
+    if context.messages.timestamps {
+        message(context, "Message timestamps enabled");
+    } else {
+        message(context, "Message timestamps disabled");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    fn messages(context: &Context) -> String {
+        let buffer_ref = context.buffer_list.find(MESSAGES_BUFFER).unwrap();
+        context.buffer_list.resolve_ref(buffer_ref).to_string()
+    }
+
+    #[test]
+    fn collapse_repeated_messages() {
+        let mut context = Context::new(Buffer::new());
+        log(&mut context, "Mark set");
+        log(&mut context, "Quit");
+        log(&mut context, "Quit");
+        log(&mut context, "Quit");
+        log(&mut context, "Mark set");
+        assert_eq!(messages(&context), "Mark set\nQuit [3 times]\nMark set\n");
+    }
+
+    #[test]
+    fn keep_at_most_log_max_lines() {
+        let mut context = Context::new(Buffer::new());
+        context.messages.log_max = 2;
+        log(&mut context, "one");
+        log(&mut context, "two\nthree");
+        log(&mut context, "four");
+        assert_eq!(messages(&context), "three\nfour\n");
+    }
+
+    #[test]
+    fn keep_positions_in_the_messages_buffer() {
+        let mut context = Context::new(Buffer::new());
+        view_echo_area_messages(&mut context, &mut Term::new()).unwrap();
+        log(&mut context, "Quit [2 times]");
+        log(&mut context, "Quit");
+        log(&mut context, "Mark set");
+
+        let buffer_ref = messages_buffer(&mut context);
+        let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+        buffer.set_cursor(Cursor::new(2, 8));
+        buffer.set_mark(Some(Cursor::new(1, 2)));
+        log(&mut context, "Mark set");
+        log(&mut context, "Mark set");
+
+        let buffer = context.buffer_list.resolve_ref(buffer_ref);
+        assert_eq!(buffer.get_line_unchecked(2), "Mark set [3 times]");
+        assert_eq!(buffer.cursor(), Cursor::new(2, 8));
+        assert_eq!(buffer.mark(), Some(Cursor::new(1, 2)));
+    }
+}
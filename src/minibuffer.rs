@@ -9,11 +9,11 @@ pub fn minibuffer_complete(context: &mut Context, _term: &mut Term) -> commands:
 pub fn new() -> Buffer {
     let mut minibuffer = Buffer::new();
     let mut keymap = Keymap::new();
-    keymap.define_key("RET", minibuffer_complete);
-    keymap.define_key("C-a", commands::beginning_of_buffer);
-    keymap.define_key("C-e", commands::end_of_buffer);
-    keymap.define_key("C-g", commands::keyboard_quit);
-    keymap.define_key("DEL", commands::delete_backward_char);
+    keymap.define_key("RET", "exit-minibuffer");
+    keymap.define_key("C-a", "beginning-of-buffer");
+    keymap.define_key("C-e", "end-of-buffer");
+    keymap.define_key("C-g", "keyboard-quit");
+    keymap.define_key("DEL", "delete-backward-char");
 
     minibuffer.keymap = keymap;
     minibuffer
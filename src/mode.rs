@@ -0,0 +1,107 @@
+//! Major modes
+//!
+//! The major mode of a buffer customizes the editor for the kind of
+//! text it contains, like prose or source code of a specific
+//! language.
+//!
+
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::indent::{self, IndentFunction};
+use crate::syntax::{SyntaxClass, SyntaxTable};
+
+#[derive(Clone)]
+pub struct MajorMode {
+    pub name: &'static str,
+    /// A description of the mode, shown by `describe-mode`.
+    pub doc: &'static str,
+    pub syntax_table: Rc<SyntaxTable>,
+
+    /// Compute the indentation of a line.
+    pub indent_function: IndentFunction,
+    /// Number of columns for each level of indentation.
+    pub indent_offset: usize,
+    /// The default for `Buffer::indent_tabs_mode` in this mode.
+    pub indent_tabs_mode: bool,
+}
+
+impl MajorMode {
+    pub fn fundamental() -> MajorMode {
+        MajorMode {
+            name: "Fundamental",
+            doc: "Major mode not specialized for anything in particular.\n\
+                  Lines are indented like the previous non-blank line.",
+            syntax_table: Rc::new(SyntaxTable::standard()),
+            indent_function: indent::indent_relative,
+            indent_offset: 4,
+            indent_tabs_mode: true,
+        }
+    }
+
+    pub fn text() -> MajorMode {
+        let mut table = SyntaxTable::standard();
+        // Contractions like "don't" are a single word.
+        table.set('\'', SyntaxClass::Word);
+        MajorMode {
+            name: "Text",
+            doc: "Major mode for editing text written for humans to read.\n\
+                  Apostrophes are part of words.",
+            syntax_table: Rc::new(table),
+            ..MajorMode::fundamental()
+        }
+    }
+
+    /// A syntax table for C-like programming languages.
+    fn prog_syntax_table() -> SyntaxTable {
+        let mut table = SyntaxTable::standard();
+        table.set('_', SyntaxClass::Symbol);
+        table.set('"', SyntaxClass::StringQuote);
+        table.set('\\', SyntaxClass::Escape);
+        table.line_comment = Some("//");
+        table.block_comment = Some(("/*", "*/"));
+        table
+    }
+
+    pub fn rust() -> MajorMode {
+        // Single quotes are not string delimiters in Rust, as they
+        // are used for lifetimes as well.
+        MajorMode {
+            name: "Rust",
+            doc: "Major mode for editing Rust code.\n\
+                  Lines are indented according to the nesting of delimiters.",
+            syntax_table: Rc::new(MajorMode::prog_syntax_table()),
+            indent_function: indent::indent_braces,
+            indent_offset: 4,
+            indent_tabs_mode: false,
+        }
+    }
+
+    pub fn c() -> MajorMode {
+        let mut table = MajorMode::prog_syntax_table();
+        table.set('\'', SyntaxClass::StringQuote);
+        MajorMode {
+            name: "C",
+            doc: "Major mode for editing C and similar languages.\n\
+                  Lines are indented according to the nesting of delimiters.",
+            syntax_table: Rc::new(table),
+            indent_function: indent::indent_braces,
+            indent_offset: 4,
+            indent_tabs_mode: true,
+        }
+    }
+
+    /// Choose the major mode for a file based on its extension.
+    pub fn for_filename(filename: &str) -> MajorMode {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+        match extension {
+            "txt" | "md" | "markdown" | "org" => MajorMode::text(),
+            "rs" => MajorMode::rust(),
+            "c" | "h" | "cc" | "cpp" | "hpp" | "java" | "js" => MajorMode::c(),
+            _ => MajorMode::fundamental(),
+        }
+    }
+}
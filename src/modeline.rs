@@ -0,0 +1,258 @@
+//! Modeline
+//!
+//! The modeline at the bottom of each window shows information about
+//! its buffer. What it shows is set by a format, like Emacs
+//! `mode-line-format`, where %-constructs are replaced by the buffer
+//! name, the line number, and so on. Extensions can add their own
+//! segments, which the format refers to by name.
+//!
+
+use crate::buffer::Buffer;
+use crate::commands::Result;
+use crate::face::Face;
+use crate::layout::Region;
+use crate::read;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Context, Window};
+
+/// The default modeline format.
+pub const DEFAULT_FORMAT: &str = "  %Z%*%+  %b  %p L%l C%c  %[(%m%M)%]";
+
+/// Face of the modeline of the selected window.
+pub const ACTIVE_FACE: Face = Face {
+    fg: Some(15),
+    bg: Some(236),
+    reverse: false,
+    bold: false,
+};
+
+/// Face of the modeline of the other windows.
+pub const INACTIVE_FACE: Face = Face {
+    fg: Some(246),
+    bg: Some(234),
+    reverse: false,
+    bold: false,
+};
+
+/// A function returning the text of a custom segment for a window.
+pub type SegmentFunction = fn(&Context, &Window) -> String;
+
+pub struct ModelineState {
+    /// The format of the modeline, with %-constructs:
+    ///
+    /// - `%b`: the buffer name
+    /// - `%f`: the file name of the buffer
+    /// - `%*`: `%` if the buffer is read-only, `*` if it is modified,
+    ///   `-` otherwise
+    /// - `%+`: `*` if the buffer is modified, `%` if it is read-only,
+    ///   `-` otherwise
+    /// - `%l`, `%c`: the line and the column of point, from 1 and 0
+    /// - `%C`: the column of point, from 1
+    /// - `%p`: how much of the accessible portion of the buffer is
+    ///   above the top of the window
+    /// - `%i`, `%I`: the size of the buffer, and in a readable form
+    /// - `%m`: the name of the major mode
+    /// - `%M`: the minor modes, each preceded by a space
+    /// - `%z`, `%Z`: the encoding, and the encoding and end of line
+    ///   style
+    /// - `%[`, `%]`: one bracket for each level of recursive editing
+    /// - `%{name}`: the custom segment `name`
+    /// - `%%`: a `%`
+    pub format: String,
+    pub active_face: Face,
+    pub inactive_face: Face,
+
+    segments: Vec<(String, SegmentFunction)>,
+}
+
+impl ModelineState {
+    pub fn new() -> ModelineState {
+        ModelineState {
+            format: DEFAULT_FORMAT.to_string(),
+            active_face: ACTIVE_FACE,
+            inactive_face: INACTIVE_FACE,
+            segments: vec![],
+        }
+    }
+
+    /// Define the custom segment `name`, shown by `%{name}` in the
+    /// format. A segment with the same name is replaced.
+    pub fn add_segment(&mut self, name: &str, function: SegmentFunction) {
+        self.segments.retain(|(n, _)| n != name);
+        self.segments.push((name.to_string(), function));
+    }
+
+    fn segment(&self, name: &str) -> Option<SegmentFunction> {
+        self.segments
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, function)| *function)
+    }
+}
+
+/// Return `size` in a short readable form, like `12k` or `3.4M`.
+fn human_size(size: usize) -> String {
+    let mut value = size as f64;
+    for unit in &["", "k", "M", "G"] {
+        if value < 1000.0 {
+            if unit.is_empty() || value >= 10.0 {
+                return format!("{:.0}{}", value, unit);
+            } else {
+                return format!("{:.1}{}", value, unit);
+            }
+        }
+        value /= 1000.0;
+    }
+    format!("{:.0}T", value)
+}
+
+fn minor_modes(context: &Context, window: &Window) -> String {
+    let mut modes = String::new();
+    if context.kmacro.is_recording() {
+        modes.push_str(" Def");
+    }
+    if window.visual_line_mode {
+        modes.push_str(" Wrap");
+    }
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    if buffer.hs_minor_mode {
+        modes.push_str(" hs");
+    }
+    if buffer.outline_minor_mode {
+        modes.push_str(" Outl");
+    }
+    if buffer.narrowing.is_some() {
+        modes.push_str(" Narrow");
+    }
+    modes
+}
+
+/// The end of line style mnemonic of `buffer`.
+fn eol_mnemonic(buffer: &Buffer) -> char {
+    if buffer.get_line_unchecked(0).ends_with('\r') {
+        '\\'
+    } else {
+        ':'
+    }
+}
+
+/// Return the modeline of `window`, shown in `region`.
+pub fn format_modeline(
+    context: &Context,
+    window: &Window,
+    term_columns: usize,
+    region: &Region,
+) -> String {
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let format = &context.modeline.format;
+    let mut text = String::new();
+    let mut chars = format.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            text.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => text.push_str(&buffer.name),
+            Some('f') => text.push_str(buffer.filename.as_deref().unwrap_or("")),
+            Some('*') if buffer.read_only => text.push('%'),
+            Some('+') if buffer.modified => text.push('*'),
+            Some('*') if buffer.modified => text.push('*'),
+            Some('+') if buffer.read_only => text.push('%'),
+            Some('*') | Some('+') => text.push('-'),
+            Some('l') => text.push_str(&(buffer.cursor().line + 1).to_string()),
+            Some('c') => text.push_str(&buffer.display_column(buffer.cursor()).to_string()),
+            Some('C') => text.push_str(&(buffer.display_column(buffer.cursor()) + 1).to_string()),
+            Some('p') => {
+                // Only the accessible portion of the buffer counts.
+                let (min, max) = (buffer.point_min().line, buffer.point_max().line);
+                let top = window.scroll_line <= min;
+                let bottom = window.is_end_visible(buffer, term_columns, region);
+                match (top, bottom) {
+                    (true, true) => text.push_str("All"),
+                    (true, false) => text.push_str("Top"),
+                    (false, true) => text.push_str("Bot"),
+                    (false, false) => text.push_str(&format!(
+                        "{}%",
+                        100 * (window.scroll_line - min) / (max - min + 1)
+                    )),
+                }
+            }
+            Some('i') => {
+                let size = buffer.char_offset(buffer.end_position());
+                text.push_str(&size.to_string());
+            }
+            Some('I') => text.push_str(&human_size(buffer.char_offset(buffer.end_position()))),
+            Some('m') => text.push_str(buffer.mode.name),
+            Some('M') => text.push_str(&minor_modes(context, window)),
+            Some('z') => text.push('U'),
+            Some('Z') => {
+                text.push('U');
+                text.push(eol_mnemonic(buffer));
+            }
+            Some('[') => text.push_str(&"[".repeat(context.event_loop.recursion_depth())),
+            Some(']') => text.push_str(&"]".repeat(context.event_loop.recursion_depth())),
+            Some('{') => {
+                let name: String = chars.by_ref().take_while(|ch| *ch != '}').collect();
+                if let Some(function) = context.modeline.segment(&name) {
+                    text.push_str(&function(context, window));
+                }
+            }
+            Some('%') => text.push('%'),
+            Some(other) => {
+                text.push('%');
+                text.push(other);
+            }
+            None => text.push('%'),
+        }
+    }
+
+    text.chars().take(term_columns).collect()
+}
+
+pub fn set_mode_line_format(context: &mut Context, term: &mut Term) -> Result {
+    let input =
+        read::read_string(term, context, "Mode line format: ", |_, _| {}, false).map_err(|_| ())?;
+    if input.is_empty() {
+        context.modeline.format = DEFAULT_FORMAT.to_string();
+        message(context, "Mode line format reset");
+    } else {
+        context.modeline.format = input;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cursor;
+
+    fn modeline(context: &Context) -> String {
+        let region = Region { top: 0, height: 10 };
+        format_modeline(context, &context.window_list.windows[0], 80, &region)
+    }
+
+    #[test]
+    fn replace_constructs() {
+        let mut buffer = Buffer::from_string("one\ntwo\tx");
+        buffer.name = "notes".to_string();
+        buffer.modified = true;
+        buffer.set_cursor(Cursor { line: 1, column: 4 });
+        let mut context = Context::new(buffer);
+
+        context.modeline.format = "%b%* L%l C%c %p %i %m%{empty} 100%%".to_string();
+        assert_eq!(modeline(&context), "notes* L2 C8 All 9 Fundamental 100%");
+
+        context
+            .modeline
+            .add_segment("empty", |_, _| "!".to_string());
+        context.modeline.format = "%{empty}%{missing}%q".to_string();
+        assert_eq!(modeline(&context), "!%q");
+
+        assert_eq!(human_size(999), "999");
+        assert_eq!(human_size(1234), "1.2k");
+        assert_eq!(human_size(56_000_000), "56M");
+    }
+}
@@ -0,0 +1,248 @@
+//! Text units
+//!
+//! Functions to find the boundaries of words, sentences and
+//! paragraphs around a position of a buffer. They do not modify the
+//! buffer, the commands built on top of them do.
+//!
+
+use crate::{Buffer, Cursor};
+
+/// Characters that end a sentence.
+const SENTENCE_END: &[char] = &['.', '?', '!'];
+
+/// Characters that can follow the end of a sentence, like closing
+/// quotes or parenthesis.
+const SENTENCE_CLOSE: &[char] = &['"', '\'', ')', ']'];
+
+fn is_word_at(buffer: &Buffer, pos: Cursor) -> bool {
+    let table = &buffer.mode.syntax_table;
+    buffer.char_after(pos).is_some_and(|ch| table.is_word(ch))
+}
+
+fn is_word_before(buffer: &Buffer, pos: Cursor) -> bool {
+    let table = &buffer.mode.syntax_table;
+    buffer.char_before(pos).is_some_and(|ch| table.is_word(ch))
+}
+
+/// Return the position at the end of the next word.
+pub fn forward_word(buffer: &Buffer, mut pos: Cursor) -> Cursor {
+    while !is_word_at(buffer, pos) {
+        match buffer.forward_pos(pos) {
+            Some(next) => pos = next,
+            None => return pos,
+        }
+    }
+    while is_word_at(buffer, pos) {
+        pos = buffer.forward_pos(pos).unwrap();
+    }
+    pos
+}
+
+/// Return the start and end of the word around `pos`, if there is
+/// one.
+pub fn word_at(buffer: &Buffer, pos: Cursor) -> Option<(Cursor, Cursor)> {
+    if !is_word_at(buffer, pos) && !is_word_before(buffer, pos) {
+        return None;
+    }
+    let mut start = pos;
+    while is_word_before(buffer, start) {
+        start = buffer.backward_pos(start).unwrap();
+    }
+    let mut end = pos;
+    while is_word_at(buffer, end) {
+        end = buffer.forward_pos(end).unwrap();
+    }
+    Some((start, end))
+}
+
+/// Return the position at the beginning of the previous word.
+pub fn backward_word(buffer: &Buffer, mut pos: Cursor) -> Cursor {
+    while !is_word_before(buffer, pos) {
+        match buffer.backward_pos(pos) {
+            Some(previous) => pos = previous,
+            None => return pos,
+        }
+    }
+    while is_word_before(buffer, pos) {
+        pos = buffer.backward_pos(pos).unwrap();
+    }
+    pos
+}
+
+fn is_blank_line(buffer: &Buffer, line: usize) -> bool {
+    buffer.get_line_unchecked(line).trim().is_empty()
+}
+
+/// Return the position of the blank line after the current
+/// paragraph, or the end of the buffer.
+pub fn forward_paragraph(buffer: &Buffer, pos: Cursor) -> Cursor {
+    let mut line = pos.line;
+    while line < buffer.lines_count() && is_blank_line(buffer, line) {
+        line += 1;
+    }
+    while line < buffer.lines_count() && !is_blank_line(buffer, line) {
+        line += 1;
+    }
+    if line < buffer.lines_count() {
+        Cursor { line, column: 0 }
+    } else {
+        buffer.end_position()
+    }
+}
+
+/// Return the position of the blank line before the current
+/// paragraph, or the beginning of the buffer.
+pub fn backward_paragraph(buffer: &Buffer, pos: Cursor) -> Cursor {
+    let mut line = pos.line as isize;
+    while line >= 0 && is_blank_line(buffer, line as usize) {
+        line -= 1;
+    }
+    while line >= 0 && !is_blank_line(buffer, line as usize) {
+        line -= 1;
+    }
+    if line >= 0 {
+        Cursor {
+            line: line as usize,
+            column: 0,
+        }
+    } else {
+        Cursor { line: 0, column: 0 }
+    }
+}
+
+/// Check if `pos` is right after the end of a sentence.
+///
+/// That is, after a sentence end character and possibly some closing
+/// characters, and followed by whitespace or the end of the buffer.
+fn is_sentence_end(buffer: &Buffer, pos: Cursor) -> bool {
+    if !buffer.char_after(pos).is_none_or(char::is_whitespace) {
+        return false;
+    }
+    let mut pos = pos;
+    while let Some(ch) = buffer.char_before(pos) {
+        if SENTENCE_END.contains(&ch) {
+            return true;
+        } else if SENTENCE_CLOSE.contains(&ch) {
+            pos = buffer.backward_pos(pos).unwrap();
+        } else {
+            return false;
+        }
+    }
+    false
+}
+
+fn skip_whitespace_forward(buffer: &Buffer, mut pos: Cursor) -> Cursor {
+    while buffer.char_after(pos).is_some_and(char::is_whitespace) {
+        pos = buffer.forward_pos(pos).unwrap();
+    }
+    pos
+}
+
+/// Return the position at the end of the current sentence.
+///
+/// Sentences do not extend beyond the paragraph they are part of.
+pub fn forward_sentence(buffer: &Buffer, pos: Cursor) -> Cursor {
+    let start = skip_whitespace_forward(buffer, pos);
+    let mut limit = forward_paragraph(buffer, start);
+    if limit.column == 0 && limit > start {
+        // Stop at the end of the last line of the paragraph rather
+        // than at the blank line that follows.
+        limit = buffer.backward_pos(limit).unwrap();
+    }
+
+    let mut current = start;
+    while current < limit {
+        current = buffer.forward_pos(current).unwrap();
+        if is_sentence_end(buffer, current) {
+            return current;
+        }
+    }
+    limit
+}
+
+/// Return the position at the beginning of the current sentence.
+pub fn backward_sentence(buffer: &Buffer, pos: Cursor) -> Cursor {
+    let limit = backward_paragraph(buffer, pos);
+    let limit = if is_blank_line(buffer, limit.line) {
+        skip_whitespace_forward(buffer, limit)
+    } else {
+        limit
+    };
+
+    let mut current = pos;
+    while current > limit {
+        current = buffer.backward_pos(current).unwrap();
+        if is_sentence_end(buffer, current) {
+            let start = skip_whitespace_forward(buffer, current);
+            if start < pos {
+                return start;
+            }
+        }
+    }
+    limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_by_words() {
+        let buffer = Buffer::from_string("  hello, world\nfoo");
+        assert_eq!(forward_word(&buffer, Cursor::new(0, 0)), Cursor::new(0, 7));
+        assert_eq!(forward_word(&buffer, Cursor::new(0, 7)), Cursor::new(0, 14));
+        assert_eq!(forward_word(&buffer, Cursor::new(0, 14)), Cursor::new(1, 3));
+        assert_eq!(backward_word(&buffer, Cursor::new(1, 0)), Cursor::new(0, 9));
+        assert_eq!(backward_word(&buffer, Cursor::new(0, 4)), Cursor::new(0, 2));
+    }
+
+    #[test]
+    fn move_by_paragraphs() {
+        let buffer = Buffer::from_string("a\nb\n\nc\nd");
+        assert_eq!(
+            forward_paragraph(&buffer, Cursor::new(0, 0)),
+            Cursor::new(2, 0)
+        );
+        assert_eq!(
+            forward_paragraph(&buffer, Cursor::new(2, 0)),
+            Cursor::new(4, 1)
+        );
+        assert_eq!(
+            backward_paragraph(&buffer, Cursor::new(4, 1)),
+            Cursor::new(2, 0)
+        );
+        assert_eq!(
+            backward_paragraph(&buffer, Cursor::new(2, 0)),
+            Cursor::new(0, 0)
+        );
+    }
+
+    #[test]
+    fn move_by_sentences() {
+        let buffer = Buffer::from_string("One. Two (three!) four.\n\nFive");
+        assert_eq!(
+            forward_sentence(&buffer, Cursor::new(0, 0)),
+            Cursor::new(0, 4)
+        );
+        assert_eq!(
+            forward_sentence(&buffer, Cursor::new(0, 4)),
+            Cursor::new(0, 17)
+        );
+        assert_eq!(
+            forward_sentence(&buffer, Cursor::new(0, 17)),
+            Cursor::new(0, 23)
+        );
+        assert_eq!(
+            backward_sentence(&buffer, Cursor::new(0, 20)),
+            Cursor::new(0, 18)
+        );
+        assert_eq!(
+            backward_sentence(&buffer, Cursor::new(0, 18)),
+            Cursor::new(0, 5)
+        );
+        assert_eq!(
+            backward_sentence(&buffer, Cursor::new(2, 2)),
+            Cursor::new(2, 0)
+        );
+    }
+}
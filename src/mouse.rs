@@ -0,0 +1,181 @@
+//! Mouse
+//!
+//! The terminal reports clicks, drags and the wheel as mouse keys,
+//! like `down-mouse-1`, that are bound in keymaps as any other key.
+//! The commands bound to them find what is under the mouse from the
+//! position of the last mouse event.
+//!
+
+use std::cmp;
+
+use crate::commands::Result;
+use crate::layout;
+use crate::term::Term;
+use crate::window::select_window;
+use crate::{Context, Cursor, Key};
+
+/// The number of lines scrolled by each step of the wheel.
+const WHEEL_LINES: usize = 3;
+
+pub struct MouseState {
+    /// The window and position where the first button was pressed,
+    /// to set the region when the mouse is dragged.
+    drag_start: Option<(usize, Cursor)>,
+}
+
+impl MouseState {
+    pub fn new() -> MouseState {
+        MouseState { drag_start: None }
+    }
+}
+
+/// What is under the mouse.
+enum Target {
+    /// The text of a window.
+    Text { window: usize },
+    /// The modeline of a window.
+    Modeline { window: usize },
+}
+
+/// Return what is at the screen `row`, ignoring the minibuffer.
+fn target_at(term: &Term, context: &Context, row: usize) -> Option<Target> {
+    let layout = layout::get_layout(term, context);
+    let window_list = &context.window_list;
+    let (window, region) = layout
+        .window_regions
+        .iter()
+        .enumerate()
+        .find(|(_, region)| region.top <= row && row < region.top + region.height)?;
+    if row - region.top < window_list.windows[window].window_lines(region) {
+        Some(Target::Text { window })
+    } else {
+        Some(Target::Modeline { window })
+    }
+}
+
+/// Return the position in the buffer of `window` shown at the screen
+/// `row` and `column`. Rows outside of the window go to its first or
+/// last row.
+fn position_in_window(
+    term: &Term,
+    context: &Context,
+    window: usize,
+    row: usize,
+    column: usize,
+) -> Cursor {
+    let layout = layout::get_layout(term, context);
+    let region = &layout.window_regions[window];
+    let window = &context.window_list.windows[window];
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let last_row = window.window_lines(region).saturating_sub(1);
+    let row = cmp::min(row.saturating_sub(region.top), last_row);
+    window.position_at(buffer, term.columns, region, row, column)
+}
+
+/// Return the window and the position in its buffer under the mouse,
+/// if it is on the text of a window.
+pub fn text_position_at_mouse(term: &Term, context: &Context) -> Option<(usize, Cursor)> {
+    let position = context.event_loop.mouse_position()?;
+    if context.window_list.minibuffer_focused {
+        return None;
+    }
+    match target_at(term, context, position.row)? {
+        Target::Text { window } => {
+            let cursor = position_in_window(term, context, window, position.row, position.column);
+            Some((window, cursor))
+        }
+        Target::Modeline { .. } => None,
+    }
+}
+
+/// Select the window under the mouse and move point to where it was
+/// clicked. Clicking a modeline only selects its window.
+pub fn mouse_set_point(context: &mut Context, term: &mut Term) -> Result {
+    context.mouse.drag_start = None;
+    let position = context.event_loop.mouse_position().ok_or(())?;
+    if context.window_list.minibuffer_focused {
+        return Ok(());
+    }
+
+    match target_at(term, context, position.row) {
+        Some(Target::Text { window }) => {
+            let cursor = position_in_window(term, context, window, position.row, position.column);
+            select_window(context, window);
+            let buffer_ref = context.window_list.windows[window].buffer_ref;
+            context
+                .buffer_list
+                .resolve_ref_as_mut(buffer_ref)
+                .set_cursor(cursor);
+            context.mouse.drag_start = Some((window, cursor));
+            Ok(())
+        }
+        Some(Target::Modeline { window }) => {
+            select_window(context, window);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Set the region from where the mouse was pressed to where it is
+/// now.
+pub fn mouse_drag_region(context: &mut Context, term: &mut Term) -> Result {
+    let position = context.event_loop.mouse_position().ok_or(())?;
+    let (window, start) = match context.mouse.drag_start {
+        Some(drag_start) if drag_start.0 < context.window_list.windows.len() => drag_start,
+        _ => return Ok(()),
+    };
+
+    let cursor = position_in_window(term, context, window, position.row, position.column);
+    let buffer_ref = context.window_list.windows[window].buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.set_mark(Some(start));
+    buffer.mark_active = true;
+    buffer.set_cursor(cursor);
+    Ok(())
+}
+
+/// Scroll the window under the mouse, selecting it.
+pub fn mwheel_scroll(context: &mut Context, term: &mut Term) -> Result {
+    let position = context.event_loop.mouse_position().ok_or(())?;
+    if context.window_list.minibuffer_focused {
+        return Ok(());
+    }
+    let window = match target_at(term, context, position.row) {
+        Some(Target::Text { window }) | Some(Target::Modeline { window }) => window,
+        None => return Ok(()),
+    };
+    let down =
+        context.event_loop.this_command_keys.last() == Some(&Key::parse_unchecked("wheel-down"));
+
+    select_window(context, window);
+    let buffer_ref = context.window_list.windows[window].buffer_ref;
+    let lines_count = context.buffer_list.resolve_ref(buffer_ref).lines_count();
+    let w = &mut context.window_list.windows[window];
+    w.scroll_line = if down {
+        cmp::min(w.scroll_line + WHEEL_LINES, lines_count - 1)
+    } else {
+        w.scroll_line.saturating_sub(WHEEL_LINES)
+    };
+
+    // Keep point in the window, or the scroll would be undone right
+    // away to show it.
+    let layout = layout::get_layout(term, context);
+    let region = &layout.window_regions[window];
+    let w = &context.window_list.windows[window];
+    let buffer = context.buffer_list.resolve_ref(buffer_ref);
+    let first = w.position_at(buffer, term.columns, region, 0, 0);
+    let last_row = w.window_lines(region).saturating_sub(1);
+    let last = w.position_at(buffer, term.columns, region, last_row, 0);
+
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    if buffer.cursor() < first {
+        buffer.set_cursor(first);
+    } else if buffer.cursor().line > last.line {
+        buffer.set_cursor(Cursor {
+            line: last.line,
+            column: 0,
+        });
+    }
+    Ok(())
+}
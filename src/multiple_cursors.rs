@@ -0,0 +1,410 @@
+//! Multiple cursors
+//!
+//! Fake cursors are extra points of a buffer, each with its own mark,
+//! kill ring and goal column. Editing and motion commands, and the
+//! insertion of characters, run at the cursor of the buffer first and
+//! then at each fake cursor, made the real cursor for the time of the
+//! command. Fake cursors are markers, so they follow the edits made
+//! at the other cursors.
+//!
+
+use crate::buffer_list::BufferRef;
+use crate::commands::{self, Result};
+use crate::kill_ring::KillRing;
+use crate::marker::MarkerId;
+use crate::motion;
+use crate::mouse;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Buffer, Context, Cursor};
+
+pub struct FakeCursor {
+    pub point: MarkerId,
+    pub mark: Option<MarkerId>,
+    pub kill_ring: KillRing,
+    pub goal_column: Option<usize>,
+}
+
+impl FakeCursor {
+    /// Save the cursor and mark of the buffer `buffer_ref`, with the
+    /// kill ring and goal column, in a fake cursor. The kill ring of
+    /// the context is left empty.
+    fn save(context: &mut Context, buffer_ref: BufferRef) -> FakeCursor {
+        let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+        let point = buffer.markers.add(buffer.cursor(), false);
+        let mark = buffer.mark().map(|mark| buffer.markers.add(mark, false));
+        let mut kill_ring = std::mem::replace(&mut context.kill_ring, KillRing::new());
+        // Do what the event loop does to the real kill ring and goal
+        // column after a command.
+        if !kill_ring.to_preserve {
+            kill_ring.append = false;
+        }
+        let goal_column = context
+            .goal_column
+            .column
+            .filter(|_| context.goal_column.to_preserve);
+        FakeCursor {
+            point,
+            mark,
+            kill_ring,
+            goal_column,
+        }
+    }
+
+    /// Make the fake cursor the real cursor of the buffer `buffer_ref`,
+    /// deleting its markers.
+    fn restore(self, context: &mut Context, buffer_ref: BufferRef) {
+        let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+        if let Some(pos) = buffer.markers.get(self.point) {
+            buffer.set_cursor(pos);
+        }
+        buffer.set_mark(self.mark.and_then(|mark| buffer.markers.get(mark)));
+        remove_markers(buffer, &self);
+        context.kill_ring = self.kill_ring;
+        context.goal_column.column = self.goal_column;
+    }
+}
+
+fn remove_markers(buffer: &mut Buffer, cursor: &FakeCursor) {
+    buffer.markers.remove(cursor.point);
+    if let Some(mark) = cursor.mark {
+        buffer.markers.remove(mark);
+    }
+}
+
+/// Add a fake cursor at `point` in the buffer `buffer_ref`, with a
+/// copy of the kill ring.
+fn add_fake_cursor(
+    context: &mut Context,
+    buffer_ref: BufferRef,
+    point: Cursor,
+    mark: Option<Cursor>,
+) {
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let cursor = FakeCursor {
+        point: buffer.markers.add(point, false),
+        mark: mark.map(|mark| buffer.markers.add(mark, false)),
+        kill_ring: context.kill_ring.clone(),
+        goal_column: None,
+    };
+    buffer.fake_cursors.push(cursor);
+}
+
+/// Return the positions of the fake cursors of `buffer`.
+pub fn fake_cursor_positions(buffer: &Buffer) -> Vec<Cursor> {
+    buffer
+        .fake_cursors
+        .iter()
+        .filter_map(|cursor| buffer.markers.get(cursor.point))
+        .collect()
+}
+
+/// Delete the fake cursors that are at the same position as the real
+/// cursor or as another fake cursor.
+fn remove_duplicates(buffer: &mut Buffer) {
+    let mut seen = vec![buffer.cursor()];
+    let cursors = std::mem::take(&mut buffer.fake_cursors);
+    for cursor in cursors {
+        match buffer.markers.get(cursor.point) {
+            Some(pos) if !seen.contains(&pos) => {
+                seen.push(pos);
+                buffer.fake_cursors.push(cursor);
+            }
+            _ => remove_markers(buffer, &cursor),
+        }
+    }
+}
+
+/// Delete the fake cursors of the current buffer.
+pub fn remove_fake_cursors(context: &mut Context) {
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    for cursor in std::mem::take(&mut buffer.fake_cursors) {
+        remove_markers(buffer, &cursor);
+    }
+}
+
+/// Run `command` at each fake cursor of the current buffer, after it
+/// ran at the real cursor. Failures at fake cursors are ignored.
+pub fn run_for_fake_cursors<F>(context: &mut Context, mut command: F)
+where
+    F: FnMut(&mut Context) -> Result,
+{
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    if buffer.fake_cursors.is_empty() {
+        return;
+    }
+    let cursors = std::mem::take(&mut buffer.fake_cursors);
+
+    // The real cursor is saved as a fake one while the others edit
+    // the buffer.
+    let real = FakeCursor::save(context, buffer_ref);
+    let preserve_goal_column = context.goal_column.to_preserve;
+    let mut done = vec![];
+    for cursor in cursors {
+        cursor.restore(context, buffer_ref);
+        context.kill_ring.to_preserve = false;
+        context.goal_column.to_preserve = false;
+        let _ = command(context);
+        done.push(FakeCursor::save(context, buffer_ref));
+    }
+    real.restore(context, buffer_ref);
+    context.goal_column.to_preserve = preserve_goal_column;
+
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.fake_cursors = done;
+    remove_duplicates(buffer);
+}
+
+/// Return the text to mark more of, with the offsets of point and of
+/// the mark from its start: the region if it is not empty, else the
+/// word at point.
+fn text_like_this(buffer: &Buffer) -> Option<(String, usize, Option<usize>)> {
+    let (start, end) = match buffer.region().filter(|(start, end)| start != end) {
+        Some(region) => region,
+        None => motion::word_at(buffer, buffer.cursor())?,
+    };
+    let offset = |pos| buffer.byte_offset(pos) - buffer.byte_offset(start);
+    let text = buffer.region_text(start, end);
+    let mark = buffer
+        .mark()
+        .filter(|_| buffer.region() == Some((start, end)));
+    Some((text, offset(buffer.cursor()), mark.map(offset)))
+}
+
+/// Return the position at the byte `offset` of `text`.
+fn position_at(text: &str, offset: usize) -> Cursor {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let column = before
+        .rfind('\n')
+        .map_or(offset, |newline| offset - newline - 1);
+    Cursor { line, column }
+}
+
+/// Add a fake cursor at the next or previous occurrence of the region
+/// or of the word at point, after the last cursor or before the first.
+fn mark_like_this(context: &mut Context, forward: bool) -> Result {
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let buffer = context.buffer_list.resolve_ref(buffer_ref);
+    let (text, point_offset, mark_offset) = match text_like_this(buffer) {
+        Some(text) => text,
+        None => {
+            message(context, "No word or region at point");
+            return Err(());
+        }
+    };
+
+    let mut points = fake_cursor_positions(buffer);
+    points.push(buffer.cursor());
+    let contents = buffer.to_string();
+    // Fake cursors are not necessarily at the same offset in a match
+    // as point, so the bounds may fall anywhere in the buffer.
+    let found = if forward {
+        let last = points.iter().max().unwrap();
+        let from = (buffer.byte_offset(*last) + text.len()).saturating_sub(point_offset);
+        contents
+            .match_indices(&text)
+            .map(|(start, _)| start)
+            .find(|start| *start >= from)
+    } else {
+        let first = points.iter().min().unwrap();
+        let to = buffer.byte_offset(*first).checked_sub(point_offset);
+        contents
+            .rmatch_indices(&text)
+            .map(|(start, _)| start)
+            .find(|start| to.is_some_and(|to| start + text.len() <= to))
+    };
+    let start = match found {
+        Some(start) => start,
+        None => {
+            message(context, "No more matches found");
+            return Err(());
+        }
+    };
+
+    let point = position_at(&contents, start + point_offset);
+    let mark = mark_offset.map(|offset| position_at(&contents, start + offset));
+    add_fake_cursor(context, buffer_ref, point, mark);
+    Ok(())
+}
+
+/// Add a cursor at the next occurrence of the region, or of the word
+/// at point.
+pub fn mark_next_like_this(context: &mut Context, _term: &mut Term) -> Result {
+    mark_like_this(context, true)
+}
+
+/// Add a cursor at the previous occurrence of the region, or of the
+/// word at point.
+pub fn mark_previous_like_this(context: &mut Context, _term: &mut Term) -> Result {
+    mark_like_this(context, false)
+}
+
+/// Add a cursor on each line of the region, at the display column of
+/// point, and deactivate the mark.
+pub fn edit_lines(context: &mut Context, _term: &mut Term) -> Result {
+    let (start, end) = commands::region_or_message(context)?;
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    let cursor = buffer.cursor();
+    let mark = if cursor == start { end } else { start };
+    if mark.line == cursor.line {
+        message(context, "The region must span more than one line");
+        return Err(());
+    }
+    buffer.set_mark(None);
+
+    let column = buffer.display_column(cursor);
+    let lines = if mark.line < cursor.line {
+        mark.line..cursor.line
+    } else {
+        cursor.line + 1..mark.line + 1
+    };
+    let points: Vec<Cursor> = lines
+        .map(|line| Cursor {
+            line,
+            column: buffer.column_at_display(line, column),
+        })
+        .collect();
+    for point in points {
+        add_fake_cursor(context, buffer_ref, point, None);
+    }
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    remove_duplicates(buffer);
+    Ok(())
+}
+
+/// Add a fake cursor where the mouse was clicked, or delete the fake
+/// cursor that is there.
+pub fn add_cursor_on_click(context: &mut Context, term: &mut Term) -> Result {
+    let (window, pos) = match mouse::text_position_at_mouse(term, context) {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    if context.window_list.windows[window].buffer_ref != buffer_ref {
+        return Ok(());
+    }
+
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    if pos == buffer.cursor() {
+        return Ok(());
+    }
+    let clicked = buffer
+        .fake_cursors
+        .iter()
+        .position(|cursor| buffer.markers.get(cursor.point) == Some(pos));
+    match clicked {
+        Some(index) => {
+            let cursor = buffer.fake_cursors.remove(index);
+            remove_markers(buffer, &cursor);
+        }
+        None => add_fake_cursor(context, buffer_ref, pos, None),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::insert_char;
+    use crate::kill_ring::Direction;
+
+    fn main_buffer(context: &mut Context) -> &mut Buffer {
+        context
+            .buffer_list
+            .resolve_ref_as_mut(BufferRef::main_window())
+    }
+
+    #[test]
+    fn mark_words_like_this_and_type_at_each() {
+        let mut context = Context::new(Buffer::from_string("foo bar\nfoo\nbaz foo"));
+        main_buffer(&mut context).set_cursor(Cursor::new(1, 1));
+        mark_like_this(&mut context, true).unwrap();
+        assert!(mark_like_this(&mut context, true).is_err());
+        mark_like_this(&mut context, false).unwrap();
+        assert!(mark_like_this(&mut context, false).is_err());
+        let buffer = main_buffer(&mut context);
+        assert_eq!(
+            fake_cursor_positions(buffer),
+            vec![Cursor::new(2, 5), Cursor::new(0, 1)]
+        );
+
+        insert_char(&mut context, '-').unwrap();
+        run_for_fake_cursors(&mut context, |context| insert_char(context, '-'));
+        let buffer = main_buffer(&mut context);
+        assert_eq!(buffer.to_string(), "f-oo bar\nf-oo\nbaz f-oo");
+        assert_eq!(buffer.cursor(), Cursor::new(1, 2));
+        assert_eq!(
+            fake_cursor_positions(buffer),
+            vec![Cursor::new(2, 6), Cursor::new(0, 2)]
+        );
+    }
+
+    #[test]
+    fn mark_like_this_past_fake_cursors_at_the_ends() {
+        let mut context = Context::new(Buffer::from_string("foo bar"));
+        main_buffer(&mut context).set_cursor(Cursor::new(0, 1));
+        add_fake_cursor(
+            &mut context,
+            BufferRef::main_window(),
+            Cursor::new(0, 7),
+            None,
+        );
+        assert!(mark_like_this(&mut context, true).is_err());
+
+        let mut context = Context::new(Buffer::from_string("x foo"));
+        main_buffer(&mut context).set_cursor(Cursor::new(0, 5));
+        add_fake_cursor(
+            &mut context,
+            BufferRef::main_window(),
+            Cursor::new(0, 0),
+            None,
+        );
+        assert!(mark_like_this(&mut context, false).is_err());
+        assert_eq!(
+            fake_cursor_positions(main_buffer(&mut context)),
+            vec![Cursor::new(0, 0)]
+        );
+    }
+
+    #[test]
+    fn keep_a_kill_ring_per_cursor() {
+        let mut context = Context::new(Buffer::from_string("one\ntwo\nthree"));
+        main_buffer(&mut context).set_cursor(Cursor::new(2, 0));
+        add_fake_cursor(
+            &mut context,
+            BufferRef::main_window(),
+            Cursor::new(0, 0),
+            None,
+        );
+        add_fake_cursor(
+            &mut context,
+            BufferRef::main_window(),
+            Cursor::new(1, 0),
+            None,
+        );
+
+        let mut kill_line = |context: &mut Context| {
+            let buffer = main_buffer(context);
+            let line = buffer.cursor().line;
+            let end = Cursor::new(line, buffer.get_line_unchecked(line).len());
+            let text = buffer.delete_region(buffer.cursor(), end);
+            context.kill_ring.kill(text, Direction::Forward);
+            Ok(())
+        };
+        kill_line(&mut context).unwrap();
+        run_for_fake_cursors(&mut context, &mut kill_line);
+        assert_eq!(context.kill_ring.current(), Some("three"));
+        let buffer = main_buffer(&mut context);
+        assert_eq!(buffer.to_string(), "\n\n");
+        let kills: Vec<_> = buffer
+            .fake_cursors
+            .iter()
+            .map(|cursor| cursor.kill_ring.current().unwrap())
+            .collect();
+        assert_eq!(kills, vec!["one", "two"]);
+    }
+}
@@ -0,0 +1,162 @@
+//! Narrowing
+//!
+//! Narrowing restricts the accessible portion of a buffer to a part of
+//! its text. The rest is hidden, motion stops at the edges of the
+//! accessible portion and edits can't reach past them, until the
+//! buffer is widened again.
+//!
+
+use crate::commands::{self, Result};
+use crate::sexp;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Buffer, Context, Cursor};
+
+fn current_buffer(context: &mut Context) -> &mut Buffer {
+    let window = context.window_list.get_current_window();
+    context.buffer_list.resolve_ref_as_mut(window.buffer_ref)
+}
+
+/// Return the start and end of the top-level definition around `pos`:
+/// the outermost block between braces, from the line that starts it
+/// to the line after the closing brace. The lines just before the
+/// block, like its signature or documentation, are included.
+fn defun_at(buffer: &Buffer, pos: Cursor) -> Option<(Cursor, Cursor)> {
+    let (open, close) = sexp::brace_blocks(buffer)
+        .into_iter()
+        .find(|(open, close)| *open <= pos && pos <= *close)?;
+
+    let mut first = open.line;
+    while first > 0 {
+        let previous = buffer.get_line_unchecked(first - 1).trim_end();
+        if previous.is_empty() || previous.ends_with('}') || previous.ends_with(';') {
+            break;
+        }
+        first -= 1;
+    }
+    let end = if close.line + 1 < buffer.lines_count() {
+        Cursor {
+            line: close.line + 1,
+            column: 0,
+        }
+    } else {
+        buffer.end_position()
+    };
+    Some((
+        Cursor {
+            line: first,
+            column: 0,
+        },
+        end,
+    ))
+}
+
+/// Restrict editing in the buffer to the region.
+pub fn narrow_to_region(context: &mut Context, _term: &mut Term) -> Result {
+    let (start, end) = commands::region_or_message(context)?;
+    let buffer = current_buffer(context);
+    buffer.narrow(start, end);
+    buffer.set_mark(None);
+    Ok(())
+}
+
+/// Restrict editing in the buffer to the definition around point.
+pub fn narrow_to_defun(context: &mut Context, _term: &mut Term) -> Result {
+    let buffer = current_buffer(context);
+    match defun_at(buffer, buffer.cursor()) {
+        Some((start, end)) => {
+            buffer.narrow(start, end);
+            Ok(())
+        }
+        None => {
+            message(context, "No definition at point");
+            Err(())
+        }
+    }
+}
+
+/// Remove the restrictions of narrowing from the buffer.
+pub fn widen(context: &mut Context, _term: &mut Term) -> Result {
+    current_buffer(context).widen();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_list::BufferRef;
+    use crate::mode::MajorMode;
+
+    #[test]
+    fn restrict_the_accessible_portion() {
+        let mut buffer = Buffer::from_string("zero\none two\nthree\nfour");
+        buffer.narrow(Cursor::new(1, 4), Cursor::new(2, 5));
+        assert!(buffer.is_line_hidden(0));
+        assert!(!buffer.is_line_hidden(1));
+        assert!(buffer.is_line_hidden(3));
+        assert_eq!(buffer.display_column(Cursor::new(1, 7)), 3);
+        assert_eq!(buffer.char_before(Cursor::new(1, 4)), None);
+        assert_eq!(buffer.char_after(Cursor::new(2, 5)), None);
+
+        buffer.insert_at(Cursor::new(2, 5), "!");
+        buffer.insert_at(Cursor::new(1, 4), "(");
+        assert_eq!(buffer.point_min(), Cursor::new(1, 4));
+        assert_eq!(buffer.point_max(), Cursor::new(2, 6));
+
+        buffer.set_mark(Some(Cursor::new(0, 0)));
+        buffer.set_cursor(Cursor::new(2, 6));
+        let (start, end) = buffer.region().unwrap();
+        assert_eq!((start, end), (Cursor::new(1, 4), Cursor::new(2, 6)));
+        buffer.delete_region(start, end);
+        assert_eq!(buffer.to_string(), "zero\none \nfour");
+
+        // The text around it can still be edited, as when the buffer
+        // is trimmed.
+        buffer.remove_line(0);
+        assert_eq!(buffer.to_string(), "one \nfour");
+        assert_eq!(buffer.point_min(), Cursor::new(0, 4));
+        buffer.widen();
+        assert_eq!(buffer.point_max(), Cursor::new(1, 4));
+    }
+
+    #[test]
+    fn keep_char_commands_in_the_accessible_portion() {
+        let mut context = Context::new(Buffer::from_string("abcdef"));
+        let term = &mut Term::new();
+        let main = BufferRef::main_window();
+        let buffer = context.buffer_list.resolve_ref_as_mut(main);
+        buffer.narrow(Cursor::new(0, 1), Cursor::new(0, 3));
+        buffer.set_cursor(Cursor::new(0, 3));
+
+        assert!(commands::delete_char(&mut context, term).is_err());
+        assert!(commands::forward_char(&mut context, term).is_err());
+        let buffer = context.buffer_list.resolve_ref_as_mut(main);
+        assert_eq!(buffer.to_string(), "abcdef");
+        assert_eq!(buffer.cursor(), Cursor::new(0, 3));
+
+        buffer.set_cursor(Cursor::new(0, 1));
+        assert!(commands::backward_char(&mut context, term).is_err());
+        assert!(commands::delete_backward_char(&mut context, term).is_ok());
+        commands::delete_char(&mut context, term).unwrap();
+        let buffer = context.buffer_list.resolve_ref(main);
+        assert_eq!(buffer.to_string(), "acdef");
+        assert_eq!(buffer.cursor(), Cursor::new(0, 1));
+    }
+
+    #[test]
+    fn find_definitions() {
+        let mut buffer = Buffer::from_string(
+            "use x;\n/// Doc.\nfn a(\n    b: u8,\n) {\n    if b {}\n}\n\nfn c() {}",
+        );
+        buffer.mode = MajorMode::rust();
+        assert_eq!(
+            defun_at(&buffer, Cursor::new(5, 8)),
+            Some((Cursor::new(1, 0), Cursor::new(7, 0)))
+        );
+        assert_eq!(
+            defun_at(&buffer, Cursor::new(8, 8)),
+            Some((Cursor::new(8, 0), Cursor::new(8, 9)))
+        );
+        assert_eq!(defun_at(&buffer, Cursor::new(7, 0)), None);
+    }
+}
@@ -0,0 +1,508 @@
+//! Overlays
+//!
+//! An overlay is a range of a buffer with properties that change how
+//! its text is displayed, like a face or strings shown around it, or
+//! how it behaves, like a keymap. The ends of an overlay move with the
+//! text when text is inserted or deleted before them, so search
+//! matches, matching delimiters or diagnostics stay on their text.
+//!
+
+use std::cmp;
+
+use crate::face::Face;
+use crate::marker::{MarkerId, MarkerList};
+use crate::{Cursor, Keymap};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OverlayId(u64);
+
+#[derive(Clone)]
+pub struct Overlay {
+    id: OverlayId,
+    /// The ends of the overlay, which follow the markers at them once
+    /// it is added to a buffer.
+    pub start: Cursor,
+    pub end: Cursor,
+    markers: Option<(MarkerId, MarkerId)>,
+
+    /// The subsystem that created the overlay, to remove its overlays
+    /// together.
+    pub source: &'static str,
+    /// If set, text inserted at the start is outside of the overlay.
+    pub front_advance: bool,
+    /// If set, text inserted at the end is inside of the overlay.
+    pub rear_advance: bool,
+    /// If set, the overlay is deleted when its text is deleted.
+    pub evaporate: bool,
+
+    pub face: Option<Face>,
+    /// When overlays overlap, the properties of the one with the
+    /// highest priority are used.
+    pub priority: i32,
+    /// If set, the text is not displayed.
+    pub invisible: bool,
+    /// Strings displayed before and after the text.
+    pub before_string: Option<String>,
+    pub after_string: Option<String>,
+    /// A keymap that takes precedence over the keymap of the buffer
+    /// when point is in the overlay.
+    pub keymap: Option<Keymap>,
+}
+
+impl Overlay {
+    pub fn new(source: &'static str, start: Cursor, end: Cursor) -> Overlay {
+        Overlay {
+            id: OverlayId(0),
+            start,
+            end,
+            markers: None,
+            source,
+            front_advance: false,
+            rear_advance: false,
+            evaporate: false,
+            face: None,
+            priority: 0,
+            invisible: false,
+            before_string: None,
+            after_string: None,
+            keymap: None,
+        }
+    }
+
+    pub fn id(&self) -> OverlayId {
+        self.id
+    }
+
+    /// Check if `pos` is in the overlay. The end is not.
+    pub fn contains(&self, pos: Cursor) -> bool {
+        self.start <= pos && pos < self.end
+    }
+}
+
+/// The overlays of a buffer.
+///
+/// The ends of the overlays are markers of the buffer, and their
+/// positions are copied to the overlays after each edit. The overlays
+/// are sorted by their start, and `max_ends` holds the furthest end of
+/// the overlays up to each of them, so the overlays around a position
+/// are found without going through all of them.
+pub struct OverlayList {
+    overlays: Vec<Overlay>,
+    max_ends: Vec<Cursor>,
+    next_id: u64,
+}
+
+impl OverlayList {
+    pub fn new() -> OverlayList {
+        OverlayList {
+            overlays: vec![],
+            max_ends: vec![],
+            next_id: 1,
+        }
+    }
+
+    /// Add `overlay`, with markers at its ends in `markers`, and
+    /// return its id.
+    pub fn add(&mut self, markers: &mut MarkerList, mut overlay: Overlay) -> OverlayId {
+        overlay.id = OverlayId(self.next_id);
+        self.next_id += 1;
+        if overlay.end < overlay.start {
+            std::mem::swap(&mut overlay.start, &mut overlay.end);
+        }
+        overlay.markers = Some((
+            markers.add(overlay.start, overlay.front_advance),
+            markers.add(overlay.end, overlay.rear_advance),
+        ));
+        let id = overlay.id;
+        let index = self.overlays.partition_point(|o| o.start <= overlay.start);
+        self.overlays.insert(index, overlay);
+        self.update_max_ends(index);
+        id
+    }
+
+    pub fn get(&self, id: OverlayId) -> Option<&Overlay> {
+        self.overlays.iter().find(|overlay| overlay.id == id)
+    }
+
+    /// Change the properties of the overlay `id` with `f`. Its ends
+    /// can be moved.
+    pub fn update(
+        &mut self,
+        markers: &mut MarkerList,
+        id: OverlayId,
+        f: impl FnOnce(&mut Overlay),
+    ) -> bool {
+        let overlay = match self.overlays.iter_mut().find(|overlay| overlay.id == id) {
+            Some(overlay) => overlay,
+            None => return false,
+        };
+        f(overlay);
+        if overlay.end < overlay.start {
+            std::mem::swap(&mut overlay.start, &mut overlay.end);
+        }
+        if let Some((start, end)) = overlay.markers {
+            markers.remove(start);
+            markers.remove(end);
+        }
+        overlay.markers = Some((
+            markers.add(overlay.start, overlay.front_advance),
+            markers.add(overlay.end, overlay.rear_advance),
+        ));
+        self.sort_from(0);
+        true
+    }
+
+    /// Delete the overlay `id` and return it.
+    pub fn remove(&mut self, markers: &mut MarkerList, id: OverlayId) -> Option<Overlay> {
+        let index = self.overlays.iter().position(|overlay| overlay.id == id)?;
+        let overlay = self.overlays.remove(index);
+        remove_markers(markers, &overlay);
+        self.update_max_ends(index);
+        Some(overlay)
+    }
+
+    /// Delete all the overlays of `source`.
+    pub fn clear(&mut self, markers: &mut MarkerList, source: &str) {
+        self.overlays.retain(|overlay| {
+            let keep = overlay.source != source;
+            if !keep {
+                remove_markers(markers, overlay);
+            }
+            keep
+        });
+        self.update_max_ends(0);
+    }
+
+    pub fn clear_all(&mut self, markers: &mut MarkerList) {
+        for overlay in &self.overlays {
+            remove_markers(markers, overlay);
+        }
+        self.overlays.clear();
+        self.max_ends.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overlays.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Overlay> {
+        self.overlays.iter()
+    }
+
+    /// Return the overlays that contain `pos`, by decreasing priority.
+    pub fn at(&self, pos: Cursor) -> Vec<&Overlay> {
+        let mut overlays: Vec<&Overlay> = self
+            .in_range(pos, pos)
+            .filter(|o| o.contains(pos))
+            .collect();
+        overlays.sort_by_key(|overlay| cmp::Reverse(overlay.priority));
+        overlays
+    }
+
+    /// Return the overlays that overlap or touch the text from `start`
+    /// to `end`.
+    pub fn in_range(&self, start: Cursor, end: Cursor) -> impl Iterator<Item = &Overlay> {
+        // The overlays before `first` all end before `start`, and the
+        // ones from `last` start after `end`.
+        let first = self.max_ends.partition_point(|max_end| *max_end < start);
+        let last = self
+            .overlays
+            .partition_point(|overlay| overlay.start <= end);
+        self.overlays[first..cmp::max(first, last)]
+            .iter()
+            .filter(move |overlay| overlay.end >= start)
+    }
+
+    /// Sort the overlays from `index` after their ends moved, the
+    /// ones before it being before all of them.
+    fn sort_from(&mut self, index: usize) {
+        // The overlays are still almost sorted, which the sort is
+        // fast for.
+        self.overlays[index..].sort_by_key(|overlay| overlay.start);
+        self.update_max_ends(index);
+    }
+
+    /// Compute `max_ends` again from the overlay at `index`.
+    fn update_max_ends(&mut self, index: usize) {
+        self.max_ends.truncate(index);
+        for i in index..self.overlays.len() {
+            let end = self.overlays[i].end;
+            let max_end = self
+                .max_ends
+                .last()
+                .map_or(end, |max_end| cmp::max(*max_end, end));
+            self.max_ends.push(max_end);
+        }
+    }
+
+    /// Move the overlays to their markers after the text changed at
+    /// `pos`.
+    pub fn follow_markers(&mut self, markers: &mut MarkerList, pos: Cursor) {
+        // The overlays that end before `pos` didn't move.
+        let first = self.max_ends.partition_point(|max_end| *max_end < pos);
+        for overlay in &mut self.overlays[first..] {
+            let (start, end) = match overlay.markers {
+                Some(ids) => ids,
+                None => continue,
+            };
+            overlay.start = markers.get(start).unwrap_or(overlay.start);
+            overlay.end = markers.get(end).unwrap_or(overlay.end);
+            // An empty overlay that advances at the start but not at
+            // the end would end before its start.
+            if overlay.end < overlay.start {
+                overlay.end = overlay.start;
+                markers.set(end, overlay.end);
+            }
+        }
+        self.sort_from(first);
+    }
+
+    /// Delete the overlays that evaporate and became empty after the
+    /// text at `pos` was deleted.
+    pub fn evaporate(&mut self, markers: &mut MarkerList, pos: Cursor) {
+        let first = self.max_ends.partition_point(|max_end| *max_end < pos);
+        let mut moved = self.overlays.split_off(first);
+        moved.retain(|overlay| {
+            let keep = !overlay.evaporate || overlay.start != overlay.end;
+            if !keep {
+                remove_markers(markers, overlay);
+            }
+            keep
+        });
+        self.overlays.append(&mut moved);
+        self.update_max_ends(first);
+    }
+}
+
+/// Delete the markers at the ends of `overlay`.
+fn remove_markers(markers: &mut MarkerList, overlay: &Overlay) {
+    if let Some((start, end)) = overlay.markers {
+        markers.remove(start);
+        markers.remove(end);
+    }
+}
+
+/// A piece of a line as it is displayed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Segment<'a> {
+    /// Text of the line, as a range of bytes.
+    Text { start: usize, end: usize },
+    /// A string of an overlay, shown at the byte `column`.
+    String {
+        column: usize,
+        text: &'a str,
+        face: Option<Face>,
+    },
+}
+
+/// Split the line `line`, whose text is `text`, into the segments to
+/// display, leaving out the invisible text and adding the strings of
+/// `overlays`.
+pub fn line_segments<'a>(overlays: &'a OverlayList, line: usize, text: &str) -> Vec<Segment<'a>> {
+    let line_start = Cursor { line, column: 0 };
+    let line_end = Cursor {
+        line,
+        column: text.len(),
+    };
+    let column_in_line = |pos: Cursor| {
+        if pos.line < line {
+            0
+        } else if pos.line > line {
+            text.len()
+        } else {
+            pos.column
+        }
+    };
+
+    let mut hidden = vec![];
+    // Strings, with their position and whether they are after strings,
+    // which come before the before strings at the same position.
+    let mut strings = vec![];
+    for overlay in overlays.in_range(line_start, line_end) {
+        if overlay.invisible && overlay.start < overlay.end {
+            hidden.push(column_in_line(overlay.start)..column_in_line(overlay.end));
+        }
+        let face = overlay.face;
+        if let Some(after) = overlay.after_string.as_deref() {
+            if overlay.end.line == line {
+                strings.push((overlay.end.column, 0, overlay.priority, after, face));
+            }
+        }
+        if let Some(before) = overlay.before_string.as_deref() {
+            if overlay.start.line == line {
+                strings.push((overlay.start.column, 1, overlay.priority, before, face));
+            }
+        }
+    }
+    strings
+        .sort_by_key(|(column, order, priority, _, _)| (*column, *order, cmp::Reverse(*priority)));
+
+    let mut cuts: Vec<usize> = vec![0, text.len()];
+    cuts.extend(hidden.iter().flat_map(|range| vec![range.start, range.end]));
+    cuts.extend(strings.iter().map(|(column, ..)| *column));
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let is_hidden = |column: usize| hidden.iter().any(|range| range.contains(&column));
+    let mut segments = vec![];
+    let mut strings = strings.into_iter().peekable();
+    for (i, start) in cuts.iter().enumerate() {
+        while let Some((column, _, _, text, face)) = strings.next_if(|s| s.0 == *start) {
+            if !hidden
+                .iter()
+                .any(|range| range.start < column && column < range.end)
+            {
+                segments.push(Segment::String { column, text, face });
+            }
+        }
+        if let Some(end) = cuts.get(i + 1) {
+            if !is_hidden(*start) {
+                segments.push(Segment::Text {
+                    start: *start,
+                    end: *end,
+                });
+            }
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn move_overlays_with_the_text() {
+        let mut buffer = Buffer::from_string("hello world\nbye");
+        let id = buffer.add_overlay(Overlay::new("test", Cursor::new(0, 6), Cursor::new(0, 11)));
+
+        buffer.insert_at(Cursor::new(0, 0), "oh\n");
+        let overlay = buffer.overlays.get(id).unwrap();
+        assert_eq!(
+            (overlay.start, overlay.end),
+            (Cursor::new(1, 6), Cursor::new(1, 11))
+        );
+
+        // Text inserted at the start goes in the overlay by default,
+        // and text inserted at the end does not.
+        buffer.insert_at(Cursor::new(1, 11), "!");
+        buffer.insert_at(Cursor::new(1, 6), "big ");
+        let overlay = buffer.overlays.get(id).unwrap();
+        assert_eq!(
+            (overlay.start, overlay.end),
+            (Cursor::new(1, 6), Cursor::new(1, 15))
+        );
+
+        buffer.delete_region(Cursor::new(0, 1), Cursor::new(1, 8));
+        let overlay = buffer.overlays.get(id).unwrap();
+        assert_eq!(
+            (overlay.start, overlay.end),
+            (Cursor::new(0, 1), Cursor::new(0, 8))
+        );
+
+        buffer.update_overlay(id, |overlay| overlay.evaporate = true);
+        buffer.delete_region(Cursor::new(0, 0), Cursor::new(0, 9));
+        assert!(buffer.overlays.get(id).is_none());
+    }
+
+    #[test]
+    fn find_overlays_around_positions() {
+        let mut buffer = Buffer::from_string("one\ntwo\nthree\nfour");
+        let long = buffer.add_overlay(Overlay::new("test", Cursor::new(0, 1), Cursor::new(2, 2)));
+        let short = buffer.add_overlay(Overlay::new("test", Cursor::new(1, 0), Cursor::new(1, 2)));
+        let last = buffer.add_overlay(Overlay::new("test", Cursor::new(3, 0), Cursor::new(3, 4)));
+        let ids = |start, end| -> Vec<OverlayId> {
+            buffer
+                .overlays
+                .in_range(start, end)
+                .map(|o| o.id())
+                .collect()
+        };
+        assert_eq!(ids(Cursor::new(0, 0), Cursor::new(0, 0)), vec![]);
+        assert_eq!(ids(Cursor::new(2, 0), Cursor::new(2, 5)), vec![long]);
+        assert_eq!(
+            ids(Cursor::new(1, 1), Cursor::new(3, 0)),
+            vec![long, short, last]
+        );
+
+        // The overlays stay sorted as they move.
+        buffer.insert_at(Cursor::new(0, 0), "x\n");
+        buffer.delete_region(Cursor::new(1, 0), Cursor::new(1, 2));
+        let starts: Vec<Cursor> = buffer.overlays.iter().map(|o| o.start).collect();
+        assert_eq!(
+            starts,
+            vec![Cursor::new(1, 0), Cursor::new(2, 0), Cursor::new(4, 0)]
+        );
+        let ids = |start, end| -> Vec<OverlayId> {
+            buffer
+                .overlays
+                .in_range(start, end)
+                .map(|o| o.id())
+                .collect()
+        };
+        assert_eq!(ids(Cursor::new(3, 1), Cursor::new(3, 1)), vec![long]);
+        assert_eq!(ids(Cursor::new(4, 4), Cursor::new(4, 4)), vec![last]);
+    }
+
+    #[test]
+    fn split_lines_into_segments() {
+        let mut markers = MarkerList::new();
+        let mut overlays = OverlayList::new();
+        let mut hidden = Overlay::new("test", Cursor::new(0, 2), Cursor::new(1, 1));
+        hidden.invisible = true;
+        hidden.before_string = Some("...".to_string());
+        overlays.add(&mut markers, hidden);
+        let mut mark = Overlay::new("test", Cursor::new(1, 3), Cursor::new(1, 3));
+        mark.after_string = Some("<".to_string());
+        overlays.add(&mut markers, mark);
+
+        assert_eq!(
+            line_segments(&overlays, 0, "abcd"),
+            vec![
+                Segment::Text { start: 0, end: 2 },
+                Segment::String {
+                    column: 2,
+                    text: "...",
+                    face: None
+                },
+            ]
+        );
+        assert_eq!(
+            line_segments(&overlays, 1, "efgh"),
+            vec![
+                Segment::Text { start: 1, end: 3 },
+                Segment::String {
+                    column: 3,
+                    text: "<",
+                    face: None
+                },
+                Segment::Text { start: 3, end: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn hide_lines_and_keep_point_out() {
+        let mut buffer = Buffer::from_string("head\nbody\nmore\ntail");
+        let mut fold = Overlay::new("test", Cursor::new(0, 4), Cursor::new(2, 4));
+        fold.invisible = true;
+        fold.before_string = Some("...".to_string());
+        buffer.add_overlay(fold);
+
+        assert!(!buffer.is_line_hidden(0));
+        assert!(buffer.is_line_hidden(1));
+        assert!(buffer.is_line_hidden(2));
+        assert!(!buffer.is_line_hidden(3));
+        assert_eq!(buffer.line_display_width(0), 7);
+        assert_eq!(buffer.display_position(Cursor::new(2, 4)), (0, 7));
+        assert_eq!(buffer.column_at_display(0, 5), 4);
+
+        buffer.set_cursor(Cursor::new(1, 2));
+        buffer.move_out_of_invisible(Cursor::new(0, 2));
+        assert_eq!(buffer.cursor(), Cursor::new(2, 4));
+        buffer.set_cursor(Cursor::new(2, 1));
+        buffer.move_out_of_invisible(Cursor::new(3, 1));
+        assert_eq!(buffer.cursor(), Cursor::new(0, 4));
+    }
+}
@@ -1,35 +1,46 @@
-use crate::event_loop::{self, event_loop, read_key};
-use crate::keymap::{CommandHandler, Item};
+use crate::command_table::Command;
+use crate::event_loop::{self, event_loop, read_key, EventLoopError};
+use crate::keymap::Item;
 use crate::term::Term;
-use crate::window::{message, refresh_screen};
-use crate::{Context, Key};
+use crate::window::refresh_screen;
+use crate::{Context, Cursor, Key};
 
 pub fn read_key_binding(
     term: &mut Term,
     context: &mut Context,
-) -> Result<CommandHandler, Vec<Key>> {
+) -> Result<&'static Command, Vec<Key>> {
     let mut read = vec![];
 
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
-    let mut keymap = buffer.keymap.clone();
+    let mut keymaps = buffer.active_keymaps();
 
     loop {
         if !read.is_empty() {
+            // Echo the prefix without logging it as a message.
             let keys = Key::format_seq(&read) + "-";
-            message(context, keys);
+            context.buffer_list.minibuffer.set(keys);
             refresh_screen(term, context).unwrap();
         }
 
         let k = read_key(term, context);
-        let item = keymap.lookup(&k);
+        let items: Vec<Item> = keymaps.iter().filter_map(|km| km.lookup(&k)).collect();
 
         read.push(k);
+        context.event_loop.this_command_keys = read.clone();
 
-        match item {
-            Some(Item::Command(cmd)) => break Ok(cmd),
-            Some(Item::Keymap(km)) => {
-                keymap = km;
+        // The first keymap that binds the key wins. A prefix key
+        // continues in the prefix keymaps until a command.
+        match items.first() {
+            Some(Item::Command(cmd)) => break Ok(*cmd),
+            Some(Item::Keymap(_)) => {
+                keymaps = items
+                    .into_iter()
+                    .map_while(|item| match item {
+                        Item::Keymap(km) => Some(km),
+                        Item::Command(_) => None,
+                    })
+                    .collect();
             }
             None => break Err(read),
         }
@@ -52,11 +63,80 @@ where
     let window = context.window_list.get_current_window();
     let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
 
-    buffer.cursor.line = 0;
-    buffer.cursor.column = prompt.len();
+    buffer.set_cursor(Cursor {
+        line: 0,
+        column: prompt.len(),
+    });
 
-    let result = event_loop(term, context, callback, exit_on_undefined)
-        .map(|_| context.buffer_list.minibuffer.to_string());
+    let result = event_loop(term, context, callback, exit_on_undefined).map(|_| {
+        let input = context.buffer_list.minibuffer.to_string();
+        input
+            .strip_prefix(prompt)
+            .map(String::from)
+            .unwrap_or(input)
+    });
+
+    context.buffer_list.minibuffer.truncate();
+    context.window_list.minibuffer_focused = false;
+
+    result
+}
+
+/// Ask a question in the minibuffer and read a single key for the
+/// answer, until it is y or n. C-g quits.
+pub fn read_y_or_n(
+    term: &mut Term,
+    context: &mut Context,
+    prompt: &str,
+) -> event_loop::Result<bool> {
+    let prompt = format!("{}(y or n) ", prompt);
+    let mut text = prompt.clone();
+    context.window_list.minibuffer_focused = true;
+
+    let result = loop {
+        let minibuffer = &mut context.buffer_list.minibuffer;
+        minibuffer.set(&text);
+        minibuffer.set_cursor(Cursor {
+            line: 0,
+            column: text.len(),
+        });
+
+        let key = read_key(term, context);
+        match key.as_char() {
+            Some('y') => break Ok(true),
+            Some('n') => break Ok(false),
+            _ if key == Key::parse_unchecked("C-g") => break Err(EventLoopError::Quit),
+            _ => text = format!("Please answer y or n.  {}", prompt),
+        }
+    };
+
+    context.buffer_list.minibuffer.truncate();
+    context.window_list.minibuffer_focused = false;
+
+    result
+}
+
+/// Read a single character in the minibuffer, after `prompt`. Keys
+/// that are not characters are ignored. C-g quits.
+pub fn read_char(term: &mut Term, context: &mut Context, prompt: &str) -> event_loop::Result<char> {
+    context.window_list.minibuffer_focused = true;
+
+    let result = loop {
+        let minibuffer = &mut context.buffer_list.minibuffer;
+        minibuffer.set(prompt);
+        minibuffer.set_cursor(Cursor {
+            line: 0,
+            column: prompt.len(),
+        });
+
+        let key = read_key(term, context);
+        if key == Key::parse_unchecked("C-g") {
+            break Err(EventLoopError::Quit);
+        }
+        if let Some(ch) = key.as_char() {
+            break Ok(ch);
+        }
+    };
 
     context.buffer_list.minibuffer.truncate();
     context.window_list.minibuffer_focused = false;
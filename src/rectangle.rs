@@ -0,0 +1,390 @@
+//! Rectangles
+//!
+//! A rectangle is the text between two display columns on a range of
+//! lines. The region defines a rectangle with the mark and point at
+//! two opposite corners. Tabs that cross the edges of a rectangle are
+//! converted to spaces before it is edited.
+//!
+
+use std::cmp;
+
+use crate::buffer::{display_width, next_display_column};
+use crate::commands::{self, Result};
+use crate::face;
+use crate::mark_ring;
+use crate::overlay::Overlay;
+use crate::read;
+use crate::term::Term;
+use crate::window::message;
+use crate::{Buffer, Context, Cursor};
+
+/// The lines and display columns of a rectangle. The right column is
+/// not part of it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rectangle {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Rectangle {
+    /// Return the rectangle with corners at `a` and `b`.
+    pub fn from_corners(buffer: &Buffer, a: Cursor, b: Cursor) -> Rectangle {
+        let a_column = buffer.display_column(a);
+        let b_column = buffer.display_column(b);
+        Rectangle {
+            top: cmp::min(a.line, b.line),
+            bottom: cmp::max(a.line, b.line),
+            left: cmp::min(a_column, b_column),
+            right: cmp::max(a_column, b_column),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.right - self.left
+    }
+
+    pub fn lines(&self) -> std::ops::RangeInclusive<usize> {
+        self.top..=self.bottom
+    }
+}
+
+pub struct RectangleState {
+    /// The last rectangle killed or copied, one string per line.
+    pub killed: Vec<String>,
+    /// If set, the region is shown as a rectangle.
+    pub mark_mode: bool,
+}
+
+impl RectangleState {
+    pub fn new() -> RectangleState {
+        RectangleState {
+            killed: vec![],
+            mark_mode: false,
+        }
+    }
+}
+
+/// Return the byte column of `line` at the display column `column`,
+/// converting a tab that crosses it to spaces. If a string of an
+/// overlay covers the column, return the column where it is shown.
+/// Return None if the line is shorter.
+fn split_at_column(buffer: &mut Buffer, line: usize, column: usize) -> Option<usize> {
+    let byte = buffer.column_at_display(line, column);
+    let pos = Cursor { line, column: byte };
+    let start = buffer.display_column(pos);
+    if start >= column {
+        return Some(byte);
+    }
+    if byte == buffer.get_line_unchecked(line).len() {
+        return None;
+    }
+    // A tab covers the column.
+    let end = next_display_column(start, '\t', buffer.tab_width);
+    let tab_end = Cursor {
+        line,
+        column: byte + 1,
+    };
+    buffer.delete_region(pos, tab_end);
+    buffer.insert_at(pos, &" ".repeat(end - start));
+    Some(byte + column - start)
+}
+
+/// Return the byte column of `line` at the display column `column`,
+/// adding spaces at the end of the line if it is shorter.
+fn pad_to_column(buffer: &mut Buffer, line: usize, column: usize) -> usize {
+    match split_at_column(buffer, line, column) {
+        Some(byte) => byte,
+        None => {
+            let width = buffer.line_display_width(line);
+            let end = Cursor {
+                line,
+                column: buffer.get_line_unchecked(line).len(),
+            };
+            buffer.insert_at(end, &" ".repeat(column - width)).column
+        }
+    }
+}
+
+/// Return the text of `rectangle`, one string per line. Short lines
+/// are padded with spaces.
+pub fn extract(buffer: &Buffer, rectangle: Rectangle) -> Vec<String> {
+    rectangle
+        .lines()
+        .map(|line| {
+            let mut text = String::new();
+            let mut column = 0;
+            for ch in buffer.get_line_unchecked(line).chars() {
+                let next = next_display_column(column, ch, buffer.tab_width);
+                if column >= rectangle.right {
+                    break;
+                }
+                if column >= rectangle.left && next <= rectangle.right {
+                    text.push(ch);
+                } else if next > rectangle.left {
+                    // A tab across an edge.
+                    let shown = cmp::min(next, rectangle.right) - cmp::max(column, rectangle.left);
+                    text.push_str(&" ".repeat(shown));
+                }
+                column = next;
+            }
+            let width = display_width(&text, buffer.tab_width);
+            text + &" ".repeat(rectangle.width().saturating_sub(width))
+        })
+        .collect()
+}
+
+/// Delete the text of `rectangle`.
+pub fn delete(buffer: &mut Buffer, rectangle: Rectangle) {
+    for line in rectangle.lines() {
+        let start = match split_at_column(buffer, line, rectangle.left) {
+            Some(start) => start,
+            None => continue,
+        };
+        let end = split_at_column(buffer, line, rectangle.right)
+            .unwrap_or_else(|| buffer.get_line_unchecked(line).len());
+        buffer.delete_region(
+            Cursor {
+                line,
+                column: start,
+            },
+            Cursor { line, column: end },
+        );
+    }
+}
+
+/// Insert `lines` as a rectangle with its upper left corner at `pos`,
+/// and return the position of its lower right corner. Lines are added
+/// at the end of the buffer if needed.
+pub fn insert(buffer: &mut Buffer, pos: Cursor, lines: &[String]) -> Cursor {
+    let column = buffer.display_column(pos);
+    let mut end = pos;
+    for (i, text) in lines.iter().enumerate() {
+        let line = pos.line + i;
+        if line == buffer.lines_count() {
+            buffer.insert_at(buffer.end_position(), "\n");
+        }
+        let byte = pad_to_column(buffer, line, column);
+        end = buffer.insert_at(Cursor { line, column: byte }, text);
+    }
+    end
+}
+
+/// Insert `text(i)` on the `i`-th line of `rectangle`, at its left
+/// edge. Short lines are padded.
+fn insert_on_lines(buffer: &mut Buffer, rectangle: Rectangle, text: impl Fn(usize) -> String) {
+    for (i, line) in rectangle.lines().enumerate() {
+        let byte = pad_to_column(buffer, line, rectangle.left);
+        buffer.insert_at(Cursor { line, column: byte }, &text(i));
+    }
+}
+
+/// Return the rectangle of the region of the current buffer, or
+/// report that there is no region.
+fn region_rectangle(context: &mut Context) -> std::result::Result<Rectangle, ()> {
+    let (start, end) = commands::region_or_message(context)?;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    Ok(Rectangle::from_corners(buffer, start, end))
+}
+
+/// Edit the rectangle of the region with `f`, then leave point at its
+/// upper left corner.
+fn edit_region_rectangle(context: &mut Context, f: impl FnOnce(&mut Buffer, Rectangle)) -> Result {
+    let rectangle = region_rectangle(context)?;
+    context.rectangle.mark_mode = false;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    f(buffer, rectangle);
+    let column = buffer.column_at_display(rectangle.top, rectangle.left);
+    buffer.set_cursor(Cursor {
+        line: rectangle.top,
+        column,
+    });
+    Ok(())
+}
+
+pub fn kill_rectangle(context: &mut Context, _term: &mut Term) -> Result {
+    let mut killed = vec![];
+    edit_region_rectangle(context, |buffer, rectangle| {
+        killed = extract(buffer, rectangle);
+        delete(buffer, rectangle);
+    })?;
+    context.rectangle.killed = killed;
+    Ok(())
+}
+
+pub fn copy_rectangle_as_kill(context: &mut Context, _term: &mut Term) -> Result {
+    let rectangle = region_rectangle(context)?;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    context.rectangle.killed = extract(buffer, rectangle);
+    context.rectangle.mark_mode = false;
+    message(context, "Rectangle copied");
+    Ok(())
+}
+
+pub fn delete_rectangle(context: &mut Context, _term: &mut Term) -> Result {
+    edit_region_rectangle(context, delete)
+}
+
+/// Insert the last killed rectangle with its upper left corner at
+/// point, and leave point at its lower right corner.
+pub fn yank_rectangle(context: &mut Context, _term: &mut Term) -> Result {
+    if context.rectangle.killed.is_empty() {
+        message(context, "No rectangle killed");
+        return Err(());
+    }
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    buffer.set_mark(Some(buffer.cursor()));
+    let end = insert(buffer, buffer.cursor(), &context.rectangle.killed);
+    buffer.set_cursor(end);
+    Ok(())
+}
+
+/// Insert spaces in the rectangle, shifting its text to the right.
+pub fn open_rectangle(context: &mut Context, _term: &mut Term) -> Result {
+    edit_region_rectangle(context, |buffer, rectangle| {
+        insert_on_lines(buffer, rectangle, |_| " ".repeat(rectangle.width()));
+    })
+}
+
+/// Replace the text of the rectangle with spaces.
+pub fn clear_rectangle(context: &mut Context, _term: &mut Term) -> Result {
+    edit_region_rectangle(context, |buffer, rectangle| {
+        for line in rectangle.lines() {
+            if buffer.line_display_width(line) <= rectangle.left {
+                continue;
+            }
+            let right = cmp::min(rectangle.right, buffer.line_display_width(line));
+            let one_line = Rectangle {
+                top: line,
+                bottom: line,
+                right,
+                ..rectangle
+            };
+            delete(buffer, one_line);
+            insert_on_lines(buffer, one_line, |_| " ".repeat(one_line.width()));
+        }
+    })
+}
+
+/// Replace the text of the rectangle with a string read in the
+/// minibuffer, on each line.
+pub fn string_rectangle(context: &mut Context, term: &mut Term) -> Result {
+    region_rectangle(context)?;
+    let string =
+        read::read_string(term, context, "String rectangle: ", |_, _| {}, false).map_err(|_| ())?;
+    edit_region_rectangle(context, |buffer, rectangle| {
+        delete(buffer, rectangle);
+        insert_on_lines(buffer, rectangle, |_| string.clone());
+    })
+}
+
+/// Insert numbers from 1 at the left edge of the rectangle, aligned
+/// to the right.
+pub fn rectangle_number_lines(context: &mut Context, _term: &mut Term) -> Result {
+    edit_region_rectangle(context, |buffer, rectangle| {
+        let count = rectangle.bottom - rectangle.top + 1;
+        let width = count.to_string().len();
+        insert_on_lines(buffer, rectangle, |i| format!("{:>1$} ", i + 1, width));
+    })
+}
+
+pub fn rectangle_mark_mode(context: &mut Context, _term: &mut Term) -> Result {
+    context.rectangle.mark_mode = !context.rectangle.mark_mode;
+    if context.rectangle.mark_mode {
+        // The rectangle starts at point.
+        let buffer_ref = context.window_list.get_current_window().buffer_ref;
+        let cursor = context.buffer_list.resolve_ref(buffer_ref).cursor();
+        mark_ring::push_mark(context, buffer_ref, cursor);
+        message(context, "Mark set (rectangle mode)");
+    }
+    Ok(())
+}
+
+/// Highlight the rectangle of the region of the current buffer, if
+/// `rectangle-mark-mode` is enabled.
+pub fn rectangle_mark_update(context: &mut Context) {
+    commands::clear_displayed_overlays(context, "rectangle");
+    if !context.rectangle.mark_mode {
+        return;
+    }
+
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    let rectangle = match buffer.mark() {
+        Some(mark) => Rectangle::from_corners(buffer, mark, buffer.cursor()),
+        None => return,
+    };
+    for line in rectangle.lines() {
+        let start = buffer.column_at_display(line, rectangle.left);
+        let end = buffer.column_at_display(line, rectangle.right);
+        if start == end {
+            continue;
+        }
+        let mut overlay = Overlay::new(
+            "rectangle",
+            Cursor {
+                line,
+                column: start,
+            },
+            Cursor { line, column: end },
+        );
+        overlay.face = Some(face::REGION);
+        buffer.add_overlay(overlay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kill_and_yank_rectangles() {
+        let mut buffer = Buffer::from_string("a = 1;\nbb = 22;\nc\n");
+        let rectangle = Rectangle {
+            top: 0,
+            bottom: 2,
+            left: 1,
+            right: 4,
+        };
+        assert_eq!(extract(&buffer, rectangle), vec![" = ", "b =", "   "]);
+
+        delete(&mut buffer, rectangle);
+        assert_eq!(buffer.to_string(), "a1;\nb 22;\nc\n");
+
+        let lines = vec!["xy".to_string(), "z".to_string()];
+        let end = insert(&mut buffer, Cursor::new(2, 1), &lines);
+        assert_eq!(buffer.to_string(), "a1;\nb 22;\ncxy\n z");
+        assert_eq!(end, Cursor::new(3, 2));
+    }
+
+    #[test]
+    fn split_tabs_across_the_edges() {
+        let mut buffer = Buffer::from_string("\tx\nabcdefghij");
+        buffer.tab_width = 4;
+        let rectangle = Rectangle::from_corners(&buffer, Cursor::new(1, 2), Cursor::new(0, 1));
+        assert_eq!(rectangle.left, 2);
+        assert_eq!(rectangle.right, 4);
+        assert_eq!(extract(&buffer, rectangle), vec!["  ", "cd"]);
+
+        insert_on_lines(&mut buffer, rectangle, |i| format!("{}", i + 1));
+        assert_eq!(buffer.to_string(), "  1  x\nab2cdefghij");
+    }
+
+    #[test]
+    fn insert_across_overlay_strings() {
+        let mut buffer = Buffer::from_string("hello\nab {\n  x\n}");
+        let mut overlay = Overlay::new("test", Cursor::new(1, 4), Cursor::new(2, 3));
+        overlay.invisible = true;
+        overlay.before_string = Some("...".to_string());
+        buffer.add_overlay(overlay);
+
+        let lines = vec!["1".to_string(), "2".to_string()];
+        insert(&mut buffer, Cursor::new(0, 5), &lines);
+        assert_eq!(buffer.to_string(), "hello1\nab {2\n  x\n}");
+    }
+}
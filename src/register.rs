@@ -0,0 +1,379 @@
+//! Registers
+//!
+//! Registers are named by a character and hold text, a position in a
+//! buffer, a number or a window configuration, to be used later.
+//! Positions are markers, so they follow the edits of their buffer.
+//!
+
+use std::collections::BTreeMap;
+
+use crate::buffer_list::BufferRef;
+use crate::commands::{self, Result};
+use crate::mark_ring::{self, Position};
+use crate::read;
+use crate::term::Term;
+use crate::window::{self, message};
+use crate::{Context, Cursor, Window};
+
+/// The layout of the windows, with the point of each window.
+pub struct WindowConfiguration {
+    windows: Vec<Window>,
+    selected: usize,
+}
+
+pub enum Register {
+    Text(String),
+    Position(Position),
+    Number(i64),
+    WindowConfiguration(WindowConfiguration),
+}
+
+impl Register {
+    /// Return the markers used by the register.
+    fn positions(&self) -> Vec<Position> {
+        match self {
+            Register::Position(position) => vec![*position],
+            Register::WindowConfiguration(configuration) => configuration
+                .windows
+                .iter()
+                .filter_map(|window| window.point)
+                .collect(),
+            Register::Text(_) | Register::Number(_) => vec![],
+        }
+    }
+}
+
+pub struct Registers {
+    registers: BTreeMap<char, Register>,
+}
+
+impl Registers {
+    pub fn new() -> Registers {
+        Registers {
+            registers: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name)
+    }
+}
+
+/// Store `register` in the register `name`, deleting the markers of
+/// the register it replaces.
+pub fn set_register(context: &mut Context, name: char, register: Register) {
+    if let Some(old) = context.registers.registers.insert(name, register) {
+        for (buffer_ref, id) in old.positions() {
+            context
+                .buffer_list
+                .resolve_ref_as_mut(buffer_ref)
+                .markers
+                .remove(id);
+        }
+    }
+}
+
+/// Add a marker at `pos` in the buffer `buffer_ref`.
+fn add_position(context: &mut Context, buffer_ref: BufferRef, pos: Cursor) -> Position {
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    (buffer_ref, buffer.markers.add(pos, false))
+}
+
+/// Return the current window configuration. The points of the
+/// windows are saved with new markers.
+fn current_configuration(context: &mut Context) -> WindowConfiguration {
+    let window_list = &context.window_list;
+    let selected = window_list.selected;
+    let mut windows = window_list.windows.clone();
+    for (i, window) in windows.iter_mut().enumerate() {
+        let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+        let pos = match window.point {
+            Some((buffer_ref, id)) if i != selected && buffer_ref == window.buffer_ref => {
+                buffer.markers.get(id).unwrap_or(buffer.cursor())
+            }
+            _ => buffer.cursor(),
+        };
+        window.point = Some((window.buffer_ref, buffer.markers.add(pos, false)));
+    }
+    WindowConfiguration { windows, selected }
+}
+
+/// Restore the window configuration `configuration`. The windows are
+/// given new markers, so that it can be restored again.
+fn restore_configuration(context: &mut Context, configuration: &WindowConfiguration) {
+    let windows = configuration.windows.clone();
+    let old_windows = std::mem::replace(&mut context.window_list.windows, windows);
+    for window in &old_windows {
+        window::delete_window_point(context, window);
+    }
+    context.window_list.selected = configuration.selected;
+    context.window_list.minibuffer_focused = false;
+
+    for i in 0..context.window_list.windows.len() {
+        let window = &mut context.window_list.windows[i];
+        let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+        let pos = window.point.and_then(|(_, id)| buffer.markers.get(id));
+        window.point = None;
+        if let Some(pos) = pos {
+            if i == configuration.selected {
+                buffer.set_cursor(pos);
+            } else {
+                window.point = Some((window.buffer_ref, buffer.markers.add(pos, false)));
+            }
+        }
+    }
+}
+
+/// Read the name of a register in the minibuffer.
+fn read_register(
+    context: &mut Context,
+    term: &mut Term,
+    prompt: &str,
+) -> std::result::Result<char, ()> {
+    read::read_char(term, context, prompt).map_err(|_| ())
+}
+
+/// Return a description of the register, on one line.
+fn describe_register(context: &Context, register: &Register) -> String {
+    match register {
+        Register::Text(text) => {
+            let first_line = text.lines().next().unwrap_or("");
+            if first_line.len() == text.len() {
+                format!("text: {}", text)
+            } else {
+                format!("text starting with: {}", first_line)
+            }
+        }
+        Register::Position((buffer_ref, id)) => {
+            let buffer = context.buffer_list.resolve_ref(*buffer_ref);
+            match buffer.markers.get(*id) {
+                Some(pos) => format!(
+                    "a buffer position: buffer {}, position {}",
+                    buffer.name,
+                    buffer.char_offset(pos) + 1
+                ),
+                None => "a deleted position".to_string(),
+            }
+        }
+        Register::Number(number) => format!("the number {}", number),
+        Register::WindowConfiguration(configuration) => format!(
+            "a window configuration of {} windows",
+            configuration.windows.len()
+        ),
+    }
+}
+
+/// Show `text` in the register list buffer, in another window.
+fn show_registers(context: &mut Context, text: &str) {
+    let buffer_ref = context.buffer_list.get_or_create("*Registers*");
+    let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+    buffer.read_only = false;
+    buffer.set(text.trim_end());
+    buffer.read_only = true;
+    buffer.modified = false;
+    let index = context.window_list.display_buffer(buffer_ref);
+    context.window_list.windows[index].scroll_line = 0;
+}
+
+pub fn copy_to_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Copy to register: ")?;
+    let (start, end) = commands::region_or_message(context)?;
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let text = buffer.region_text(start, end);
+    set_register(context, name, Register::Text(text));
+    Ok(())
+}
+
+pub fn insert_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Insert register: ")?;
+    let text = match context.registers.get(name) {
+        Some(Register::Text(text)) => text.clone(),
+        Some(Register::Number(number)) => number.to_string(),
+        _ => {
+            message(context, "Register does not contain text");
+            return Err(());
+        }
+    };
+    let window = context.window_list.get_current_window();
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    // Point stays before the text, and the mark goes after it.
+    let end = buffer.insert_at(buffer.cursor(), &text);
+    buffer.set_mark(Some(end));
+    Ok(())
+}
+
+pub fn point_to_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Point to register: ")?;
+    let buffer_ref = context.window_list.get_current_window().buffer_ref;
+    let cursor = context.buffer_list.resolve_ref(buffer_ref).cursor();
+    let position = add_position(context, buffer_ref, cursor);
+    set_register(context, name, Register::Position(position));
+    Ok(())
+}
+
+pub fn window_configuration_to_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Window configuration to register: ")?;
+    let configuration = current_configuration(context);
+    set_register(context, name, Register::WindowConfiguration(configuration));
+    Ok(())
+}
+
+pub fn jump_to_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Jump to register: ")?;
+    // Take the register out while restoring, to borrow the context.
+    let register = context.registers.registers.remove(&name);
+    let result = match &register {
+        Some(Register::Position(position)) => {
+            mark_ring::push_jump(context);
+            mark_ring::goto_position(context, *position);
+            Ok(())
+        }
+        Some(Register::WindowConfiguration(configuration)) => {
+            restore_configuration(context, configuration);
+            Ok(())
+        }
+        _ => {
+            message(
+                context,
+                "Register doesn't contain a buffer position or configuration",
+            );
+            Err(())
+        }
+    };
+    if let Some(register) = register {
+        context.registers.registers.insert(name, register);
+    }
+    result
+}
+
+pub fn number_to_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Number to register: ")?;
+    let input = read::read_string(term, context, "Number (default 0): ", |_, _| {}, false)
+        .map_err(|_| ())?;
+    let number = match input.trim() {
+        "" => 0,
+        input => input.parse().map_err(|_| {
+            message(context, format!("Invalid number: {}", input));
+        })?,
+    };
+    set_register(context, name, Register::Number(number));
+    Ok(())
+}
+
+/// Add 1 to a number register, or append the region to a text
+/// register.
+pub fn increment_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "Increment register: ")?;
+    let region = match context.registers.get(name) {
+        Some(Register::Text(_)) => {
+            let (start, end) = commands::region_or_message(context)?;
+            let window = context.window_list.get_current_window();
+            let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+            Some(buffer.region_text(start, end))
+        }
+        _ => None,
+    };
+    match (context.registers.registers.get_mut(&name), region) {
+        (Some(Register::Number(number)), _) => {
+            *number += 1;
+            Ok(())
+        }
+        (Some(Register::Text(text)), Some(region)) => {
+            text.push_str(&region);
+            Ok(())
+        }
+        _ => {
+            message(context, "Register does not contain a number or text");
+            Err(())
+        }
+    }
+}
+
+pub fn view_register(context: &mut Context, term: &mut Term) -> Result {
+    let name = read_register(context, term, "View register: ")?;
+    let text = match context.registers.get(name) {
+        Some(Register::Text(text)) => format!("Register {} contains the text:\n\n{}", name, text),
+        Some(register) => format!(
+            "Register {} contains {}.",
+            name,
+            describe_register(context, register)
+        ),
+        None => {
+            message(context, format!("Register {} is empty", name));
+            return Err(());
+        }
+    };
+    show_registers(context, &text);
+    Ok(())
+}
+
+pub fn list_registers(context: &mut Context, _term: &mut Term) -> Result {
+    if context.registers.registers.is_empty() {
+        message(context, "All registers are empty");
+        return Err(());
+    }
+    let text: String = context
+        .registers
+        .registers
+        .iter()
+        .map(|(name, register)| {
+            format!(
+                "Register {} contains {}\n",
+                name,
+                describe_register(context, register)
+            )
+        })
+        .collect();
+    show_registers(context, &text);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn restore_window_configurations() {
+        let mut context = Context::new(Buffer::from_string("a\nb\nc\nd"));
+        let main = BufferRef::main_window();
+        context
+            .buffer_list
+            .resolve_ref_as_mut(main)
+            .set_cursor(Cursor { line: 2, column: 0 });
+        let configuration = current_configuration(&mut context);
+
+        context.window_list.split_selected();
+        context
+            .buffer_list
+            .resolve_ref_as_mut(main)
+            .set_cursor(Cursor { line: 3, column: 1 });
+        context
+            .buffer_list
+            .resolve_ref_as_mut(main)
+            .insert_at(Cursor { line: 0, column: 0 }, "z\n");
+
+        restore_configuration(&mut context, &configuration);
+        assert_eq!(context.window_list.windows.len(), 1);
+        let buffer = context.buffer_list.get_main_buffer();
+        assert_eq!(buffer.cursor(), Cursor { line: 3, column: 0 });
+
+        // Replacing the register deletes its markers.
+        let (_, point) = configuration.windows[0].point.unwrap();
+        set_register(
+            &mut context,
+            'w',
+            Register::WindowConfiguration(configuration),
+        );
+        set_register(&mut context, 'w', Register::Number(3));
+        let register = context.registers.get('w').unwrap();
+        assert_eq!(describe_register(&context, register), "the number 3");
+        assert!(context
+            .buffer_list
+            .get_main_buffer()
+            .markers
+            .get(point)
+            .is_none());
+    }
+}
@@ -0,0 +1,663 @@
+//! Balanced expressions
+//!
+//! A balanced expression (sexp) is either a symbol, a string or a
+//! group of expressions between matching delimiters. The buffer is
+//! split into tokens according to its syntax table, skipping
+//! comments, and the motion functions work on those tokens. The
+//! tokens of a buffer are kept until its text changes.
+//!
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::syntax::{SyntaxClass, SyntaxTable};
+use crate::{Buffer, Cursor};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Token {
+    Open {
+        pos: Cursor,
+        ch: char,
+    },
+    Close {
+        pos: Cursor,
+        ch: char,
+    },
+    /// A sequence of word and symbol constituents.
+    Atom {
+        start: Cursor,
+        end: Cursor,
+    },
+    String {
+        start: Cursor,
+        end: Cursor,
+    },
+}
+
+impl Token {
+    pub fn start(&self) -> Cursor {
+        match *self {
+            Token::Open { pos, .. } | Token::Close { pos, .. } => pos,
+            Token::Atom { start, .. } | Token::String { start, .. } => start,
+        }
+    }
+
+    pub fn end(&self) -> Cursor {
+        match *self {
+            Token::Open { pos, ch } | Token::Close { pos, ch } => Cursor {
+                line: pos.line,
+                column: pos.column + ch.len_utf8(),
+            },
+            Token::Atom { end, .. } | Token::String { end, .. } => end,
+        }
+    }
+
+    /// Move the ends of the token that are on `line` by `delta` bytes.
+    fn shift_line(&mut self, line: usize, delta: isize) {
+        let shift = |pos: &mut Cursor| {
+            if pos.line == line {
+                pos.column = pos.column.saturating_add_signed(delta);
+            }
+        };
+        match self {
+            Token::Open { pos, .. } | Token::Close { pos, .. } => shift(pos),
+            Token::Atom { start, end } | Token::String { start, end } => {
+                shift(start);
+                shift(end);
+            }
+        }
+    }
+}
+
+/// The open delimiters that are not closed before a position, found
+/// by walking the tokens of a buffer forward. This is faster than
+/// `backward_up_list` for positions visited in order.
+pub struct OpenDelimiters {
+    tokens: Vec<Token>,
+    /// The index of the first token not visited yet.
+    next: usize,
+    stack: Vec<Cursor>,
+}
+
+impl OpenDelimiters {
+    pub fn new(tokens: Vec<Token>) -> OpenDelimiters {
+        OpenDelimiters {
+            tokens,
+            next: 0,
+            stack: vec![],
+        }
+    }
+
+    /// Return the innermost open delimiter of the expressions that
+    /// contain `pos`. `pos` can't be before the previous position.
+    pub fn containing(&mut self, pos: Cursor) -> Option<Cursor> {
+        while let Some(token) = self.tokens.get(self.next).filter(|t| t.end() <= pos) {
+            match *token {
+                Token::Open { pos, .. } => self.stack.push(pos),
+                Token::Close { .. } => {
+                    self.stack.pop();
+                }
+                _ => {}
+            }
+            self.next += 1;
+        }
+        self.stack.last().copied()
+    }
+
+    /// Update the tokens after `delta` bytes were inserted at the
+    /// start of `line`, or removed if it is negative. The line must
+    /// not be before the previous position.
+    pub fn shift_line(&mut self, line: usize, delta: isize) {
+        for token in &mut self.tokens[self.next..] {
+            if token.start().line > line {
+                break;
+            }
+            token.shift_line(line, delta);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanError {
+    /// A delimiter has no matching delimiter.
+    Unbalanced,
+    /// The expression containing the position ends before finding
+    /// the next expression.
+    ContainingExpressionEnds,
+}
+
+impl ScanError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ScanError::Unbalanced => "Unbalanced parentheses",
+            ScanError::ContainingExpressionEnds => "Containing expression ends prematurely",
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ScanError>;
+
+/// The tokens of a buffer, with the syntax table they were split with.
+pub type TokenCache = RefCell<Option<(Rc<SyntaxTable>, Rc<Vec<Token>>)>>;
+
+enum State {
+    Code,
+    String { quote: char, start: Cursor },
+    BlockComment,
+}
+
+fn is_atom_constituent(class: SyntaxClass) -> bool {
+    matches!(
+        class,
+        SyntaxClass::Word | SyntaxClass::Symbol | SyntaxClass::Escape
+    )
+}
+
+/// Split the content of `buffer` into tokens, skipping whitespace,
+/// punctuation and comments.
+pub fn tokenize(buffer: &Buffer) -> Vec<Token> {
+    let table: &SyntaxTable = &buffer.mode.syntax_table;
+    let mut tokens = vec![];
+    let mut state = State::Code;
+
+    for linenum in 0..buffer.lines_count() {
+        let line = buffer.get_line_unchecked(linenum);
+        let at = |column| Cursor {
+            line: linenum,
+            column,
+        };
+        let mut atom_start: Option<usize> = None;
+        let mut chars = line.char_indices();
+
+        while let Some((column, ch)) = chars.next() {
+            let class = table.class(ch);
+
+            match state {
+                State::String { quote, start } => {
+                    if class == SyntaxClass::Escape {
+                        chars.next();
+                    } else if ch == quote {
+                        tokens.push(Token::String {
+                            start,
+                            end: at(column + ch.len_utf8()),
+                        });
+                        state = State::Code;
+                    }
+                    continue;
+                }
+                State::BlockComment => {
+                    if let Some((_, end)) = table.block_comment {
+                        if line[column..].starts_with(end) {
+                            for _ in 1..end.chars().count() {
+                                chars.next();
+                            }
+                            state = State::Code;
+                        }
+                    }
+                    continue;
+                }
+                State::Code => {}
+            }
+
+            if is_atom_constituent(class) {
+                atom_start.get_or_insert(column);
+                if class == SyntaxClass::Escape {
+                    chars.next();
+                }
+                continue;
+            }
+
+            if let Some(start) = atom_start.take() {
+                tokens.push(Token::Atom {
+                    start: at(start),
+                    end: at(column),
+                });
+            }
+
+            if table
+                .line_comment
+                .is_some_and(|comment| line[column..].starts_with(comment))
+            {
+                break;
+            }
+            if let Some((start, _)) = table.block_comment {
+                if line[column..].starts_with(start) {
+                    for _ in 1..start.chars().count() {
+                        chars.next();
+                    }
+                    state = State::BlockComment;
+                    continue;
+                }
+            }
+
+            match class {
+                SyntaxClass::Open(_) => tokens.push(Token::Open {
+                    pos: at(column),
+                    ch,
+                }),
+                SyntaxClass::Close(_) => tokens.push(Token::Close {
+                    pos: at(column),
+                    ch,
+                }),
+                SyntaxClass::StringQuote => {
+                    state = State::String {
+                        quote: ch,
+                        start: at(column),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(start) = atom_start {
+            tokens.push(Token::Atom {
+                start: at(start),
+                end: at(line.len()),
+            });
+        }
+    }
+
+    // An unterminated string extends until the end of the buffer.
+    if let State::String { start, .. } = state {
+        tokens.push(Token::String {
+            start,
+            end: buffer.end_position(),
+        });
+    }
+
+    tokens
+}
+
+/// Return the tokens of `buffer`, splitting its content again only if
+/// it changed since the last call.
+pub fn buffer_tokens(buffer: &Buffer) -> Rc<Vec<Token>> {
+    let table = &buffer.mode.syntax_table;
+    let mut cache = buffer.tokens.borrow_mut();
+    match &*cache {
+        Some((cached_table, tokens)) if Rc::ptr_eq(cached_table, table) => tokens.clone(),
+        _ => {
+            let tokens = Rc::new(tokenize(buffer));
+            *cache = Some((table.clone(), tokens.clone()));
+            tokens
+        }
+    }
+}
+
+/// Return the index of the token that matches the delimiter at
+/// `index`, if there is any.
+fn find_matching(tokens: &[Token], index: usize) -> Option<usize> {
+    let mut depth = 0;
+    match tokens[index] {
+        Token::Open { .. } => {
+            for (i, token) in tokens.iter().enumerate().skip(index) {
+                match token {
+                    Token::Open { .. } => depth += 1,
+                    Token::Close { .. } => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            None
+        }
+        Token::Close { .. } => {
+            for (i, token) in tokens.iter().enumerate().take(index + 1).rev() {
+                match token {
+                    Token::Close { .. } => depth += 1,
+                    Token::Open { .. } => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Return the blocks between braces of `buffer`, as the positions of
+/// each `{` and of its matching `}`, in the order of the open braces.
+pub fn brace_blocks(buffer: &Buffer) -> Vec<(Cursor, Cursor)> {
+    let mut opens = vec![];
+    let mut blocks = vec![];
+    for &token in buffer_tokens(buffer).iter() {
+        match token {
+            Token::Open { pos, ch } => opens.push((pos, ch)),
+            Token::Close { pos, ch } => {
+                if let Some((open, '{')) = opens.pop() {
+                    if ch == '}' {
+                        blocks.push((open, pos));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks.sort();
+    blocks
+}
+
+/// Return the position after the next balanced expression.
+pub fn forward_sexp(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    match tokens.iter().position(|token| token.end() > pos) {
+        None => Ok(buffer.end_position()),
+        Some(index) => match tokens[index] {
+            Token::Close { .. } => Err(ScanError::ContainingExpressionEnds),
+            Token::Open { .. } => find_matching(&tokens, index)
+                .map(|i| tokens[i].end())
+                .ok_or(ScanError::Unbalanced),
+            token => Ok(token.end()),
+        },
+    }
+}
+
+/// Return the position at the beginning of the previous balanced
+/// expression.
+pub fn backward_sexp(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    match tokens.iter().rposition(|token| token.start() < pos) {
+        None => Ok(Cursor { line: 0, column: 0 }),
+        Some(index) => match tokens[index] {
+            Token::Open { .. } => Err(ScanError::ContainingExpressionEnds),
+            Token::Close { .. } => find_matching(&tokens, index)
+                .map(|i| tokens[i].start())
+                .ok_or(ScanError::Unbalanced),
+            token => Ok(token.start()),
+        },
+    }
+}
+
+/// Return the position after the next group of expressions between
+/// delimiters.
+pub fn forward_list(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    let index = (tokens.partition_point(|token| token.start() < pos)..tokens.len())
+        .find(|&index| matches!(tokens[index], Token::Open { .. } | Token::Close { .. }))
+        .ok_or(ScanError::Unbalanced)?;
+    match tokens[index] {
+        Token::Open { .. } => find_matching(&tokens, index)
+            .map(|i| tokens[i].end())
+            .ok_or(ScanError::Unbalanced),
+        _ => Err(ScanError::ContainingExpressionEnds),
+    }
+}
+
+/// Return the position at the beginning of the previous group of
+/// expressions between delimiters.
+pub fn backward_list(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    let index = (0..tokens.partition_point(|token| token.end() <= pos))
+        .rev()
+        .find(|&index| matches!(tokens[index], Token::Open { .. } | Token::Close { .. }))
+        .ok_or(ScanError::Unbalanced)?;
+    match tokens[index] {
+        Token::Close { .. } => find_matching(&tokens, index)
+            .map(|i| tokens[i].start())
+            .ok_or(ScanError::Unbalanced),
+        _ => Err(ScanError::ContainingExpressionEnds),
+    }
+}
+
+/// Return the position after the end of the expression containing
+/// `pos`.
+pub fn up_list(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    let mut depth = 0;
+    for token in tokens.iter().filter(|token| token.start() >= pos) {
+        match token {
+            Token::Open { .. } => depth += 1,
+            Token::Close { .. } if depth == 0 => return Ok(token.end()),
+            Token::Close { .. } => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(ScanError::Unbalanced)
+}
+
+/// Return the position at the beginning of the expression containing
+/// `pos`.
+pub fn backward_up_list(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    let mut depth = 0;
+    for token in tokens.iter().rev().filter(|token| token.end() <= pos) {
+        match token {
+            Token::Close { .. } => depth += 1,
+            Token::Open { .. } if depth == 0 => return Ok(token.start()),
+            Token::Open { .. } => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(ScanError::Unbalanced)
+}
+
+/// Return the position after the next open delimiter, entering the
+/// expression it starts.
+pub fn down_list(buffer: &Buffer, pos: Cursor) -> Result<Cursor> {
+    let tokens = buffer_tokens(buffer);
+    for token in tokens.iter().filter(|token| token.start() >= pos) {
+        match token {
+            Token::Open { .. } => return Ok(token.end()),
+            Token::Close { .. } => return Err(ScanError::ContainingExpressionEnds),
+            _ => {}
+        }
+    }
+    Err(ScanError::Unbalanced)
+}
+
+/// A delimiter next to the cursor and its matching delimiter.
+pub struct ParenMatch {
+    pub paren: Cursor,
+    pub matching: Option<Cursor>,
+    /// If set, the delimiters do not match or there is no matching
+    /// delimiter at all.
+    pub mismatched: bool,
+}
+
+/// Find the delimiter after `pos` if it is an open delimiter, or the
+/// delimiter before `pos` if it is a close delimiter, and its match.
+pub fn find_paren_match(buffer: &Buffer, pos: Cursor) -> Option<ParenMatch> {
+    // This runs after every command, so avoid the tokens unless there
+    // is a delimiter next to `pos`.
+    let table = &buffer.mode.syntax_table;
+    let open_after = buffer
+        .char_after(pos)
+        .is_some_and(|ch| matches!(table.class(ch), SyntaxClass::Open(_)));
+    let close_before = buffer
+        .char_before(pos)
+        .is_some_and(|ch| matches!(table.class(ch), SyntaxClass::Close(_)));
+    if !open_after && !close_before {
+        return None;
+    }
+
+    // A close delimiter ending at `pos` is the first token that does
+    // not end before it, and an open delimiter at `pos` the first or
+    // the second one.
+    let tokens = buffer_tokens(buffer);
+    let first = tokens.partition_point(|token| token.end() < pos);
+    let index = (first..tokens.len())
+        .take(2)
+        .find(|&index| match tokens[index] {
+            Token::Open { pos: open, .. } => open == pos,
+            token @ Token::Close { .. } => token.end() == pos,
+            _ => false,
+        })?;
+    let paren = tokens[index].start();
+
+    match find_matching(&tokens, index) {
+        Some(other) => {
+            let mismatched = match (tokens[index], tokens[other]) {
+                (Token::Open { ch, .. }, Token::Close { ch: close, .. })
+                | (Token::Close { ch: close, .. }, Token::Open { ch, .. }) => {
+                    table.class(ch) != SyntaxClass::Open(close)
+                }
+                _ => true,
+            };
+            Some(ParenMatch {
+                paren,
+                matching: Some(tokens[other].start()),
+                mismatched,
+            })
+        }
+        None => Some(ParenMatch {
+            paren,
+            matching: None,
+            mismatched: true,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_list::BufferRef;
+    use crate::commands;
+    use crate::mode::MajorMode;
+    use crate::term::Term;
+    use crate::Context;
+
+    fn rust_buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::from_string(text);
+        buffer.mode = MajorMode::rust();
+        buffer
+    }
+
+    #[test]
+    fn skip_strings_and_comments() {
+        let buffer = rust_buffer("f(\"(\", /* ) */ x) // )\ny");
+        assert_eq!(
+            forward_sexp(&buffer, Cursor::new(0, 0)),
+            Ok(Cursor::new(0, 1))
+        );
+        assert_eq!(
+            forward_sexp(&buffer, Cursor::new(0, 1)),
+            Ok(Cursor::new(0, 17))
+        );
+        assert_eq!(
+            backward_sexp(&buffer, Cursor::new(0, 17)),
+            Ok(Cursor::new(0, 1))
+        );
+        assert_eq!(
+            forward_sexp(&buffer, Cursor::new(0, 17)),
+            Ok(Cursor::new(1, 1))
+        );
+    }
+
+    #[test]
+    fn move_up_and_down_lists() {
+        let buffer = rust_buffer("a (b [c] d)");
+        assert_eq!(down_list(&buffer, Cursor::new(0, 0)), Ok(Cursor::new(0, 3)));
+        assert_eq!(up_list(&buffer, Cursor::new(0, 7)), Ok(Cursor::new(0, 8)));
+        assert_eq!(
+            backward_up_list(&buffer, Cursor::new(0, 9)),
+            Ok(Cursor::new(0, 2))
+        );
+        assert_eq!(
+            up_list(&buffer, Cursor::new(0, 11)),
+            Err(ScanError::Unbalanced)
+        );
+        assert_eq!(
+            forward_list(&buffer, Cursor::new(0, 0)),
+            Ok(Cursor::new(0, 11))
+        );
+        assert_eq!(
+            forward_list(&buffer, Cursor::new(0, 3)),
+            Ok(Cursor::new(0, 8))
+        );
+        assert_eq!(
+            forward_list(&buffer, Cursor::new(0, 8)),
+            Err(ScanError::ContainingExpressionEnds)
+        );
+        assert_eq!(
+            backward_list(&buffer, Cursor::new(0, 11)),
+            Ok(Cursor::new(0, 2))
+        );
+        assert_eq!(
+            backward_list(&buffer, Cursor::new(0, 10)),
+            Ok(Cursor::new(0, 5))
+        );
+        assert_eq!(
+            forward_sexp(&buffer, Cursor::new(0, 8)),
+            Ok(Cursor::new(0, 10)),
+            "skip the atom d"
+        );
+        assert_eq!(
+            forward_sexp(&buffer, Cursor::new(0, 10)),
+            Err(ScanError::ContainingExpressionEnds)
+        );
+    }
+
+    #[test]
+    fn flag_mismatched_parens() {
+        let buffer = rust_buffer("(a] (b)");
+        let paren = find_paren_match(&buffer, Cursor::new(0, 0)).unwrap();
+        assert_eq!(paren.matching, Some(Cursor::new(0, 2)));
+        assert!(paren.mismatched);
+
+        let paren = find_paren_match(&buffer, Cursor::new(0, 7)).unwrap();
+        assert_eq!(paren.matching, Some(Cursor::new(0, 4)));
+        assert!(!paren.mismatched);
+        assert!(find_paren_match(&buffer, Cursor::new(0, 6)).is_none());
+    }
+
+    #[test]
+    fn walk_open_delimiters() {
+        let buffer = rust_buffer("a (b\n[c]\n d)\ne");
+        let mut opens = OpenDelimiters::new(tokenize(&buffer));
+        assert_eq!(opens.containing(Cursor::new(0, 0)), None);
+        assert_eq!(opens.containing(Cursor::new(1, 0)), Some(Cursor::new(0, 2)));
+        assert_eq!(opens.containing(Cursor::new(1, 2)), Some(Cursor::new(1, 0)));
+        opens.shift_line(2, -1);
+        assert_eq!(opens.containing(Cursor::new(2, 1)), Some(Cursor::new(0, 2)));
+        assert_eq!(opens.containing(Cursor::new(2, 2)), None);
+    }
+
+    #[test]
+    fn split_again_after_changes() {
+        let mut buffer = rust_buffer("(a)");
+        let tokens = buffer_tokens(&buffer);
+        assert!(Rc::ptr_eq(&tokens, &buffer_tokens(&buffer)));
+
+        buffer.insert_at(Cursor::new(0, 0), "(");
+        assert_eq!(
+            forward_sexp(&buffer, Cursor::new(0, 1)),
+            Ok(Cursor::new(0, 4))
+        );
+        assert_eq!(
+            up_list(&buffer, Cursor::new(0, 1)),
+            Err(ScanError::Unbalanced)
+        );
+    }
+
+    #[test]
+    fn match_parens_after_moving_over_multibyte_characters() {
+        let mut context = Context::new(rust_buffer("ñ(é)"));
+        let term = &mut Term::new();
+        let main = BufferRef::main_window();
+        commands::forward_char(&mut context, term).unwrap();
+        let buffer = context.buffer_list.resolve_ref(main);
+        assert_eq!(buffer.cursor(), Cursor::new(0, 2));
+        let paren_match = find_paren_match(buffer, buffer.cursor()).unwrap();
+        assert_eq!(paren_match.matching, Some(Cursor::new(0, 5)));
+
+        for _ in 0..3 {
+            commands::forward_char(&mut context, term).unwrap();
+        }
+        let buffer = context.buffer_list.resolve_ref(main);
+        assert_eq!(buffer.cursor(), Cursor::new(0, 6));
+        let paren_match = find_paren_match(buffer, buffer.cursor()).unwrap();
+        assert_eq!(paren_match.matching, Some(Cursor::new(0, 2)));
+        assert!(commands::forward_char(&mut context, term).is_err());
+
+        for _ in 0..4 {
+            commands::backward_char(&mut context, term).unwrap();
+        }
+        let buffer = context.buffer_list.resolve_ref(main);
+        assert_eq!(buffer.cursor(), Cursor::new(0, 0));
+        assert!(find_paren_match(buffer, buffer.cursor()).is_none());
+        assert!(commands::backward_char(&mut context, term).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Signals
+//!
+//! Suspending the editor with C-z, and the handling of the signals it
+//! receives: the screen is redrawn when the editor is continued or
+//! resized, and modified buffers are saved in an emergency when it is
+//! terminated or its terminal hangs up.
+//!
+//! `auto-save-mode` saves them to the same files when the editor has
+//! been idle for a while, so less work is lost if it is killed.
+//!
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use nix::libc::c_int;
+use nix::sys::signal::{self, Signal};
+use signal_hook::{flag, pipe};
+
+use crate::commands::Result;
+use crate::term::{self, Term};
+use crate::window::message;
+use crate::Context;
+
+/// How long the editor must be idle before `auto-save-mode` saves the
+/// modified buffers.
+const AUTO_SAVE_IDLE: Duration = Duration::from_secs(30);
+
+const SIGNALS: [c_int; 4] = [
+    signal_hook::SIGWINCH,
+    signal_hook::SIGCONT,
+    signal_hook::SIGTERM,
+    signal_hook::SIGHUP,
+];
+
+/// Set up the signal handlers. They only set flags in `context`, which
+/// are checked by `handle_signals`, and wake up the event loop through
+/// a pipe.
+pub fn register(context: &mut Context) -> io::Result<()> {
+    flag::register(signal_hook::SIGWINCH, context.was_resized.clone())?;
+    flag::register(signal_hook::SIGCONT, context.was_continued.clone())?;
+    for &sig in &[signal_hook::SIGTERM, signal_hook::SIGHUP] {
+        flag::register_usize(sig, context.terminated.clone(), sig as usize)?;
+    }
+
+    let (read, write) = UnixStream::pair()?;
+    read.set_nonblocking(true)?;
+    write.set_nonblocking(true)?;
+    for &sig in &SIGNALS {
+        pipe::register(sig, write.try_clone()?)?;
+    }
+    context.event_loop.signal_pipe = Some(read);
+    Ok(())
+}
+
+/// Act on the signals received since the last call. Return true if
+/// the screen must be redrawn.
+///
+/// If the editor was asked to terminate, this does not return.
+pub fn handle_signals(term: &mut Term, context: &mut Context) -> bool {
+    let terminated = context.terminated.load(Ordering::Relaxed);
+    if terminated != 0 {
+        let saved = emergency_save(context);
+        term::restore_terminal();
+        // The terminal may be gone after a hangup.
+        for filename in saved {
+            let _ = writeln!(io::stderr(), "rile: modified buffer saved to {}", filename);
+        }
+        process::exit(128 + terminated as i32);
+    }
+
+    let continued = context.was_continued.swap(false, Ordering::Relaxed);
+    if continued {
+        // The shell may have changed the terminal while we were
+        // stopped.
+        term::resume_terminal(term);
+    }
+    let resized = term::reconciliate_term_size(term, &context.was_resized);
+    continued || resized
+}
+
+/// Return the name of the file where `filename` is saved in an
+/// emergency.
+fn emergency_filename(filename: &str) -> String {
+    let path = Path::new(filename);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("#{}#", name))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Save every modified buffer visiting a file to a file next to it,
+/// leaving the original file untouched. Return the names of the files
+/// written.
+pub fn emergency_save(context: &Context) -> Vec<String> {
+    context
+        .buffer_list
+        .iter()
+        .filter(|(_, buffer)| buffer.modified)
+        .filter_map(|(_, buffer)| {
+            let filename = emergency_filename(buffer.filename.as_ref()?);
+            fs::write(&filename, buffer.to_string()).ok()?;
+            Some(filename)
+        })
+        .collect()
+}
+
+fn do_auto_save(context: &mut Context, _term: &mut Term) -> Result {
+    if !emergency_save(context).is_empty() {
+        message(context, "Auto-saving...done");
+    }
+    Ok(())
+}
+
+pub fn auto_save_mode(context: &mut Context, _term: &mut Term) -> Result {
+    match context.auto_save_timer.take() {
+        Some(timer) => {
+            context.timers.cancel_timer(timer);
+            message(context, "Auto-save mode disabled");
+        }
+        None => {
+            let timer = context
+                .timers
+                .run_with_idle_timer(AUTO_SAVE_IDLE, true, do_auto_save);
+            context.auto_save_timer = Some(timer);
+            message(context, "Auto-save mode enabled");
+        }
+    }
+    Ok(())
+}
+
+/// Stop the editor and give the terminal back to the shell, until it
+/// is continued.
+pub fn suspend_frame(context: &mut Context, term: &mut Term) -> Result {
+    term.flush().unwrap();
+    term::restore_terminal();
+    let _ = signal::raise(Signal::SIGTSTP);
+
+    // Running again: take the terminal back.
+    context.was_continued.store(false, Ordering::Relaxed);
+    term::resume_terminal(term);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emergency_file_is_next_to_the_original() {
+        assert_eq!(emergency_filename("/tmp/src/main.rs"), "/tmp/src/#main.rs#");
+        assert_eq!(emergency_filename("notes"), "#notes#");
+    }
+}
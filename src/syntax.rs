@@ -0,0 +1,84 @@
+//! Syntax tables
+//!
+//! A syntax table describes the role of each character in the text
+//! of a buffer, for example if it is part of a word or it is
+//! whitespace. Commands that work on words, sentences, paragraphs
+//! or balanced expressions use it to decide where those units start
+//! and end.
+//!
+
+use std::collections::HashMap;
+
+/// The syntactic role of a character.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyntaxClass {
+    Whitespace,
+    /// Constituent of words.
+    Word,
+    /// Constituent of symbols, but not of words. For example `_` in
+    /// most programming languages.
+    Symbol,
+    Punctuation,
+    /// An open delimiter, with its matching close delimiter.
+    Open(char),
+    /// A close delimiter, with its matching open delimiter.
+    Close(char),
+    /// A delimiter of strings.
+    StringQuote,
+    /// A character that escapes the next one, like a backslash in strings.
+    Escape,
+}
+
+#[derive(Clone)]
+pub struct SyntaxTable {
+    entries: HashMap<char, SyntaxClass>,
+
+    /// The sequence that starts a comment until the end of the line.
+    pub line_comment: Option<&'static str>,
+    /// The sequences that start and end a block comment.
+    pub block_comment: Option<(&'static str, &'static str)>,
+}
+
+impl SyntaxTable {
+    /// A syntax table where alphanumeric characters are words,
+    /// whitespace is whitespace, brackets are delimiters and anything
+    /// else is punctuation.
+    pub fn standard() -> SyntaxTable {
+        let mut table = SyntaxTable {
+            entries: HashMap::new(),
+            line_comment: None,
+            block_comment: None,
+        };
+        table.set_pair('(', ')');
+        table.set_pair('[', ']');
+        table.set_pair('{', '}');
+        table
+    }
+
+    /// Make `open` and `close` a pair of matching delimiters.
+    pub fn set_pair(&mut self, open: char, close: char) {
+        self.set(open, SyntaxClass::Open(close));
+        self.set(close, SyntaxClass::Close(open));
+    }
+
+    /// Modify the syntax class of `ch`.
+    pub fn set(&mut self, ch: char, class: SyntaxClass) {
+        self.entries.insert(ch, class);
+    }
+
+    pub fn class(&self, ch: char) -> SyntaxClass {
+        if let Some(class) = self.entries.get(&ch) {
+            *class
+        } else if ch.is_alphanumeric() {
+            SyntaxClass::Word
+        } else if ch.is_whitespace() {
+            SyntaxClass::Whitespace
+        } else {
+            SyntaxClass::Punctuation
+        }
+    }
+
+    pub fn is_word(&self, ch: char) -> bool {
+        self.class(ch) == SyntaxClass::Word
+    }
+}
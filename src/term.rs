@@ -2,6 +2,7 @@ use std::env;
 use std::io::prelude::*;
 use std::mem;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 
@@ -9,7 +10,10 @@ use nix::libc;
 use nix::sys::termios;
 use nix::unistd;
 
-use crate::Key;
+/// The configuration of the terminal before entering raw mode and
+/// in raw mode, so it can be switched back and forth from anywhere,
+/// including a panic hook.
+static TERMIOS: Mutex<Option<(termios::Termios, termios::Termios)>> = Mutex::new(None);
 
 /// Execute a function with the terminal in raw mode.
 ///
@@ -42,23 +46,68 @@ pub fn with_raw_mode<F: FnOnce()>(run: F) -> nix::Result<()> {
     termios.input_flags &= !termios::InputFlags::ISTRIP;
     termios.control_flags |= termios::ControlFlags::CS8;
 
-    // Be okay with read() returning 0 bytes read
+    // Make read() return at once, even with 0 bytes read. Waiting for
+    // input is done with poll().
     termios.control_chars[termios::SpecialCharacterIndices::VMIN as usize] = 0;
-    termios.control_chars[termios::SpecialCharacterIndices::VTIME as usize] = 1;
+    termios.control_chars[termios::SpecialCharacterIndices::VTIME as usize] = 0;
 
-    termios::tcsetattr(libc::STDIN_FILENO, termios::SetArg::TCSAFLUSH, &termios)?;
+    *TERMIOS.lock().unwrap() = Some((original_termios, termios));
+    enter_raw_mode()?;
 
     run();
 
-    termios::tcsetattr(
-        libc::STDIN_FILENO,
-        termios::SetArg::TCSAFLUSH,
-        &original_termios,
-    )?;
+    leave_raw_mode()?;
+    *TERMIOS.lock().unwrap() = None;
 
     return Ok(());
 }
 
+/// Switch the terminal to raw mode, if `with_raw_mode` is running.
+pub fn enter_raw_mode() -> nix::Result<()> {
+    let saved = TERMIOS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((_, raw)) = &*saved {
+        termios::tcsetattr(libc::STDIN_FILENO, termios::SetArg::TCSAFLUSH, raw)?;
+    }
+    Ok(())
+}
+
+/// Switch the terminal back to its original configuration, if
+/// `with_raw_mode` is running.
+pub fn leave_raw_mode() -> nix::Result<()> {
+    let saved = TERMIOS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((original, _)) = &*saved {
+        termios::tcsetattr(libc::STDIN_FILENO, termios::SetArg::TCSAFLUSH, original)?;
+    }
+    Ok(())
+}
+
+/// Give the terminal back to the shell: leave raw mode, stop mouse
+/// tracking and bracketed paste, go back to the main screen and show
+/// the cursor.
+///
+/// This writes to the terminal directly, so it is safe to call
+/// without a `Term`, for example from a panic hook.
+pub fn restore_terminal() {
+    let _ = leave_raw_mode();
+    let _ = unistd::write(
+        libc::STDOUT_FILENO,
+        b"\x1b[?2004l\x1b[?1006l\x1b[?1002l\x1b[?1049l\x1b[?25h",
+    );
+}
+
+/// Take the terminal again after `restore_terminal`. The whole
+/// screen must be redrawn afterwards.
+pub fn resume_terminal(term: &mut Term) {
+    let _ = enter_raw_mode();
+    term.enable_alternative_screen_buffer();
+    term.enable_mouse_tracking();
+    term.enable_bracketed_paste();
+    term.clear_screen();
+    let (rows, columns) = get_window_size();
+    term.rows = rows;
+    term.columns = columns;
+}
+
 pub struct Term {
     buffer: Vec<u8>,
     // The size of the terminal
@@ -146,8 +195,30 @@ impl Term {
         self.csi("?1049l");
     }
 
+    /// Make the terminal report mouse clicks, drags and the wheel as
+    /// input, in the SGR format.
+    pub fn enable_mouse_tracking(&mut self) {
+        self.csi("?1002h");
+        self.csi("?1006h");
+    }
+
+    /// Stop reporting mouse events.
+    pub fn disable_mouse_tracking(&mut self) {
+        self.csi("?1006l");
+        self.csi("?1002l");
+    }
+
+    /// Make the terminal mark pasted text, so it can be told apart
+    /// from typed keys.
+    pub fn enable_bracketed_paste(&mut self) {
+        self.csi("?2004h");
+    }
+
+    pub fn disable_bracketed_paste(&mut self) {
+        self.csi("?2004l");
+    }
+
     /// Clear the screen.
-    #[allow(unused)]
     pub fn clear_screen(&mut self) {
         self.csi("2J");
     }
@@ -213,36 +284,10 @@ fn support_true_color() -> bool {
     env::var("COLORTERM") == Ok(String::from("truecolor"))
 }
 
-/// Read and return a key.
-pub fn read_key_timeout() -> Option<Key> {
-    const ARROW_UP: &'static [u8; 2] = b"[A";
-    const ARROW_DOWN: &'static [u8; 2] = b"[B";
-    const ARROW_RIGHT: &'static [u8; 2] = b"[C";
-    const ARROW_LEFT: &'static [u8; 2] = b"[D";
-
-    let mut buf = [0u8];
-    unistd::read(libc::STDIN_FILENO, &mut buf).unwrap();
-    let cmd = buf[0] as u32;
-    if cmd == 0x1b {
-        let mut seq: [u8; 2] = [0; 2];
-        unistd::read(libc::STDIN_FILENO, &mut seq).unwrap();
-
-        if seq[1] == 0 {
-            Some(Key::from_code(seq[0] as u32).meta())
-        } else {
-            match &seq {
-                ARROW_UP => Some(Key::parse_unchecked("C-p")),
-                ARROW_DOWN => Some(Key::parse_unchecked("C-n")),
-                ARROW_RIGHT => Some(Key::parse_unchecked("C-f")),
-                ARROW_LEFT => Some(Key::parse_unchecked("C-b")),
-                _ => None,
-            }
-        }
-    } else if cmd > 0 {
-        Some(Key::from_code(cmd))
-    } else {
-        None
-    }
+/// Read the bytes available from the terminal into `buf` without
+/// waiting, and return how many were read.
+pub fn read_input(buf: &mut [u8]) -> usize {
+    unistd::read(libc::STDIN_FILENO, buf).unwrap_or(0)
 }
 
 pub fn reconciliate_term_size(term: &mut Term, was_resized: &AtomicBool) -> bool {
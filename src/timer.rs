@@ -0,0 +1,213 @@
+//! Timers
+//!
+//! Commands can be scheduled to run at some time, or when the editor
+//! has been idle for a while. Timers run while the editor waits for
+//! input, so they never interrupt a command.
+//!
+
+use std::time::{Duration, Instant};
+
+use crate::keymap::CommandHandler;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TimerId(u64);
+
+enum When {
+    /// Run at a time, and then every `repeat` if it is set.
+    At {
+        time: Instant,
+        repeat: Option<Duration>,
+    },
+    /// Run once the editor has been idle for `idle`. If `repeat` is
+    /// set, run again in each later idle period.
+    Idle {
+        idle: Duration,
+        repeat: bool,
+        /// Set if the timer already ran in the current idle period.
+        done: bool,
+    },
+}
+
+struct Timer {
+    id: TimerId,
+    when: When,
+    handler: CommandHandler,
+}
+
+pub struct TimerList {
+    timers: Vec<Timer>,
+    next_id: u64,
+    /// When the editor started waiting for input, if it is waiting.
+    idle_since: Option<Instant>,
+}
+
+impl TimerList {
+    pub fn new() -> TimerList {
+        TimerList {
+            timers: vec![],
+            next_id: 0,
+            idle_since: None,
+        }
+    }
+
+    fn add(&mut self, when: When, handler: CommandHandler) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push(Timer { id, when, handler });
+        id
+    }
+
+    /// Run `handler` after `delay`, and then every `repeat` if it is
+    /// set.
+    pub fn run_at_time(
+        &mut self,
+        delay: Duration,
+        repeat: Option<Duration>,
+        handler: CommandHandler,
+    ) -> TimerId {
+        let time = Instant::now() + delay;
+        self.add(When::At { time, repeat }, handler)
+    }
+
+    /// Run `handler` when the editor has been idle for `idle`. If
+    /// `repeat` is set, it runs in every idle period, not only the
+    /// next one.
+    pub fn run_with_idle_timer(
+        &mut self,
+        idle: Duration,
+        repeat: bool,
+        handler: CommandHandler,
+    ) -> TimerId {
+        let when = When::Idle {
+            idle,
+            repeat,
+            done: false,
+        };
+        self.add(when, handler)
+    }
+
+    /// Cancel the timer `id`. Return false if it does not exist, for
+    /// example because it already ran.
+    pub fn cancel_timer(&mut self, id: TimerId) -> bool {
+        let len = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() != len
+    }
+
+    /// Record that the editor starts waiting for input at `now`.
+    pub fn start_idle(&mut self, now: Instant) {
+        self.idle_since.get_or_insert(now);
+    }
+
+    /// Record that the editor is busy again, ending the idle period.
+    pub fn stop_idle(&mut self) {
+        self.idle_since = None;
+        for timer in &mut self.timers {
+            if let When::Idle { done, .. } = &mut timer.when {
+                *done = false;
+            }
+        }
+    }
+
+    /// Return when `timer` should run next, if it should.
+    fn deadline(&self, timer: &Timer) -> Option<Instant> {
+        match timer.when {
+            When::At { time, .. } => Some(time),
+            When::Idle { idle, done, .. } if !done => self.idle_since.map(|since| since + idle),
+            When::Idle { .. } => None,
+        }
+    }
+
+    /// Return how long to wait from `now` until the next timer is due.
+    pub fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        self.timers
+            .iter()
+            .filter_map(|timer| self.deadline(timer))
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    /// Return the handlers of the timers that are due at `now`, in
+    /// the order they are due, and reschedule or remove their timers.
+    pub fn take_due(&mut self, now: Instant) -> Vec<CommandHandler> {
+        let mut due: Vec<(Instant, CommandHandler)> = self
+            .timers
+            .iter()
+            .filter_map(|timer| Some((self.deadline(timer)?, timer.handler)))
+            .filter(|(deadline, _)| *deadline <= now)
+            .collect();
+        due.sort_by_key(|(deadline, _)| *deadline);
+
+        let idle_since = self.idle_since;
+        self.timers.retain_mut(|timer| match &mut timer.when {
+            When::At { time, repeat } if *time <= now => match repeat {
+                Some(repeat) => {
+                    // Skip the runs that were missed instead of
+                    // running them all at once.
+                    let next = *time + *repeat;
+                    *time = if next <= now { now + *repeat } else { next };
+                    true
+                }
+                None => false,
+            },
+            When::Idle { idle, repeat, done } => match idle_since {
+                Some(since) if !*done && since + *idle <= now => {
+                    *done = true;
+                    *repeat
+                }
+                _ => true,
+            },
+            _ => true,
+        });
+
+        due.into_iter().map(|(_, handler)| handler).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands;
+
+    #[test]
+    fn run_timers_when_due() {
+        let mut timers = TimerList::new();
+        let once = timers.run_at_time(Duration::from_secs(2), None, commands::forward_char);
+        timers.run_at_time(
+            Duration::from_secs(1),
+            Some(Duration::from_secs(10)),
+            commands::backward_char,
+        );
+        let start = Instant::now();
+        assert_eq!(timers.take_due(start).len(), 0);
+
+        let due = timers.take_due(start + Duration::from_secs(3));
+        assert_eq!(due.len(), 2);
+        assert!(std::ptr::fn_addr_eq(
+            due[0],
+            commands::backward_char as CommandHandler
+        ));
+        assert!(!timers.cancel_timer(once));
+
+        let timeout = timers.next_timeout(start + Duration::from_secs(3)).unwrap();
+        assert!(timeout > Duration::from_secs(7));
+    }
+
+    #[test]
+    fn run_idle_timers_once_per_idle_period() {
+        let mut timers = TimerList::new();
+        timers.run_with_idle_timer(Duration::from_secs(1), true, commands::forward_char);
+        let start = Instant::now();
+        assert_eq!(timers.next_timeout(start), None);
+
+        timers.start_idle(start);
+        let later = start + Duration::from_secs(5);
+        assert_eq!(timers.take_due(later).len(), 1);
+        assert_eq!(timers.take_due(later).len(), 0);
+        assert_eq!(timers.next_timeout(later), None);
+
+        timers.stop_idle();
+        timers.start_idle(later);
+        assert_eq!(timers.take_due(later + Duration::from_secs(1)).len(), 1);
+    }
+}
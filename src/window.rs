@@ -1,66 +1,484 @@
 use std::cmp;
 use std::io::Write;
+use std::ops::{Range, RangeInclusive};
 use std::thread;
 use std::time::Duration;
 
+use crate::buffer::next_display_column;
 use crate::buffer_list::BufferRef;
+use crate::face::{self, Face};
+use crate::gutter::{self, LineNumbers};
 use crate::layout;
-use crate::term;
-use crate::Context;
+use crate::marker::MarkerId;
+use crate::messages;
+use crate::modeline;
+use crate::multiple_cursors;
+use crate::overlay::{Overlay, Segment};
+use crate::term::{self, ErasePart};
+use crate::{Buffer, Context, Cursor};
 
 /// Adjust the scroll level so the cursor is on the screen.
+///
+/// When long lines are truncated, this also scrolls the window
+/// horizontally if the cursor is out of the visible columns.
 pub fn adjust_scroll(term: &term::Term, context: &mut Context) {
     let region = layout::get_current_window_region(term, context);
     let window = context.window_list.get_current_window_as_mut();
     let buffer = context.buffer_list.resolve_ref(window.buffer_ref);
+    let (line, column) = buffer.display_position(buffer.cursor());
+    let height = window.window_lines(&region);
+
+    if line < window.first_visible_line() {
+        window.scroll_line = line;
+    }
+
+    // Every visible line takes at least one row, so the lines before
+    // the `height` visible lines ending at the cursor can be skipped
+    // right away. Hidden lines take no rows.
+    let mut first = line;
+    let mut visible = 1;
+    while first > window.scroll_line && visible < height {
+        first -= 1;
+        if !buffer.is_line_hidden(first) {
+            visible += 1;
+        }
+    }
+    if visible == height && first > window.scroll_line {
+        window.scroll_line = first;
+    }
+
+    let wrap_width = window.wrap_width(term.columns, buffer);
+    let cursor_row = wrap_width.map_or(0, |width| column_row(buffer, line, column, width));
+    while window.scroll_line < line {
+        let rows: usize = (window.scroll_line..line)
+            .map(|line| window.line_rows(buffer, line, wrap_width))
+            .sum();
+        if rows + cursor_row < height {
+            break;
+        }
+        window.scroll_line += 1;
+    }
+
+    match wrap_width {
+        Some(_) => {}
+        None => {
+            let columns = window.visible_columns(term.columns, buffer);
+            if !columns.contains(&column) {
+                let half = (columns.end - columns.start) / 2;
+                window.hscroll = cmp::max(window.min_hscroll, column.saturating_sub(half));
+            }
+        }
+    }
+}
+
+/// Return the number of rows used to display `line` when lines are
+/// wrapped every `width` columns.
+pub fn line_rows(buffer: &Buffer, line: usize, width: usize) -> usize {
+    if buffer.is_line_hidden(line) {
+        return 0;
+    }
+    cmp::max(1, buffer.line_display_width(line).div_ceil(width))
+}
 
-    if buffer.cursor.line < window.first_visible_line() {
-        window.scroll_line = buffer.cursor.line;
+/// Return the row of `line`, starting at zero, where the display
+/// column `column` is shown when lines are wrapped every `width`
+/// columns.
+pub fn column_row(buffer: &Buffer, line: usize, column: usize, width: usize) -> usize {
+    cmp::min(
+        column / width,
+        line_rows(buffer, line, width).saturating_sub(1),
+    )
+}
+
+/// Return the byte ranges of `line` displayed with the faces of
+/// overlays, by increasing priority.
+fn overlay_faces(buffer: &Buffer, line: usize) -> Vec<(Range<usize>, Face)> {
+    let len = buffer.get_line_unchecked(line).len();
+    let start = Cursor { line, column: 0 };
+    let end = Cursor { line, column: len };
+    let mut overlays: Vec<&Overlay> = buffer
+        .overlays
+        .in_range(start, end)
+        .filter(|overlay| overlay.face.is_some())
+        .collect();
+    overlays.sort_by_key(|overlay| overlay.priority);
+    overlays
+        .into_iter()
+        .filter_map(|overlay| {
+            let from = if overlay.start.line < line {
+                0
+            } else {
+                overlay.start.column
+            };
+            let to = if overlay.end.line > line {
+                len
+            } else {
+                overlay.end.column
+            };
+            Some((from..to, overlay.face?))
+        })
+        .collect()
+}
+
+/// Return the byte ranges of `line` under the fake cursors of the
+/// buffer, with their face. A cursor at the end of the line is past
+/// its text.
+fn fake_cursor_faces(buffer: &Buffer, line: usize) -> Vec<(Range<usize>, Face)> {
+    let text = buffer.get_line_unchecked(line);
+    multiple_cursors::fake_cursor_positions(buffer)
+        .into_iter()
+        .filter(|pos| pos.line == line)
+        .map(|pos| {
+            let len = text[pos.column..].chars().next().map_or(1, char::len_utf8);
+            (pos.column..pos.column + len, face::FAKE_CURSOR)
+        })
+        .collect()
+}
+
+/// Write the display columns `columns` of `line` to the terminal,
+/// displaying the given byte ranges with their faces. A range past
+/// the end of the line is displayed as a space.
+///
+/// Tabs are expanded to spaces up to the next tab stop. Tabs at the
+/// edges of `columns` are written partially. Invisible text is left
+/// out and the strings of overlays are written with it.
+fn render_line(
+    term: &mut term::Term,
+    buffer: &Buffer,
+    line: usize,
+    columns: Range<usize>,
+    highlights: &[(Range<usize>, Face)],
+) {
+    let text = buffer.get_line_unchecked(line);
+    let default_face = Face::default();
+    let mut current_face = default_face;
+    let mut display_column = 0;
+
+    let mut render_char = |term: &mut term::Term, ch: char, face: Face| {
+        if display_column >= columns.end {
+            return;
+        }
+        let next_column = next_display_column(display_column, ch, buffer.tab_width);
+        let visible = cmp::max(display_column, columns.start)..cmp::min(next_column, columns.end);
+        display_column = next_column;
+        if visible.is_empty() {
+            return;
+        }
+
+        if face != current_face {
+            face.apply(term);
+            current_face = face;
+        }
+
+        if ch == '\t' {
+            write!(term, "{:width$}", "", width = visible.len()).unwrap();
+        } else {
+            write!(term, "{}", ch).unwrap();
+        }
+    };
+
+    for segment in buffer.display_segments(line) {
+        match segment {
+            Segment::Text { start, end } => {
+                for (idx, ch) in text[start..end].char_indices() {
+                    let column = start + idx;
+                    let face = highlights
+                        .iter()
+                        .rev()
+                        .find(|(range, _)| range.contains(&column))
+                        .map_or(default_face, |(_, face)| *face);
+                    render_char(term, ch, face);
+                }
+            }
+            Segment::String { text, face, .. } => {
+                for ch in text.chars() {
+                    render_char(term, ch, face.unwrap_or(default_face));
+                }
+            }
+        }
     }
 
-    let last_visible_line = window.last_visible_line(&region);
-    if buffer.cursor.line > last_visible_line {
-        window.scroll_line = buffer.cursor.line - window.window_lines(&region) + 1;
+    let end = highlights
+        .iter()
+        .rev()
+        .find(|(range, _)| range.start == text.len());
+    if let Some((_, face)) = end {
+        render_char(term, ' ', *face);
     }
+
+    term.reset_attr();
+}
+
+/// Write an indicator of truncated or continued lines.
+fn render_indicator(term: &mut term::Term, indicator: char) {
+    face::LINE_INDICATOR.apply(term);
+    write!(term, "{}", indicator).unwrap();
+    term.reset_attr();
+}
+
+/// A row of a window, displaying part of a buffer line.
+struct ScreenRow {
+    line: usize,
+    /// The first display column of the line shown in the row, when
+    /// lines are wrapped.
+    start: usize,
+    /// If set, the line continues in the next row.
+    continued: bool,
 }
 
+#[derive(Clone)]
 pub struct Window {
     pub scroll_line: usize,
     pub show_lines: bool,
+    /// How line numbers are displayed, if `show_lines` is set.
+    pub line_numbers: LineNumbers,
     pub show_modeline: bool,
 
+    /// If set, long lines are truncated at the right edge of the
+    /// window instead of continuing in the next rows.
+    pub truncate_lines: bool,
+    /// If set, vertical motion commands move by screen rows rather
+    /// than by buffer lines when lines are wrapped.
+    pub visual_line_mode: bool,
+    /// The number of columns the window is scrolled horizontally
+    /// when lines are truncated.
+    pub hscroll: usize,
+    /// The minimum horizontal scroll, as set by `scroll-left` and
+    /// `scroll-right`. Automatic horizontal scrolling does not go
+    /// below it.
+    pub min_hscroll: usize,
+
     pub buffer_ref: BufferRef,
+    /// The buffer shown in the window before `buffer_ref`, to go back
+    /// to when quitting the window.
+    pub previous_buffer: Option<BufferRef>,
+    /// The point of the window while another window is selected: a
+    /// marker in the buffer where it was saved. The selected window
+    /// uses the cursor of its buffer.
+    pub point: Option<(BufferRef, MarkerId)>,
 }
 impl Window {
     pub fn new(buffer_ref: BufferRef, show_modeline: bool) -> Window {
         Window {
             scroll_line: 0,
             show_lines: false,
+            line_numbers: LineNumbers::Absolute,
             show_modeline,
+            truncate_lines: false,
+            visual_line_mode: false,
+            hscroll: 0,
+            min_hscroll: 0,
             buffer_ref,
+            previous_buffer: None,
+            point: None,
         }
     }
 
-    fn get_pad_width(&self, region: &layout::Region) -> usize {
+    /// Show `buffer_ref` in the window, from the beginning.
+    pub fn set_buffer(&mut self, buffer_ref: BufferRef) {
+        self.previous_buffer = Some(self.buffer_ref);
+        self.buffer_ref = buffer_ref;
+        self.scroll_line = 0;
+        self.hscroll = 0;
+        self.min_hscroll = 0;
+    }
+
+    /// Return the width of the gutter, with the fringe if the buffer
+    /// has fringe marks and the line numbers if they are shown.
+    fn get_pad_width(&self, buffer: &Buffer) -> usize {
+        let mut width = 0;
+        if !buffer.fringe.is_empty() {
+            width += 1;
+        }
         if self.show_lines {
-            let last_linenum_width = format!("{}", self.scroll_line + region.height).len();
-            last_linenum_width + 1
+            // Leave room for the last line of the buffer, so the width
+            // does not change while scrolling.
+            width += gutter::digits(buffer.lines_count()) + 1;
+        }
+        width
+    }
+
+    /// Return the number of columns available to display text.
+    pub fn text_width(&self, term_columns: usize, buffer: &Buffer) -> usize {
+        term_columns.saturating_sub(self.get_pad_width(buffer))
+    }
+
+    /// Return the number of display columns of each row if long
+    /// lines are wrapped, or `None` if they are truncated.
+    ///
+    /// The last column of the window is kept for the continuation
+    /// indicator.
+    pub fn wrap_width(&self, term_columns: usize, buffer: &Buffer) -> Option<usize> {
+        if self.truncate_lines {
+            None
+        } else {
+            Some(cmp::max(
+                1,
+                self.text_width(term_columns, buffer).saturating_sub(1),
+            ))
+        }
+    }
+
+    /// Return the display columns that are visible when long lines
+    /// are truncated.
+    ///
+    /// The first column shows a truncation indicator instead of text
+    /// if the window is scrolled horizontally, and the last column is
+    /// kept for the truncation indicator of long lines.
+    pub fn visible_columns(&self, term_columns: usize, buffer: &Buffer) -> Range<usize> {
+        let width = cmp::max(2, self.text_width(term_columns, buffer));
+        let start = if self.hscroll > 0 {
+            self.hscroll + 1
         } else {
             0
+        };
+        start..self.hscroll + width - 1
+    }
+
+    /// Return the number of rows used to display `line`, wrapped
+    /// every `wrap_width` columns if it is set.
+    fn line_rows(&self, buffer: &Buffer, line: usize, wrap_width: Option<usize>) -> usize {
+        match wrap_width {
+            Some(width) => line_rows(buffer, line, width),
+            None if buffer.is_line_hidden(line) => 0,
+            None => 1,
+        }
+    }
+
+    /// Return the rows to display in the window.
+    fn screen_rows(
+        &self,
+        buffer: &Buffer,
+        term_columns: usize,
+        region: &layout::Region,
+    ) -> Vec<ScreenRow> {
+        let height = self.window_lines(region);
+        let wrap_width = self.wrap_width(term_columns, buffer);
+        let mut rows = vec![];
+
+        for line in self.scroll_line..buffer.lines_count() {
+            let count = self.line_rows(buffer, line, wrap_width);
+            for row in 0..count {
+                if rows.len() == height {
+                    return rows;
+                }
+                rows.push(ScreenRow {
+                    line,
+                    start: wrap_width.map_or(0, |width| row * width),
+                    continued: row + 1 < count,
+                });
+            }
+        }
+        rows
+    }
+
+    /// Return the first and the last lines shown in the window.
+    pub fn visible_lines(
+        &self,
+        buffer: &Buffer,
+        term_columns: usize,
+        region: &layout::Region,
+    ) -> RangeInclusive<usize> {
+        let rows = self.screen_rows(buffer, term_columns, region);
+        match (rows.first(), rows.last()) {
+            (Some(first), Some(last)) => first.line..=last.line,
+            _ => self.scroll_line..=self.scroll_line,
+        }
+    }
+
+    /// Return the buffer position shown at `row` and `column` of the
+    /// window, both starting at zero.
+    ///
+    /// Positions after the end of a row go to the last character of
+    /// the row, and rows after the end of the buffer to its end.
+    pub fn position_at(
+        &self,
+        buffer: &Buffer,
+        term_columns: usize,
+        region: &layout::Region,
+        row: usize,
+        column: usize,
+    ) -> Cursor {
+        let rows = self.screen_rows(buffer, term_columns, region);
+        let screen_row = match rows.get(row) {
+            Some(screen_row) => screen_row,
+            None => return buffer.end_position(),
+        };
+        let column = column.saturating_sub(self.get_pad_width(buffer));
+        let display_column = match self.wrap_width(term_columns, buffer) {
+            Some(width) => screen_row.start + cmp::min(column, width - 1),
+            None => {
+                let columns = self.visible_columns(term_columns, buffer);
+                (self.hscroll + column).clamp(columns.start, columns.end - 1)
+            }
+        };
+        Cursor {
+            line: screen_row.line,
+            column: buffer.column_at_display(screen_row.line, display_column),
         }
     }
 
     fn render_cursor(&self, term: &mut term::Term, context: &Context, region: &layout::Region) {
         let buffer = context.buffer_list.resolve_ref(self.buffer_ref);
+        let (line, column) = buffer.display_position(buffer.cursor());
 
-        let screen_line = buffer.cursor.line.checked_sub(self.scroll_line);
+        if line < self.scroll_line {
+            return;
+        }
 
-        if let Some(row) = screen_line {
-            term.set_cursor(
-                region.top + row + 1,
-                buffer.cursor.column + self.get_pad_width(region) + 1,
-            );
+        let wrap_width = self.wrap_width(term.columns, buffer);
+        let rows: usize = (self.scroll_line..line)
+            .map(|line| self.line_rows(buffer, line, wrap_width))
+            .sum();
+        let (row, column) = match wrap_width {
+            Some(width) => {
+                let cursor_row = column_row(buffer, line, column, width);
+                (rows + cursor_row, column - cursor_row * width)
+            }
+            None => {
+                let columns = self.visible_columns(term.columns, buffer);
+                let column = column.clamp(columns.start, columns.end);
+                (rows, column - self.hscroll)
+            }
+        };
+
+        let last_column = self.text_width(term.columns, buffer).saturating_sub(1);
+        term.set_cursor(
+            region.top + row + 1,
+            cmp::min(column, last_column) + self.get_pad_width(buffer) + 1,
+        );
+    }
+
+    /// Render the gutter of `row`. Only the first row of a line shows
+    /// its number and fringe mark.
+    fn render_gutter(&self, term: &mut term::Term, buffer: &Buffer, row: &ScreenRow) {
+        if !buffer.fringe.is_empty() {
+            match buffer.fringe.mark_at(row.line).filter(|_| row.start == 0) {
+                Some(mark) => {
+                    mark.face.apply(term);
+                    write!(term, "{}", mark.ch).unwrap();
+                }
+                None => write!(term, " ").unwrap(),
+            }
         }
+
+        if self.show_lines {
+            let width = gutter::digits(buffer.lines_count());
+            if row.start == 0 {
+                let current = buffer.cursor().line;
+                if row.line == current {
+                    face::LINE_NUMBER_CURRENT.apply(term);
+                } else {
+                    face::LINE_NUMBER.apply(term);
+                }
+                let number = self.line_numbers.number(row.line, current);
+                write!(term, "{:>width$} ", number, width = width).unwrap();
+            } else {
+                write!(term, "{:width$} ", "", width = width).unwrap();
+            }
+        }
+
+        term.reset_attr();
     }
 
     fn render_window(
@@ -70,64 +488,84 @@ impl Window {
         region: &layout::Region,
         _flashed: bool,
     ) {
-        let offset = self.get_pad_width(region);
-        let window_columns = term.columns - offset;
-
         let buffer = context.buffer_list.resolve_ref(self.buffer_ref);
+        let rows = self.screen_rows(buffer, term.columns, region);
+        let wrap_width = self.wrap_width(term.columns, buffer);
 
         // Main window
-        for row in 0..self.window_lines(region) {
-            let linenum = row + self.scroll_line;
-
-            let (line_content, line_present) = if let Some(line) = buffer.get_line(linenum) {
-                (&line[..cmp::min(line.len(), window_columns)], true)
-            } else {
-                ("", false)
+        for index in 0..self.window_lines(region) {
+            // Writing up to the last column of the terminal leaves the
+            // cursor there, so erase the row before writing it.
+            term.erase_line(ErasePart::All);
+
+            let row = match rows.get(index) {
+                Some(row) => row,
+                None => {
+                    term.csi("E");
+                    continue;
+                }
             };
-
-            if self.show_lines && line_present {
-                term.fg(240);
-                write!(term, "{:width$} ", linenum + 1, width = offset - 1).unwrap();
-            } else {
-                write!(term, "{:width$}", "", width = offset).unwrap();
+            let linenum = row.line;
+
+            self.render_gutter(term, buffer, row);
+
+            let mut highlights = overlay_faces(buffer, linenum);
+            highlights.extend(fake_cursor_faces(buffer, linenum));
+
+            match wrap_width {
+                Some(width) => {
+                    render_line(
+                        term,
+                        buffer,
+                        linenum,
+                        row.start..row.start + width,
+                        &highlights,
+                    );
+                    if row.continued {
+                        render_indicator(term, '\\');
+                    }
+                }
+                None => {
+                    let columns = self.visible_columns(term.columns, buffer);
+                    if self.hscroll > 0 {
+                        render_indicator(term, '$');
+                    }
+                    let truncated = buffer.line_display_width(linenum) > columns.end;
+                    render_line(term, buffer, linenum, columns, &highlights);
+                    if truncated {
+                        render_indicator(term, '$');
+                    }
+                }
             }
 
-            term.reset_attr();
-            if let Some(highlight) = &buffer.highlight {
-                let out = line_content.replace(highlight, &format!("\x1b[7m{}\x1b[m", highlight));
-                term.write_line(out);
-            } else {
-                term.write_line(line_content);
-            };
+            term.csi("E");
         }
 
         term.reset_attr();
     }
 
-    fn render_modeline(&self, term: &mut term::Term, context: &Context, region: &layout::Region) {
-        let buffer = &context.buffer_list.resolve_ref(self.buffer_ref);
-
-        term.fg(15);
-        term.bg(236);
-
-        let scroll_line = self.scroll_line;
-
-        let buffer_progress = if scroll_line == 0 {
-            "Top".to_string()
-        } else if self.last_visible_line(region) >= buffer.lines_count() - 1 {
-            "Bot".to_string()
+    fn render_modeline(
+        &self,
+        term: &mut term::Term,
+        context: &Context,
+        region: &layout::Region,
+        selected: bool,
+    ) {
+        let face = if selected {
+            context.modeline.active_face
         } else {
-            format!("{}%", 100 * (buffer.cursor.line + 1) / buffer.lines_count())
+            context.modeline.inactive_face
         };
+        face.apply(term);
 
         // On MacOsX's terminal, when you erase a line it won't fill the
         // full line with the current attributes, unlike ITerm. So we use
         // `write_line` to pad the string with spaces.
-        term.write_line(format!(
-            "  {}  {} L{}",
-            buffer.filename.as_ref().unwrap_or(&"*scratch*".to_string()),
-            buffer_progress,
-            buffer.cursor.line + 1
+        term.write_line(modeline::format_modeline(
+            context,
+            self,
+            term.columns,
+            region,
         ));
     }
 
@@ -143,14 +581,19 @@ impl Window {
         }
     }
 
-    /// Return the last visible line in the current window.
-    ///
-    /// Note that this can be greater than the number of lines in the
-    /// current buffer when close to the end of the buffer, as this
-    /// counts the space for lines rather than the buffer lines
-    /// itself.
-    fn last_visible_line(&self, region: &layout::Region) -> usize {
-        self.scroll_line + self.window_lines(region) - 1
+    /// Check if the end of the accessible portion of the buffer is
+    /// visible in the window.
+    pub fn is_end_visible(
+        &self,
+        buffer: &Buffer,
+        term_columns: usize,
+        region: &layout::Region,
+    ) -> bool {
+        let rows = self.screen_rows(buffer, term_columns, region);
+        match rows.last() {
+            Some(row) => row.line == buffer.point_max().line && !row.continued,
+            None => true,
+        }
     }
 
     // last: if this window is being rendered over the last
@@ -160,34 +603,44 @@ impl Window {
         context: &Context,
         region: &layout::Region,
         flashed: bool,
+        selected: bool,
     ) {
         self.render_window(term, context, region, flashed);
         if self.show_modeline {
-            self.render_modeline(term, context, region);
+            self.render_modeline(term, context, region, selected);
         }
     }
 }
 
 fn render_screen(term: &mut term::Term, context: &Context, flashed: bool) -> std::io::Result<()> {
-    let main_window = &context.window_list.main;
-    let minibuffer_window = &context.window_list.minibuffer;
+    let window_list = &context.window_list;
+    let minibuffer_window = &window_list.minibuffer;
 
     term.hide_cursor();
 
     let layout = layout::get_layout(term, context);
 
-    term.set_cursor(1, 1);
-
-    main_window.render(term, context, &layout.main_window_region, flashed);
-    context
-        .window_list
-        .minibuffer
-        .render(term, context, &layout.minibuffer_region, flashed);
+    for (i, (window, region)) in window_list
+        .windows
+        .iter()
+        .zip(&layout.window_regions)
+        .enumerate()
+    {
+        term.set_cursor(region.top + 1, 1);
+        let selected = i == window_list.selected && !window_list.minibuffer_focused;
+        window.render(term, context, region, flashed, selected);
+    }
+    term.set_cursor(layout.minibuffer_region.top + 1, 1);
+    minibuffer_window.render(term, context, &layout.minibuffer_region, flashed, true);
 
-    if context.window_list.minibuffer_focused {
+    if window_list.minibuffer_focused {
         minibuffer_window.render_cursor(term, context, &layout.minibuffer_region);
     } else {
-        main_window.render_cursor(term, context, &layout.main_window_region);
+        window_list.selected_window().render_cursor(
+            term,
+            context,
+            &layout.window_regions[window_list.selected],
+        );
     }
 
     term.show_cursor();
@@ -215,7 +668,129 @@ pub fn ding(term: &mut term::Term, context: &Context) -> std::io::Result<()> {
     Ok(())
 }
 
-/// Show a message in the minibuffer.
+/// Save the cursor of the selected window in its point marker, so
+/// that it follows the edits made from other windows.
+fn save_window_point(context: &mut Context) {
+    let window = &mut context.window_list.windows[context.window_list.selected];
+    if let Some((buffer_ref, id)) = window.point {
+        if buffer_ref == window.buffer_ref {
+            let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+            buffer.markers.set(id, buffer.cursor());
+            return;
+        }
+        // The window shows another buffer since its point was saved.
+        context
+            .buffer_list
+            .resolve_ref_as_mut(buffer_ref)
+            .markers
+            .remove(id);
+    }
+    let buffer = context.buffer_list.resolve_ref_as_mut(window.buffer_ref);
+    window.point = Some((
+        window.buffer_ref,
+        buffer.markers.add(buffer.cursor(), false),
+    ));
+}
+
+/// Move the cursor of the buffer of the selected window to the point
+/// of the window, if it was saved.
+pub fn restore_window_point(context: &mut Context) {
+    let window = &context.window_list.windows[context.window_list.selected];
+    if let Some((buffer_ref, id)) = window.point {
+        if buffer_ref == window.buffer_ref {
+            let buffer = context.buffer_list.resolve_ref_as_mut(buffer_ref);
+            if let Some(pos) = buffer.markers.get(id) {
+                buffer.set_cursor(pos);
+            }
+        }
+    }
+}
+
+/// Select the window at `index` in the window list. The point of the
+/// previously selected window is saved, and the one of the new window
+/// restored.
+pub fn select_window(context: &mut Context, index: usize) {
+    if index == context.window_list.selected {
+        return;
+    }
+    save_window_point(context);
+    context.window_list.selected = index;
+    restore_window_point(context);
+}
+
+/// Delete the point marker of `window`, when the window is deleted.
+pub fn delete_window_point(context: &mut Context, window: &Window) {
+    if let Some((buffer_ref, id)) = window.point {
+        context
+            .buffer_list
+            .resolve_ref_as_mut(buffer_ref)
+            .markers
+            .remove(id);
+    }
+}
+
+/// Show a message in the minibuffer and log it in the messages
+/// buffer.
 pub fn message<S: AsRef<str>>(context: &mut Context, str: S) {
+    messages::log(context, str.as_ref());
     context.buffer_list.minibuffer.set(str);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gutter::FringeMark;
+
+    #[test]
+    fn wrap_lines_in_rows() {
+        let buffer = Buffer::from_string("abcdefghij\n\n\tx");
+        assert_eq!(line_rows(&buffer, 0, 4), 3);
+        assert_eq!(line_rows(&buffer, 1, 4), 1);
+        assert_eq!(line_rows(&buffer, 2, 4), 3);
+        assert_eq!(column_row(&buffer, 0, 5, 4), 1);
+        assert_eq!(column_row(&buffer, 0, 10, 5), 1, "end of a full row");
+    }
+
+    #[test]
+    fn find_positions_on_screen() {
+        let buffer = Buffer::from_string("abcdefghij\nxy");
+        let region = layout::Region { top: 0, height: 5 };
+        let mut window = Window::new(BufferRef::main_window(), true);
+        let at = |line, column| Cursor { line, column };
+
+        // Rows of 5 columns, plus the continuation indicator.
+        assert_eq!(window.position_at(&buffer, 6, &region, 1, 2), at(0, 7));
+        assert_eq!(window.position_at(&buffer, 6, &region, 0, 9), at(0, 4));
+        assert_eq!(window.position_at(&buffer, 6, &region, 2, 9), at(1, 2));
+        assert_eq!(window.position_at(&buffer, 6, &region, 3, 0), at(1, 2));
+
+        window.truncate_lines = true;
+        window.hscroll = 4;
+        assert_eq!(window.position_at(&buffer, 6, &region, 0, 2), at(0, 6));
+    }
+
+    #[test]
+    fn size_the_gutter_for_the_whole_buffer() {
+        let mut buffer = Buffer::from_string(&"x\n".repeat(150));
+        let mut window = Window::new(BufferRef::main_window(), true);
+        assert_eq!(window.text_width(80, &buffer), 80);
+
+        window.show_lines = true;
+        assert_eq!(window.text_width(80, &buffer), 76);
+        window.scroll_line = 140;
+        assert_eq!(window.text_width(80, &buffer), 76);
+
+        buffer.fringe.add(FringeMark {
+            source: "test",
+            line: 3,
+            ch: '!',
+            face: Face::default(),
+            priority: 0,
+        });
+        assert_eq!(window.text_width(80, &buffer), 75);
+
+        // The gutter is wider than the terminal.
+        assert_eq!(window.text_width(4, &buffer), 0);
+        assert_eq!(window.wrap_width(4, &buffer), Some(1));
+    }
+}
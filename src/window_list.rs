@@ -1,17 +1,31 @@
+use crate::buffer_list::BufferRef;
 use crate::Window;
 
 pub struct WindowList {
     pub minibuffer_focused: bool,
-    pub main: Window,
+    /// The windows of the frame, from top to bottom. There is always
+    /// at least one.
+    pub windows: Vec<Window>,
+    /// The index of the selected window in `windows`.
+    pub selected: usize,
     pub minibuffer: Window,
 }
 
 impl WindowList {
+    pub fn new(main: Window, minibuffer: Window) -> WindowList {
+        WindowList {
+            minibuffer_focused: false,
+            windows: vec![main],
+            selected: 0,
+            minibuffer,
+        }
+    }
+
     pub fn get_current_window(&self) -> &Window {
         if self.minibuffer_focused {
             &self.minibuffer
         } else {
-            &self.main
+            &self.windows[self.selected]
         }
     }
 
@@ -19,7 +33,72 @@ impl WindowList {
         if self.minibuffer_focused {
             &mut self.minibuffer
         } else {
-            &mut self.main
+            &mut self.windows[self.selected]
+        }
+    }
+
+    /// Return the selected window, ignoring the minibuffer.
+    pub fn selected_window(&self) -> &Window {
+        &self.windows[self.selected]
+    }
+
+    /// Split the selected window in two, showing the same buffer. The
+    /// selected window is the one on top.
+    pub fn split_selected(&mut self) {
+        let window = &self.windows[self.selected];
+        let mut new_window = Window::new(window.buffer_ref, true);
+        new_window.scroll_line = window.scroll_line;
+        new_window.truncate_lines = window.truncate_lines;
+        new_window.visual_line_mode = window.visual_line_mode;
+        new_window.show_lines = window.show_lines;
+        new_window.line_numbers = window.line_numbers;
+        self.windows.insert(self.selected + 1, new_window);
+    }
+
+    /// Delete the selected window, selecting the next one, and return
+    /// it. Return None if it is the only window.
+    pub fn delete_selected(&mut self) -> Option<Window> {
+        if self.windows.len() == 1 {
+            return None;
+        }
+        let window = self.windows.remove(self.selected);
+        if self.selected == self.windows.len() {
+            self.selected = 0;
+        }
+        Some(window)
+    }
+
+    /// Delete all windows but the selected one, and return them.
+    pub fn delete_others(&mut self) -> Vec<Window> {
+        let window = self.windows.swap_remove(self.selected);
+        self.selected = 0;
+        std::mem::replace(&mut self.windows, vec![window])
+    }
+
+    /// Return the index of the window after the selected one,
+    /// cyclically.
+    pub fn next_window(&self) -> usize {
+        (self.selected + 1) % self.windows.len()
+    }
+
+    /// Show `buffer_ref` in a window other than the selected one,
+    /// splitting the selected window if it is the only window, and
+    /// return the index of that window.
+    ///
+    /// A window that already shows the buffer is reused.
+    pub fn display_buffer(&mut self, buffer_ref: BufferRef) -> usize {
+        if let Some(index) = self.windows.iter().position(|w| w.buffer_ref == buffer_ref) {
+            return index;
+        }
+
+        if self.windows.len() == 1 {
+            let index = self.selected + 1;
+            self.windows.insert(index, Window::new(buffer_ref, true));
+            index
+        } else {
+            let index = (self.selected + 1) % self.windows.len();
+            self.windows[index].set_buffer(buffer_ref);
+            index
         }
     }
 }